@@ -0,0 +1,33 @@
+-- Move access control from environment variables into the database
+CREATE TABLE IF NOT EXISTS roles (
+	name TEXT PRIMARY KEY,
+	description TEXT NOT NULL
+);
+
+INSERT INTO roles
+(name, description)
+VALUES
+	('member', 'Member of Warwick Barbell'),
+	('election_admin', 'Election administrator'),
+	('site_admin', 'Site administrator');
+
+CREATE TABLE IF NOT EXISTS user_roles (
+	warwick_id INTEGER NOT NULL,
+	role TEXT NOT NULL,
+	PRIMARY KEY (warwick_id, role),
+	CONSTRAINT fk_roles
+	FOREIGN KEY(role)
+	REFERENCES roles(name)
+	ON DELETE CASCADE
+);
+
+-- Record every change to the roles a user holds
+-- The `actor_id` is NULL for changes made by the system, such as importing roles
+CREATE TABLE IF NOT EXISTS role_audit_log (
+	id SERIAL PRIMARY KEY,
+	actor_id INTEGER,
+	warwick_id INTEGER NOT NULL,
+	role TEXT NOT NULL,
+	action TEXT NOT NULL CHECK (action IN ('grant', 'revoke')),
+	timestamp BIGINT NOT NULL
+);
@@ -0,0 +1,11 @@
+-- Track club memberships and when they expire, rather than a fixed list of members
+CREATE TABLE IF NOT EXISTS memberships (
+	id SERIAL PRIMARY KEY,
+	warwick_id INTEGER NOT NULL,
+	membership_type TEXT NOT NULL,
+	valid_from DATE NOT NULL,
+	valid_to DATE NOT NULL,
+	source TEXT NOT NULL,
+	UNIQUE (warwick_id, membership_type, valid_from),
+	CHECK (valid_from <= valid_to)
+);
@@ -0,0 +1,13 @@
+-- Allow users to create tokens for accessing the website from scripts
+-- Only a hash of each token is stored, the token itself is shown once when it is created
+CREATE TABLE IF NOT EXISTS api_tokens (
+	id SERIAL PRIMARY KEY,
+	warwick_id INTEGER NOT NULL,
+	owner_name TEXT NOT NULL,
+	name TEXT NOT NULL,
+	token_hash TEXT NOT NULL UNIQUE,
+	scopes TEXT[] NOT NULL,
+	created_at BIGINT NOT NULL,
+	last_used BIGINT,
+	revoked BOOLEAN NOT NULL DEFAULT FALSE
+);
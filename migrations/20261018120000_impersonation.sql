@@ -0,0 +1,8 @@
+-- Record whenever a site administrator starts or stops viewing the website as another user
+CREATE TABLE IF NOT EXISTS impersonation_log (
+	id SERIAL PRIMARY KEY,
+	admin_id INTEGER NOT NULL,
+	warwick_id INTEGER NOT NULL,
+	action TEXT NOT NULL CHECK (action IN ('start', 'stop')),
+	timestamp BIGINT NOT NULL
+);
@@ -0,0 +1,59 @@
+-- Keep every lift a user records, rather than overwriting their personal bests
+CREATE TABLE IF NOT EXISTS lift_records (
+	id SERIAL PRIMARY KEY,
+	warwick_id INTEGER NOT NULL,
+	lift TEXT NOT NULL CHECK (lift IN ('squat', 'bench', 'deadlift', 'snatch', 'clean_and_jerk')),
+	weight REAL NOT NULL CHECK (weight > 0),
+	reps INTEGER CHECK (reps > 0),
+	recorded_on DATE NOT NULL,
+	note TEXT
+);
+
+CREATE INDEX IF NOT EXISTS lift_records_warwick_id ON lift_records (warwick_id, lift);
+
+-- Move the existing personal bests into the history, as we don't know when they were set
+INSERT INTO lift_records (warwick_id, lift, weight, recorded_on, note)
+SELECT warwick_id, lift, weight, CURRENT_DATE, 'Recorded before history was kept'
+FROM personal_bests
+CROSS JOIN LATERAL (
+	VALUES
+		('squat', squat),
+		('bench', bench),
+		('deadlift', deadlift),
+		('snatch', snatch),
+		('clean_and_jerk', clean_and_jerk)
+) AS lifts (lift, weight)
+WHERE weight IS NOT NULL AND weight > 0;
+
+-- The `personal_bests` table now only stores how each user appears on the boards
+ALTER TABLE personal_bests DROP COLUMN squat;
+ALTER TABLE personal_bests DROP COLUMN bench;
+ALTER TABLE personal_bests DROP COLUMN deadlift;
+ALTER TABLE personal_bests DROP COLUMN snatch;
+ALTER TABLE personal_bests DROP COLUMN clean_and_jerk;
+
+-- Derive each user's personal bests from the heaviest single they have recorded for each lift
+CREATE OR REPLACE VIEW personal_best_board AS
+SELECT
+	pb.warwick_id,
+	pb.name,
+	best.squat,
+	best.bench,
+	best.deadlift,
+	best.snatch,
+	best.clean_and_jerk,
+	pb.show_pl,
+	pb.show_wl
+FROM personal_bests pb
+LEFT JOIN (
+	SELECT
+		warwick_id,
+		MAX(weight) FILTER (WHERE lift = 'squat') AS squat,
+		MAX(weight) FILTER (WHERE lift = 'bench') AS bench,
+		MAX(weight) FILTER (WHERE lift = 'deadlift') AS deadlift,
+		MAX(weight) FILTER (WHERE lift = 'snatch') AS snatch,
+		MAX(weight) FILTER (WHERE lift = 'clean_and_jerk') AS clean_and_jerk
+	FROM lift_records
+	WHERE reps IS NULL OR reps = 1
+	GROUP BY warwick_id
+) best ON best.warwick_id = pb.warwick_id;
@@ -0,0 +1,48 @@
+-- Record the category and weight class each user competes in
+ALTER TABLE personal_bests ADD COLUMN sex TEXT CHECK (sex IN ('male', 'female'));
+ALTER TABLE personal_bests ADD COLUMN weight_class TEXT;
+
+-- Keep every bodyweight a user records, so the boards can use the most recent
+CREATE TABLE IF NOT EXISTS bodyweights (
+	id SERIAL PRIMARY KEY,
+	warwick_id INTEGER NOT NULL,
+	weight REAL NOT NULL CHECK (weight > 0),
+	recorded_on DATE NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS bodyweights_warwick_id ON bodyweights (warwick_id, recorded_on);
+
+DROP VIEW IF EXISTS personal_best_board;
+
+CREATE VIEW personal_best_board AS
+SELECT
+	pb.warwick_id,
+	pb.name,
+	best.squat,
+	best.bench,
+	best.deadlift,
+	best.snatch,
+	best.clean_and_jerk,
+	pb.show_pl,
+	pb.show_wl,
+	latest.weight AS bodyweight,
+	pb.sex,
+	pb.weight_class
+FROM personal_bests pb
+LEFT JOIN (
+	SELECT
+		warwick_id,
+		MAX(weight) FILTER (WHERE lift = 'squat') AS squat,
+		MAX(weight) FILTER (WHERE lift = 'bench') AS bench,
+		MAX(weight) FILTER (WHERE lift = 'deadlift') AS deadlift,
+		MAX(weight) FILTER (WHERE lift = 'snatch') AS snatch,
+		MAX(weight) FILTER (WHERE lift = 'clean_and_jerk') AS clean_and_jerk
+	FROM lift_records
+	WHERE reps IS NULL OR reps = 1
+	GROUP BY warwick_id
+) best ON best.warwick_id = pb.warwick_id
+LEFT JOIN (
+	SELECT DISTINCT ON (warwick_id) warwick_id, weight
+	FROM bodyweights
+	ORDER BY warwick_id, recorded_on DESC, id DESC
+) latest ON latest.warwick_id = pb.warwick_id;
@@ -0,0 +1,59 @@
+-- Allow coaches to verify the lifts users record, with optional video evidence
+INSERT INTO roles
+(name, description)
+VALUES
+	('coach', 'Coach who verifies recorded lifts')
+ON CONFLICT (name) DO NOTHING;
+
+-- Existing lifts were self-reported, so they are left pending
+ALTER TABLE lift_records ADD COLUMN video_url TEXT;
+ALTER TABLE lift_records ADD COLUMN status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'verified', 'rejected'));
+ALTER TABLE lift_records ADD COLUMN reviewed_by INTEGER;
+ALTER TABLE lift_records ADD COLUMN reviewed_at BIGINT;
+
+CREATE INDEX IF NOT EXISTS lift_records_status ON lift_records (status) WHERE status = 'pending';
+
+DROP VIEW IF EXISTS personal_best_board;
+
+-- Rejected lifts no longer count, and `verified` lists the lifts whose best has been verified
+CREATE VIEW personal_best_board AS
+SELECT
+	pb.warwick_id,
+	pb.name,
+	best.squat,
+	best.bench,
+	best.deadlift,
+	best.snatch,
+	best.clean_and_jerk,
+	pb.show_pl,
+	pb.show_wl,
+	latest.weight AS bodyweight,
+	pb.sex,
+	pb.weight_class,
+	COALESCE(best.verified, ARRAY[]::TEXT[]) AS verified
+FROM personal_bests pb
+LEFT JOIN (
+	SELECT
+		warwick_id,
+		MAX(weight) FILTER (WHERE lift = 'squat') AS squat,
+		MAX(weight) FILTER (WHERE lift = 'bench') AS bench,
+		MAX(weight) FILTER (WHERE lift = 'deadlift') AS deadlift,
+		MAX(weight) FILTER (WHERE lift = 'snatch') AS snatch,
+		MAX(weight) FILTER (WHERE lift = 'clean_and_jerk') AS clean_and_jerk,
+		ARRAY_AGG(DISTINCT lift) FILTER (WHERE verified_best) AS verified
+	FROM (
+		SELECT
+			warwick_id,
+			lift,
+			weight,
+			status = 'verified' AND weight = MAX(weight) OVER (PARTITION BY warwick_id, lift) AS verified_best
+		FROM lift_records
+		WHERE (reps IS NULL OR reps = 1) AND status <> 'rejected'
+	) singles
+	GROUP BY warwick_id
+) best ON best.warwick_id = pb.warwick_id
+LEFT JOIN (
+	SELECT DISTINCT ON (warwick_id) warwick_id, weight
+	FROM bodyweights
+	ORDER BY warwick_id, recorded_on DESC, id DESC
+) latest ON latest.warwick_id = pb.warwick_id;
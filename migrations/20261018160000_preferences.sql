@@ -0,0 +1,6 @@
+-- Store each user's preferences, such as the unit they enter and view weights in
+-- Weights are always stored in kilograms
+CREATE TABLE IF NOT EXISTS preferences (
+	warwick_id INTEGER PRIMARY KEY,
+	unit TEXT NOT NULL DEFAULT 'kg' CHECK (unit IN ('kg', 'lb'))
+);
@@ -0,0 +1,5 @@
+-- Record how hard each set was, for estimating one rep maxes from an RPE table
+ALTER TABLE lift_records ADD COLUMN rpe REAL CHECK (rpe >= 1 AND rpe <= 10);
+
+-- Let each user choose how their one rep maxes are estimated
+ALTER TABLE preferences ADD COLUMN formula TEXT NOT NULL DEFAULT 'epley' CHECK (formula IN ('epley', 'brzycki', 'rpe'));
@@ -0,0 +1,23 @@
+-- Keep the lifts that can be recorded in a catalogue, so new lifts and boards can be added
+-- without a migration for each of them
+CREATE TABLE IF NOT EXISTS lifts (
+	name TEXT PRIMARY KEY CHECK (name ~ '^[a-z][a-z0-9_]*$'),
+	display_name TEXT NOT NULL,
+	abbreviation TEXT NOT NULL,
+	board TEXT NOT NULL,
+	position INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT INTO lifts
+(name, display_name, abbreviation, board, position)
+VALUES
+	('squat', 'Squat', 'SQ', 'Powerlifting', 1),
+	('bench', 'Bench', 'BP', 'Powerlifting', 2),
+	('deadlift', 'Deadlift', 'DL', 'Powerlifting', 3),
+	('snatch', 'Snatch', 'SN', 'Weightlifting', 4),
+	('clean_and_jerk', 'Clean and Jerk', 'CJ', 'Weightlifting', 5)
+ON CONFLICT (name) DO NOTHING;
+
+-- Recorded lifts must now be in the catalogue instead of a fixed list
+ALTER TABLE lift_records DROP CONSTRAINT IF EXISTS lift_records_lift_check;
+ALTER TABLE lift_records ADD CONSTRAINT lift_records_lift_fkey FOREIGN KEY (lift) REFERENCES lifts (name);
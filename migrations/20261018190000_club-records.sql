@@ -0,0 +1,12 @@
+-- Keep the category and weight class each lift was performed in, so club records stay in the
+-- class they were set in after the lifter changes class or graduates
+ALTER TABLE lift_records ADD COLUMN sex TEXT CHECK (sex IN ('male', 'female'));
+ALTER TABLE lift_records ADD COLUMN weight_class TEXT;
+
+-- Existing lifts can only be assumed to have been performed in the lifter's current class
+UPDATE lift_records lr
+SET sex = pb.sex, weight_class = pb.weight_class
+FROM personal_bests pb
+WHERE pb.warwick_id = lr.warwick_id;
+
+CREATE INDEX IF NOT EXISTS lift_records_class ON lift_records (lift, sex, weight_class, recorded_on);
@@ -0,0 +1,10 @@
+-- Let election administrators add positions without choosing identifiers themselves
+ALTER TABLE exec_positions ALTER COLUMN id ADD GENERATED BY DEFAULT AS IDENTITY (START WITH 1000000);
+
+-- Nominations are removed along with their position or candidate
+DELETE FROM nominations n
+WHERE NOT EXISTS (SELECT 1 FROM exec_positions p WHERE p.id = n.position_id)
+OR NOT EXISTS (SELECT 1 FROM candidates c WHERE c.warwick_id = n.warwick_id);
+
+ALTER TABLE nominations ADD CONSTRAINT nominations_position_id_fkey FOREIGN KEY (position_id) REFERENCES exec_positions (id) ON DELETE CASCADE;
+ALTER TABLE nominations ADD CONSTRAINT nominations_warwick_id_fkey FOREIGN KEY (warwick_id) REFERENCES candidates (warwick_id) ON DELETE CASCADE;
@@ -0,0 +1,38 @@
+-- Keep each year's election separately, rather than removing the previous one
+CREATE TABLE elections (
+	id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+	name TEXT NOT NULL,
+	held_on DATE NOT NULL,
+	status TEXT NOT NULL DEFAULT 'current' CHECK (status IN ('current', 'archived'))
+);
+
+-- Only one election can be run at a time
+CREATE UNIQUE INDEX elections_current_idx ON elections (status) WHERE status = 'current';
+
+-- Everything so far belongs to the 2022 AGM, which has finished
+INSERT INTO elections (name, held_on, status) VALUES ('2022 AGM', '2022-03-01', 'archived');
+
+ALTER TABLE exec_positions ADD COLUMN election_id INTEGER REFERENCES elections (id);
+UPDATE exec_positions SET election_id = (SELECT id FROM elections), open = FALSE;
+ALTER TABLE exec_positions ALTER COLUMN election_id SET NOT NULL;
+ALTER TABLE exec_positions ADD CONSTRAINT exec_positions_id_election_id_key UNIQUE (id, election_id);
+
+-- Candidates can stand again in later elections
+ALTER TABLE nominations DROP CONSTRAINT nominations_position_id_fkey;
+ALTER TABLE nominations DROP CONSTRAINT nominations_warwick_id_fkey;
+
+ALTER TABLE candidates ADD COLUMN election_id INTEGER REFERENCES elections (id);
+UPDATE candidates SET election_id = (SELECT id FROM elections);
+ALTER TABLE candidates ALTER COLUMN election_id SET NOT NULL;
+ALTER TABLE candidates DROP CONSTRAINT candidates_pkey;
+ALTER TABLE candidates ADD PRIMARY KEY (election_id, warwick_id);
+
+ALTER TABLE nominations ADD COLUMN election_id INTEGER;
+UPDATE nominations SET election_id = (SELECT id FROM elections);
+ALTER TABLE nominations ALTER COLUMN election_id SET NOT NULL;
+ALTER TABLE nominations ADD CONSTRAINT nominations_position_id_fkey FOREIGN KEY (position_id, election_id) REFERENCES exec_positions (id, election_id) ON DELETE CASCADE;
+ALTER TABLE nominations ADD CONSTRAINT nominations_warwick_id_fkey FOREIGN KEY (election_id, warwick_id) REFERENCES candidates (election_id, warwick_id) ON DELETE CASCADE;
+
+-- Votes belong to the election of their position, and stop it from being removed
+DELETE FROM votes v WHERE NOT EXISTS (SELECT 1 FROM exec_positions p WHERE p.id = v.position_id);
+ALTER TABLE votes ADD CONSTRAINT votes_position_id_fkey FOREIGN KEY (position_id) REFERENCES exec_positions (id);
@@ -0,0 +1,34 @@
+-- Re-open nominations is offered on each position's ballot unless it is turned off
+ALTER TABLE exec_positions ADD COLUMN ron BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- The RON candidates added by hand previously, which had to avoid real Warwick IDs
+CREATE TEMPORARY TABLE ron_candidates AS
+SELECT election_id, warwick_id FROM candidates
+WHERE name = 'RON' AND warwick_id BETWEEN 1700001 AND 1700009;
+
+UPDATE exec_positions p SET ron = EXISTS (
+	SELECT 1 FROM nominations n
+	INNER JOIN ron_candidates r ON r.election_id = n.election_id AND r.warwick_id = n.warwick_id
+	WHERE n.position_id = p.id
+);
+
+-- Positions with more than one RON only keep each voter's highest preference for it
+DELETE FROM votes v
+USING exec_positions p, ron_candidates r
+WHERE p.id = v.position_id AND r.election_id = p.election_id AND r.warwick_id = v.candidate_id
+AND EXISTS (
+	SELECT 1 FROM votes w
+	INNER JOIN ron_candidates s ON s.election_id = p.election_id AND s.warwick_id = w.candidate_id
+	WHERE w.warwick_id = v.warwick_id AND w.position_id = v.position_id AND w.ranking < v.ranking
+);
+
+-- Votes for RON now use the identifier of the built-in candidate
+UPDATE votes v SET candidate_id = 0
+FROM exec_positions p, ron_candidates r
+WHERE p.id = v.position_id AND r.election_id = p.election_id AND r.warwick_id = v.candidate_id;
+
+DELETE FROM candidates c
+USING ron_candidates r
+WHERE r.election_id = c.election_id AND r.warwick_id = c.warwick_id;
+
+DROP TABLE ron_candidates;
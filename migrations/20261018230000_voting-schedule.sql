@@ -0,0 +1,4 @@
+-- Voting can be opened and closed automatically, as Unix timestamps like session start times
+ALTER TABLE exec_positions ADD COLUMN opens_at BIGINT;
+ALTER TABLE exec_positions ADD COLUMN closes_at BIGINT;
+ALTER TABLE exec_positions ADD CONSTRAINT exec_positions_schedule_check CHECK (closes_at > opens_at);
@@ -0,0 +1,2 @@
+-- Each position is counted with its own method, defaulting to instant-runoff as before
+ALTER TABLE exec_positions ADD COLUMN counting_method TEXT NOT NULL DEFAULT 'irv' CHECK (counting_method IN ('irv', 'meek', 'scottish', 'approval', 'fptp'));
@@ -0,0 +1,49 @@
+-- Who has voted for each position, kept apart from how they voted
+CREATE TABLE voters (
+	position_id INTEGER NOT NULL REFERENCES exec_positions (id),
+	warwick_id INTEGER NOT NULL,
+	voted_at BIGINT NOT NULL,
+	PRIMARY KEY (position_id, warwick_id)
+);
+
+-- Ballots are only identified by a random ID, and can be replaced by whoever holds the secret
+-- receipt hashed here
+CREATE TABLE ballots (
+	id TEXT PRIMARY KEY,
+	position_id INTEGER NOT NULL REFERENCES exec_positions (id),
+	secret_hash TEXT NOT NULL,
+	casting BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TEMPORARY TABLE existing_ballots AS
+SELECT position_id, warwick_id, md5(random()::TEXT || clock_timestamp()::TEXT) AS ballot_id
+FROM (SELECT DISTINCT position_id, warwick_id FROM votes) v;
+
+-- When the existing ballots were cast isn't known, so they are recorded as cast now
+INSERT INTO voters (position_id, warwick_id, voted_at)
+SELECT position_id, warwick_id, EXTRACT(EPOCH FROM NOW())::BIGINT FROM existing_ballots;
+
+-- Nobody holds a receipt for the existing ballots, so no hash will match and they can't be replaced
+INSERT INTO ballots (id, position_id, secret_hash)
+SELECT ballot_id, position_id, '' FROM existing_ballots ORDER BY ballot_id;
+
+-- Votes belong to a ballot rather than a voter, and are copied in a random order so that they
+-- can't be matched up with the voters
+CREATE TABLE anonymous_votes (
+	ballot_id TEXT NOT NULL REFERENCES ballots (id) ON DELETE CASCADE,
+	position_id INTEGER NOT NULL REFERENCES exec_positions (id),
+	candidate_id INTEGER NOT NULL,
+	ranking INTEGER NOT NULL,
+	PRIMARY KEY (ballot_id, candidate_id)
+);
+
+INSERT INTO anonymous_votes (ballot_id, position_id, candidate_id, ranking)
+SELECT b.ballot_id, v.position_id, v.candidate_id, v.ranking
+FROM votes v
+INNER JOIN existing_ballots b ON b.position_id = v.position_id AND b.warwick_id = v.warwick_id
+ORDER BY b.ballot_id, v.ranking;
+
+DROP TABLE votes;
+ALTER TABLE anonymous_votes RENAME TO votes;
+
+DROP TABLE existing_ballots;
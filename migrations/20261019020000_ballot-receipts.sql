@@ -0,0 +1,18 @@
+-- Each ballot commits to its votes with a receipt code, which is published once voting closes
+ALTER TABLE ballots ADD COLUMN nonce TEXT;
+ALTER TABLE ballots ADD COLUMN receipt TEXT;
+
+UPDATE ballots SET nonce = md5(random()::TEXT || clock_timestamp()::TEXT);
+
+-- Calculated the same way as the server, from the nonce, position and candidates in order
+UPDATE ballots b SET receipt = encode(sha256(convert_to(
+	b.nonce || ':' || b.position_id || ':' || COALESCE((
+		SELECT string_agg(v.candidate_id::TEXT, ',' ORDER BY v.ranking)
+		FROM votes v
+		WHERE v.ballot_id = b.id
+	), ''),
+	'UTF8'
+)), 'hex');
+
+ALTER TABLE ballots ALTER COLUMN nonce SET NOT NULL;
+ALTER TABLE ballots ALTER COLUMN receipt SET NOT NULL;
@@ -0,0 +1,12 @@
+-- Ties for the last seats are broken by a method chosen for each position, rather than by the
+-- ballot marked as the casting ballot
+ALTER TABLE exec_positions ADD COLUMN tie_break TEXT NOT NULL DEFAULT 'backwards' CHECK (tie_break IN ('backwards', 'casting', 'lot'));
+
+-- The casting vote lists candidates in order of preference, recorded by an election administrator
+ALTER TABLE exec_positions ADD COLUMN casting_vote INTEGER[] NOT NULL DEFAULT '{}';
+
+-- The seed for drawing lots is fixed when the position is created, so it can't be chosen once the
+-- votes are known, and is published with the results
+ALTER TABLE exec_positions ADD COLUMN lot_seed TEXT NOT NULL DEFAULT md5(random()::TEXT || clock_timestamp()::TEXT);
+
+ALTER TABLE ballots DROP COLUMN casting;
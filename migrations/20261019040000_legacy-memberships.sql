@@ -0,0 +1,21 @@
+-- Members used to hold the member role directly, which never expired. They are given a membership
+-- lasting until the end of the academic year instead, on the 31st of July, so that they lapse
+-- like everyone else
+INSERT INTO memberships (warwick_id, membership_type, valid_from, valid_to, source)
+SELECT
+	warwick_id,
+	'Legacy',
+	CURRENT_DATE,
+	-- Adding 5 months moves dates after July into the next year
+	make_date(EXTRACT(YEAR FROM CURRENT_DATE + INTERVAL '5 months')::INTEGER, 7, 31),
+	'member-role'
+FROM user_roles
+WHERE role = 'member'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_audit_log (actor_id, warwick_id, role, action, timestamp)
+SELECT NULL, warwick_id, role, 'revoke', EXTRACT(EPOCH FROM NOW())::BIGINT
+FROM user_roles
+WHERE role = 'member';
+
+DELETE FROM user_roles WHERE role = 'member';
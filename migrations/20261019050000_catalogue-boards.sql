@@ -0,0 +1,38 @@
+-- Lifters choose whether to appear on the boards for the rest of the catalogue, which they did
+-- before whenever they had recorded one of its lifts
+ALTER TABLE personal_bests ADD COLUMN IF NOT EXISTS show_other BOOLEAN NOT NULL DEFAULT false;
+UPDATE personal_bests SET show_other = show_pl OR show_wl;
+
+DROP VIEW IF EXISTS personal_best_board;
+
+-- Each lifter's best for every lift in the catalogue is read from `lift_records` instead of a
+-- column per lift, so the view only keeps their bodyweight and verified lifts
+CREATE VIEW personal_best_board AS
+SELECT
+	pb.warwick_id,
+	pb.name,
+	pb.show_pl,
+	pb.show_wl,
+	pb.show_other,
+	latest.weight AS bodyweight,
+	pb.sex,
+	pb.weight_class,
+	COALESCE(best.verified, ARRAY[]::TEXT[]) AS verified
+FROM personal_bests pb
+LEFT JOIN (
+	SELECT warwick_id, ARRAY_AGG(DISTINCT lift) FILTER (WHERE verified_best) AS verified
+	FROM (
+		SELECT
+			warwick_id,
+			lift,
+			status = 'verified' AND weight = MAX(weight) OVER (PARTITION BY warwick_id, lift) AS verified_best
+		FROM lift_records
+		WHERE (reps IS NULL OR reps = 1) AND status <> 'rejected'
+	) singles
+	GROUP BY warwick_id
+) best ON best.warwick_id = pb.warwick_id
+LEFT JOIN (
+	SELECT DISTINCT ON (warwick_id) warwick_id, weight
+	FROM bodyweights
+	ORDER BY warwick_id, recorded_on DESC, id DESC
+) latest ON latest.warwick_id = pb.warwick_id;
@@ -0,0 +1,16 @@
+-- Keep every category and weight class a user has competed in, from the day they chose it, so
+-- lifts recorded for an earlier day are kept in the class the user was in on that day
+CREATE TABLE IF NOT EXISTS category_changes (
+	id SERIAL PRIMARY KEY,
+	warwick_id INTEGER NOT NULL,
+	sex TEXT CHECK (sex IN ('male', 'female')),
+	weight_class TEXT,
+	changed_on DATE NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS category_changes_warwick_id ON category_changes (warwick_id, changed_on);
+
+-- The category users are in now is the only one known, so it applies to every earlier day
+INSERT INTO category_changes (warwick_id, sex, weight_class, changed_on)
+SELECT warwick_id, sex, weight_class, '-infinity'
+FROM personal_bests;
@@ -0,0 +1,8 @@
+-- The seed for drawing lots is only chosen once voting for a position has closed, so nobody can
+-- work out how a draw would go while they can still vote
+ALTER TABLE exec_positions ALTER COLUMN lot_seed DROP DEFAULT;
+ALTER TABLE exec_positions ALTER COLUMN lot_seed DROP NOT NULL;
+
+-- Seeds chosen for positions in the current election are discarded, as they may already be known
+UPDATE exec_positions SET lot_seed = NULL
+WHERE election_id IN (SELECT id FROM elections WHERE status = 'current');
@@ -0,0 +1,7 @@
+-- Seeds for drawing lots are chosen when a position is created and never change, with their hash
+-- shown to voters, so nobody can choose another seed once they know how the draw would go
+UPDATE exec_positions SET lot_seed = md5(random()::TEXT || clock_timestamp()::TEXT)
+WHERE lot_seed IS NULL;
+
+ALTER TABLE exec_positions ALTER COLUMN lot_seed SET DEFAULT md5(random()::TEXT || clock_timestamp()::TEXT);
+ALTER TABLE exec_positions ALTER COLUMN lot_seed SET NOT NULL;
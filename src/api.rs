@@ -6,27 +6,35 @@
 use std::collections::HashMap;
 use std::env;
 
-use chrono::TimeZone;
+use chrono::{NaiveDate, TimeZone};
 use itertools::Itertools;
-use rocket::form::Form;
 use rocket::http::{Cookie, CookieJar};
 use rocket::response::{Flash, Redirect};
+use rocket::State;
 use rocket_db_pools::Connection;
+use rocket_dyn_templates::Template;
 
 use crate::auth;
+use crate::blt;
+use crate::context;
+use crate::counting;
+use crate::csrf::{CsrfForm, CsrfToken};
 use crate::email;
 use crate::forms;
 use crate::frontend;
-use crate::schema;
+use crate::schema::{self, custom_types};
+use crate::su_export;
 
-use crate::guards::{Db, ElectionAdmin, Generic, Member, SiteAdmin, User};
+use crate::guards::{
+    AccessControl, Coach, Db, ElectionAdmin, Generic, Member, RoleCache, SiteAdmin, User,
+};
 
 /// Creates a new session in the database.
 #[post("/sessions/create", data = "<data>")]
 pub async fn sessions_create(
     _user: User<SiteAdmin>,
     mut conn: Connection<Db>,
-    data: Form<forms::SessionCreate>,
+    data: CsrfForm<forms::SessionCreate>,
 ) -> Flash<Redirect> {
     let data = data.into_inner();
     let formatted = format!("{} {}", data.date, data.start_time);
@@ -49,7 +57,7 @@ pub async fn sessions_create(
 pub async fn session_delete(
     _user: User<SiteAdmin>,
     mut conn: Connection<Db>,
-    data: Form<forms::SessionDelete>,
+    data: CsrfForm<forms::SessionDelete>,
 ) -> Flash<Redirect> {
     let data = data.into_inner();
 
@@ -68,7 +76,7 @@ pub async fn session_delete(
 pub async fn register(
     user: User<Generic>,
     mut conn: Connection<Db>,
-    data: Form<forms::Register>,
+    data: CsrfForm<forms::Register>,
 ) -> Flash<Redirect> {
     let data = data.into_inner();
     let registration = schema::Registration::new(data.session_id, user.id, user.name);
@@ -109,7 +117,7 @@ pub async fn register(
 pub async fn cancel(
     user: User<Generic>,
     mut conn: Connection<Db>,
-    data: Form<forms::Cancel>,
+    data: CsrfForm<forms::Cancel>,
 ) -> Flash<Redirect> {
     let data = data.into_inner();
     let result = schema::Registration::cancel(user.id, data.session_id, &mut *conn).await;
@@ -129,30 +137,100 @@ pub async fn cancel(
 
 /// Logs the user out and deletes their cookies.
 #[get("/logout")]
-pub fn logout(user: User<Generic>, cookies: &CookieJar<'_>) -> Flash<Redirect> {
+pub async fn logout(
+    user: User<Generic>,
+    mut conn: Connection<Db>,
+    cookies: &CookieJar<'_>,
+) -> Flash<Redirect> {
     tracing::info!(id = %user.id, name = %user.name, "Logging out a user from the system");
 
+    if let Some(admin_id) = user.impersonator() {
+        schema::ImpersonationEntry::insert(admin_id, user.id, "stop", &mut *conn)
+            .await
+            .unwrap();
+    }
+
     cookies.remove_private(Cookie::named("id"));
     cookies.remove_private(Cookie::named("name"));
+    cookies.remove_private(Cookie::named("impersonating_id"));
+    cookies.remove_private(Cookie::named("impersonating_name"));
+
+    // Nobody else using the browser should be able to replace the user's ballots
+    let secrets: Vec<_> = cookies
+        .iter()
+        .map(|cookie| String::from(cookie.name()))
+        .filter(|name| name.starts_with(schema::vote::SECRET_COOKIE_PREFIX))
+        .collect();
+
+    for name in secrets {
+        cookies.remove_private(Cookie::named(name));
+    }
 
     Flash::success(
-        Redirect::to(uri!(frontend::blackboard)),
+        Redirect::to(uri!(frontend::blackboard(_))),
         "Successfully logged you out!",
     )
 }
 
 /// Updates a user's personal bests.
+///
+/// If any of the values are invalid, the form is shown again with the problem for each field.
 #[post("/pbs", data = "<data>")]
 pub async fn personal_bests(
     user: User<Member>,
     mut conn: Connection<Db>,
-    data: Form<forms::PersonalBests>,
-) -> Flash<Redirect> {
+    csrf_token: CsrfToken,
+    data: CsrfForm<forms::PersonalBests>,
+) -> Result<Flash<Redirect>, Template> {
     let data = data.into_inner();
+    let lifts = schema::Lift::get_results(&mut *conn).await.unwrap();
+
+    let data = match data.validate(&lifts) {
+        Ok(data) => data,
+        Err(errors) => {
+            let message = context::Message {
+                variant: String::from("error"),
+                message: String::from("Please correct the highlighted values."),
+            };
+
+            let page = frontend::render_personal_bests(
+                &user,
+                &mut conn,
+                csrf_token,
+                Some(message),
+                errors,
+            )
+            .await;
+
+            return Err(page);
+        }
+    };
+
+    // Only remember the preferences once the values entered with them have been accepted
+    let result = match schema::Preferences::find(user.id, &mut *conn).await {
+        Ok(preferences) => {
+            let preferences = schema::Preferences {
+                unit: data.unit.unwrap_or(preferences.unit),
+                formula: data.formula.unwrap_or(preferences.formula),
+                ..preferences
+            };
+
+            preferences.save(&mut *conn).await
+        }
+        Err(e) => Err(e),
+    };
+
+    if result.is_err() {
+        return Ok(Flash::error(
+            Redirect::to(uri!(frontend::personal_bests)),
+            "Failed to save your preferences, try again or let me know if it keeps happening.",
+        ));
+    }
+
     let result = schema::PersonalBest::update(user.id, user.name, data, &mut *conn).await;
 
     // Check whether they broke the database
-    match result {
+    Ok(match result {
         Ok(_) => Flash::success(
             Redirect::to(uri!(frontend::personal_bests)),
             "Successfully updated your PBs!",
@@ -161,6 +239,121 @@ pub async fn personal_bests(
             Redirect::to(uri!(frontend::personal_bests)),
             "Failed to update the PBs, try again or let me know if it keeps happening.",
         ),
+    })
+}
+
+/// Records a single lift in the user's history.
+#[post("/pbs/history", data = "<data>")]
+pub async fn lift_record_create(
+    user: User<Member>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::LiftRecord>,
+) -> Flash<Redirect> {
+    let data = data.into_inner();
+    let error = |message: &str| Flash::error(Redirect::to(uri!(frontend::lift_history)), message);
+
+    let recorded_on = match NaiveDate::parse_from_str(&data.recorded_on, "%Y-%m-%d") {
+        Ok(date) => custom_types::Date(date),
+        Err(_) => return error("Please enter a valid date."),
+    };
+
+    let lift = schema::Lift::find(&data.lift, &mut *conn).await.unwrap();
+
+    if lift.is_none() {
+        return error("Please choose one of the available lifts.");
+    }
+
+    if matches!(data.reps, Some(reps) if reps < 1) {
+        return error("Please enter a positive number of reps.");
+    }
+
+    if matches!(data.rpe, Some(rpe) if !(1.0..=10.0).contains(&rpe) || (rpe * 2.0).fract() != 0.0) {
+        return error("Please enter an RPE between 1 and 10, in steps of 0.5.");
+    }
+
+    let unit = schema::Preferences::find(user.id, &mut *conn)
+        .await
+        .unwrap()
+        .unit;
+
+    let weight = match forms::lift_in_kg(data.weight, unit) {
+        Ok(weight) => weight,
+        Err(message) => return error(&message),
+    };
+
+    let video_url = data.video_url().map(String::from);
+
+    if matches!(&video_url, Some(url) if !(url.starts_with("https://") || url.starts_with("http://")))
+    {
+        return error("Please enter a video link starting with https://.");
+    }
+
+    let record = schema::LiftRecord {
+        id: 0,
+        warwick_id: user.id,
+        lift: data.lift,
+        weight,
+        reps: data.reps,
+        rpe: data.rpe,
+        recorded_on,
+        note: data.note.filter(|note| !note.trim().is_empty()),
+        video_url,
+        status: custom_types::LiftStatus::Pending,
+    };
+
+    match record.insert(&mut *conn).await {
+        Ok(_) => Flash::success(
+            Redirect::to(uri!(frontend::lift_history)),
+            "Successfully recorded your lift!",
+        ),
+        Err(_) => {
+            error("Failed to record the lift, try again or let me know if it keeps happening.")
+        }
+    }
+}
+
+/// Deletes a lift from the user's history.
+#[post("/pbs/history/delete", data = "<data>")]
+pub async fn lift_record_delete(
+    user: User<Member>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::LiftRecordDelete>,
+) -> Flash<Redirect> {
+    let result = schema::LiftRecord::delete(data.record_id, user.id, &mut *conn).await;
+
+    match result {
+        Ok(true) => Flash::success(
+            Redirect::to(uri!(frontend::lift_history)),
+            "Successfully deleted the lift.",
+        ),
+        _ => Flash::error(
+            Redirect::to(uri!(frontend::lift_history)),
+            "Failed to delete the lift, has it already been deleted?",
+        ),
+    }
+}
+
+/// Verifies or rejects a lift recorded by a user.
+#[post("/pbs/review", data = "<data>")]
+pub async fn lift_review(
+    user: User<Coach>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::LiftReview>,
+) -> Flash<Redirect> {
+    let redirect = || Redirect::to(uri!(frontend::lift_review));
+
+    if data.status == custom_types::LiftStatus::Pending {
+        return Flash::error(redirect(), "Please either verify or reject the lift.");
+    }
+
+    let result = schema::LiftRecord::review(data.record_id, user.id, data.status, &mut *conn).await;
+
+    match result {
+        Ok(true) => Flash::success(redirect(), "Successfully reviewed the lift."),
+        _ => Flash::error(
+            redirect(),
+            "Failed to review the lift, has it already been reviewed or is it your own?",
+        ),
     }
 }
 
@@ -168,7 +361,7 @@ pub async fn personal_bests(
 #[post("/attendance/record", data = "<data>")]
 pub async fn record_attendance(
     mut conn: Connection<Db>,
-    data: Form<forms::Attendance>,
+    data: CsrfForm<forms::Attendance>,
 ) -> Flash<Redirect> {
     let data = data.into_inner();
 
@@ -237,7 +430,7 @@ pub async fn authorised(
     // Check we have a secret
     let (token, secret) = match auth_pair.secret {
         Some(s) => (auth_pair.token, s),
-        None => return Redirect::to(uri!(frontend::blackboard)),
+        None => return Redirect::to(uri!(frontend::blackboard(_))),
     };
 
     let pair = auth::exchange_request_for_access(
@@ -266,12 +459,21 @@ pub async fn authorised(
 pub async fn election_vote(
     user: User<Member>,
     mut conn: Connection<Db>,
+    cookies: &CookieJar<'_>,
     position_id: i32,
-    data: Form<HashMap<i32, i32>>,
+    data: CsrfForm<HashMap<i32, i32>>,
 ) -> Flash<Redirect> {
     let data = data.into_inner();
     let redirect = Redirect::to(uri!(frontend::election_voting(position_id)));
 
+    // Nobody should be able to vote on behalf of someone else
+    if user.impersonator().is_some() {
+        return Flash::error(
+            redirect,
+            "You cannot vote while viewing the website as another user.",
+        );
+    }
+
     let voting_is_open = schema::ExecPosition::voting_is_open(position_id, &mut *conn).await;
 
     // Check whether voting for this position is open
@@ -290,12 +492,40 @@ pub async fn election_vote(
         return Flash::error(redirect, "Make sure your votes are unique!");
     }
 
-    // Check that they submitted a full ballot
     let candidates = schema::Nomination::for_position_with_names(position_id, &mut *conn)
         .await
         .unwrap();
 
-    if data.values().count() != candidates.len() {
+    // Check every choice is a candidate, filled in from the first box onwards
+    let all_candidates = data
+        .values()
+        .all(|vote| candidates.iter().any(|c| c.warwick_id == *vote));
+    let in_order = (1..=data.len() as i32).all(|choice| data.contains_key(&choice));
+
+    if !all_candidates || !in_order {
+        return Flash::error(
+            redirect,
+            "Please choose a candidate in each box from the first one onwards.",
+        );
+    }
+
+    // Ranked ballots need every candidate, but approval and first past the post ballots only
+    // need the candidates the voter chooses, of which first past the post allows just one
+    let method = schema::ExecPosition::find(position_id, &mut *conn)
+        .await
+        .unwrap()
+        .map(|position| position.counting_method);
+    let ranked = method.map_or(true, custom_types::CountingMethod::is_ranked);
+
+    if data.is_empty() {
+        return Flash::error(redirect, "Please choose at least one candidate.");
+    }
+
+    if method == Some(custom_types::CountingMethod::Fptp) && data.len() > 1 {
+        return Flash::error(redirect, "Please choose a single candidate.");
+    }
+
+    if ranked && data.len() != candidates.len() {
         return Flash::error(
             redirect,
             "Please submit a full ballot, you need to choose an option for each box.",
@@ -313,27 +543,825 @@ pub async fn election_vote(
         );
     }
 
-    // Record the user's votes
-    schema::Vote::insert_all(user.id, position_id, &data, &mut *conn)
+    // Record the user's votes, replacing their ballot if they hold its secret
+    let cookie = schema::vote::secret_cookie(user.id, position_id);
+    let secret = cookies.get_private(&cookie);
+    let secret = secret.as_ref().map(Cookie::value);
+
+    let receipt = schema::Vote::cast(user.id, position_id, secret, &data, &mut *conn).await;
+
+    match receipt {
+        Ok(Some(receipt)) => {
+            cookies.add_private(Cookie::new(cookie, receipt.secret));
+            Flash::success(
+                redirect,
+                format!(
+                    "Successfully recorded your votes! Your receipt code is {}.",
+                    receipt.code
+                ),
+            )
+        }
+        Ok(None) => Flash::error(
+            redirect,
+            "You have already voted, and can only change your ballot from the session you voted in.",
+        ),
+        Err(e) => {
+            tracing::warn!(%position_id, error = %e, "Failed to record a ballot");
+            Flash::error(
+                redirect,
+                "Something went wrong recording your vote, please try again.",
+            )
+        }
+    }
+}
+
+/// Allows administrators to open and close voting for a position, replacing its schedule.
+#[post("/elections/settings/toggle", data = "<data>")]
+pub async fn election_settings_toggle(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::PositionToggle>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(uri!(frontend::election_settings));
+
+    let toggled = schema::ExecPosition::toggle_state(data.position_id, &mut *conn)
         .await
         .unwrap();
 
-    Flash::success(redirect, "Successfully recorded your votes!")
+    if toggled {
+        Flash::success(redirect, "Successfully toggled the state.")
+    } else {
+        Flash::error(
+            redirect,
+            "That position is not part of the current election.",
+        )
+    }
 }
 
-/// Allows administrators to open and close voting for a position.
-#[get("/elections/settings/toggle/<position_id>")]
-pub async fn election_settings_toggle(
+/// Allows election administrators to schedule when voting for a position opens and closes.
+#[post("/elections/settings/schedule", data = "<data>")]
+pub async fn election_settings_schedule(
     _user: User<ElectionAdmin>,
     mut conn: Connection<Db>,
-    position_id: i32,
+    data: CsrfForm<forms::PositionSchedule>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(uri!(frontend::election_settings));
+
+    let (opens_at, closes_at) = match data.timestamps() {
+        Ok(timestamps) => timestamps,
+        Err(message) => return Flash::error(redirect, message),
+    };
+
+    let scheduled =
+        schema::ExecPosition::schedule(data.position_id, opens_at, closes_at, &mut *conn)
+            .await
+            .unwrap();
+
+    if scheduled {
+        Flash::success(redirect, "Updated the voting schedule.")
+    } else {
+        Flash::error(
+            redirect,
+            "That position is not part of the current election.",
+        )
+    }
+}
+
+/// Allows election administrators to recount ballots uploaded in the BLT format, such as to check
+/// a count made with other counting software.
+#[post("/elections/recount", data = "<data>")]
+pub async fn election_recount(
+    _user: User<ElectionAdmin>,
+    data: CsrfForm<forms::BallotRecount<'_>>,
+) -> Result<Template, Flash<Redirect>> {
+    let error =
+        |message: String| Flash::error(Redirect::to(uri!(frontend::election_settings)), message);
+
+    let contents = forms::read_upload(&data.ballots).await;
+
+    let ballots = match contents.map(|contents| blt::parse(&contents)) {
+        Ok(Ok(ballots)) => ballots,
+        Ok(Err(message)) => return Err(error(message)),
+        Err(_) => {
+            return Err(error(String::from(
+                "The uploaded ballots were not valid text.",
+            )))
+        }
+    };
+
+    // Every candidate in the file is standing, even if nobody voted for them
+    let standing: Vec<_> = (1..=ballots.candidates.len() as i32).collect();
+    let count = counting::count(
+        data.counting_method,
+        &standing,
+        &ballots.ballots,
+        ballots.seats,
+    );
+    let name_of = |id: i32| ballots.candidates[id as usize - 1].as_str();
+
+    tracing::info!(title = %ballots.title, method = %data.counting_method, "Recounted uploaded ballots");
+
+    Ok(Template::render(
+        "election_recount",
+        context::ElectionRecount {
+            title: &ballots.title,
+            seats: ballots.seats,
+            ballot_count: ballots.ballots.len(),
+            winners: counting::within_seats(&count.ranking, ballots.seats)
+                .into_iter()
+                .map(name_of)
+                .collect(),
+            count_sheet: frontend::count_sheet(data.counting_method, &count, name_of),
+        },
+    ))
+}
+
+/// Allows election administrators to start a new election, once the previous one has finished.
+#[post("/elections/setup/create", data = "<data>")]
+pub async fn election_create(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::ElectionCreate>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(uri!(frontend::election_setup));
+    let name = data.name.trim();
+
+    let held_on = match data.held_on() {
+        Some(held_on) if !name.is_empty() => held_on,
+        _ => return Flash::error(redirect, "Please enter a name and date for the election."),
+    };
+
+    match schema::Election::create(name, held_on, &mut *conn).await {
+        Ok(_) => Flash::success(redirect, format!("Started the {} election", name)),
+        Err(_) => Flash::error(
+            redirect,
+            "Failed to start the election, has the current one been archived?",
+        ),
+    }
+}
+
+/// Allows election administrators to finish the current election, keeping its results.
+#[post("/elections/setup/archive", data = "<data>")]
+pub async fn election_archive(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::ElectionArchive>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(uri!(frontend::election_setup));
+
+    if !data.confirm {
+        return Flash::error(
+            redirect,
+            "Please confirm that the election should be archived.",
+        );
+    }
+
+    let archived = schema::Election::archive_current(&mut *conn).await.unwrap();
+
+    if archived {
+        Flash::success(
+            redirect,
+            "Archived the election, its results can now be viewed by members.",
+        )
+    } else {
+        Flash::error(redirect, "There is no election being run at the moment.")
+    }
+}
+
+/// Allows election administrators to add a position to the election.
+#[post("/elections/setup/positions", data = "<data>")]
+pub async fn position_create(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::Position>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(uri!(frontend::election_setup));
+
+    if let Err(message) = data.validate() {
+        return Flash::error(redirect, message);
+    }
+
+    let election = match schema::Election::current(&mut *conn).await.unwrap() {
+        Some(election) => election,
+        None => return Flash::error(redirect, "Please start an election first."),
+    };
+
+    let title = data.title.trim();
+
+    schema::ExecPosition::create(
+        election.id,
+        title,
+        data.num_winners,
+        data.ron,
+        data.counting_method,
+        data.tie_break,
+        &mut *conn,
+    )
+    .await
+    .unwrap();
+
+    Flash::success(redirect, format!("Added the {} position", title))
+}
+
+/// Allows election administrators to change the details of a position, such as its title or
+/// counting method.
+#[post("/elections/setup/positions/edit", data = "<data>")]
+pub async fn position_edit(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::PositionEdit>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(uri!(frontend::election_setup));
+
+    if let Err(message) = data.position.validate() {
+        return Flash::error(redirect, message);
+    }
+
+    let title = data.position.title.trim();
+
+    let updated = schema::ExecPosition::update(
+        data.position_id,
+        title,
+        data.position.num_winners,
+        data.position.ron,
+        data.position.counting_method,
+        data.position.tie_break,
+        &mut *conn,
+    )
+    .await
+    .unwrap();
+
+    if updated {
+        Flash::success(redirect, format!("Updated the {} position", title))
+    } else {
+        Flash::error(
+            redirect,
+            "That position is not part of the current election, or has been voted for so re-opening nominations, the number of winners, the counting method and the tie-break cannot change.",
+        )
+    }
+}
+
+/// Allows election administrators to record the casting vote used to break ties for a position.
+#[post("/elections/setup/positions/casting", data = "<data>")]
+pub async fn position_casting_vote(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::CastingVote>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(uri!(frontend::election_setup));
+
+    // The casting vote ranks the same options as the ballot, including re-opening nominations
+    let candidates: Vec<_> =
+        schema::Nomination::for_position_with_names(data.position_id, &mut *conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|candidate| candidate.warwick_id)
+            .collect();
+
+    if let Err(message) = data.validate(&candidates) {
+        return Flash::error(redirect, message);
+    }
+
+    let recorded =
+        schema::ExecPosition::set_casting_vote(data.position_id, &data.casting_vote, &mut *conn)
+            .await
+            .unwrap();
+
+    if recorded {
+        Flash::success(redirect, "Recorded the casting vote")
+    } else {
+        Flash::error(
+            redirect,
+            "That position is not part of the current election, or has been voted for so its casting vote cannot change.",
+        )
+    }
+}
+
+/// Allows election administrators to remove a position that has not been voted for.
+#[post("/elections/setup/positions/delete", data = "<data>")]
+pub async fn position_delete(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::PositionToggle>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(uri!(frontend::election_setup));
+
+    let deleted = schema::ExecPosition::delete(data.position_id, &mut *conn)
+        .await
+        .unwrap();
+
+    if deleted {
+        Flash::success(redirect, "Removed the position and its nominations.")
+    } else {
+        Flash::error(
+            redirect,
+            "That position has already been voted for, so it cannot be removed.",
+        )
+    }
+}
+
+/// Allows election administrators to add a candidate to the election.
+#[post("/elections/setup/candidates", data = "<data>")]
+pub async fn candidate_create(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::CandidateCreate>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(uri!(frontend::election_setup));
+    let name = data.name.trim();
+
+    if name.is_empty() {
+        return Flash::error(redirect, "Please enter the candidate's name.");
+    }
+
+    let election = match schema::Election::current(&mut *conn).await.unwrap() {
+        Some(election) => election,
+        None => return Flash::error(redirect, "Please start an election first."),
+    };
+
+    let candidate = schema::Candidate {
+        warwick_id: data.warwick_id.0,
+        name: name.to_string(),
+        elected: false,
+        election_id: election.id,
+    };
+
+    match candidate.insert(&mut *conn).await {
+        Ok(_) => Flash::success(redirect, format!("Added {} as a candidate", name)),
+        Err(_) => Flash::error(
+            redirect,
+            "Failed to add the candidate, are they already standing?",
+        ),
+    }
+}
+
+/// Allows election administrators to remove a candidate who has not received any votes.
+#[post("/elections/setup/candidates/delete", data = "<data>")]
+pub async fn candidate_delete(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::CandidateDelete>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(uri!(frontend::election_setup));
+
+    let deleted = schema::Candidate::delete(data.warwick_id, &mut *conn)
+        .await
+        .unwrap();
+
+    if deleted {
+        Flash::success(redirect, "Removed the candidate and their nominations.")
+    } else {
+        Flash::error(
+            redirect,
+            "That candidate has already received votes, so they cannot be removed.",
+        )
+    }
+}
+
+/// Allows election administrators to nominate a candidate for a position.
+#[post("/elections/setup/nominations", data = "<data>")]
+pub async fn nomination_create(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::NominationChange>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(uri!(frontend::election_setup));
+
+    let nomination = schema::Nomination {
+        position_id: data.position_id,
+        warwick_id: data.warwick_id,
+    };
+
+    match nomination.insert(&mut *conn).await {
+        Ok(true) => Flash::success(redirect, "Added the nomination."),
+        Ok(false) => Flash::error(
+            redirect,
+            "That position is not part of the current election, or is open for voting or has been voted for so its candidates cannot change.",
+        ),
+        Err(_) => Flash::error(
+            redirect,
+            "Failed to add the nomination, are they already nominated for that position?",
+        ),
+    }
+}
+
+/// Allows election administrators to withdraw a nomination that has not received any votes.
+#[post("/elections/setup/nominations/delete", data = "<data>")]
+pub async fn nomination_delete(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::NominationChange>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(uri!(frontend::election_setup));
+
+    let nomination = schema::Nomination {
+        position_id: data.position_id,
+        warwick_id: data.warwick_id,
+    };
+
+    let deleted = nomination.delete(&mut *conn).await.unwrap();
+
+    if deleted {
+        Flash::success(redirect, "Withdrew the nomination.")
+    } else {
+        Flash::error(
+            redirect,
+            "That nomination has already received votes, so it cannot be withdrawn.",
+        )
+    }
+}
+
+/// Allows site administrators to add a lift to the catalogue.
+#[post("/lifts", data = "<data>")]
+pub async fn lift_create(
+    _user: User<SiteAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::LiftCreate>,
+) -> Flash<Redirect> {
+    let data = data.into_inner();
+
+    if let Err(message) = data.validate(None) {
+        return Flash::error(Redirect::to(uri!(frontend::lifts)), message);
+    }
+
+    let lift = schema::Lift {
+        position: data.position.unwrap_or_default(),
+        ..schema::Lift::new(
+            &data.name,
+            data.display_name.trim(),
+            data.abbreviation.trim(),
+            data.board.trim(),
+        )
+    };
+
+    match lift.insert(&mut *conn).await {
+        Ok(_) => Flash::success(
+            Redirect::to(uri!(frontend::lifts)),
+            format!("Added {} to the {} board", lift.display_name, lift.board),
+        ),
+        Err(_) => Flash::error(
+            Redirect::to(uri!(frontend::lifts)),
+            "Failed to add the lift, is there already one with that name?",
+        ),
+    }
+}
+
+/// Allows site administrators to change how a lift in the catalogue is displayed.
+#[post("/lifts/update", data = "<data>")]
+pub async fn lift_update(
+    _user: User<SiteAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::LiftCreate>,
+) -> Flash<Redirect> {
+    let data = data.into_inner();
+
+    let existing = match schema::Lift::find(&data.name, &mut *conn).await {
+        Ok(Some(lift)) => lift,
+        _ => {
+            return Flash::error(
+                Redirect::to(uri!(frontend::lifts)),
+                "That lift is not in the catalogue.",
+            )
+        }
+    };
+
+    if let Err(message) = data.validate(Some(&existing)) {
+        return Flash::error(Redirect::to(uri!(frontend::lifts)), message);
+    }
+
+    let lift = schema::Lift {
+        position: data.position.unwrap_or(existing.position),
+        ..schema::Lift::new(
+            &data.name,
+            data.display_name.trim(),
+            data.abbreviation.trim(),
+            data.board.trim(),
+        )
+    };
+
+    match lift.update(&mut *conn).await {
+        Ok(true) => Flash::success(
+            Redirect::to(uri!(frontend::lifts)),
+            format!("Updated {} on the {} board", lift.display_name, lift.board),
+        ),
+        _ => Flash::error(
+            Redirect::to(uri!(frontend::lifts)),
+            "Failed to update the lift, try again or let me know if it keeps happening.",
+        ),
+    }
+}
+
+/// Allows site administrators to remove a lift from the catalogue.
+#[post("/lifts/delete", data = "<data>")]
+pub async fn lift_delete(
+    _user: User<SiteAdmin>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::LiftDelete>,
+) -> Flash<Redirect> {
+    let result = schema::Lift::delete(&data.name, &mut *conn).await;
+
+    match result {
+        Ok(true) => Flash::success(
+            Redirect::to(uri!(frontend::lifts)),
+            "Successfully removed the lift.",
+        ),
+        _ => Flash::error(
+            Redirect::to(uri!(frontend::lifts)),
+            "Lifts that have been recorded, or are on the powerlifting or weightlifting boards, cannot be removed.",
+        ),
+    }
+}
+
+/// Allows site administrators to grant a role to a user.
+#[post("/roles/grant", data = "<data>")]
+pub async fn role_grant(
+    user: User<SiteAdmin>,
+    mut conn: Connection<Db>,
+    cache: &State<RoleCache>,
+    data: CsrfForm<forms::RoleChange>,
+) -> Flash<Redirect> {
+    let data = data.into_inner();
+    let warwick_id = data.warwick_id.0;
+
+    // Membership comes from the register instead, so that it expires
+    if Some(data.role.as_str()) == Member::ROLE {
+        return Flash::error(
+            Redirect::to(uri!(frontend::roles)),
+            "Members are added through the membership register, so that their access expires.",
+        );
+    }
+
+    let result = schema::UserRole::grant(Some(user.id), warwick_id, &data.role, &mut *conn).await;
+
+    cache.invalidate(warwick_id);
+
+    match result {
+        Ok(_) => Flash::success(
+            Redirect::to(uri!(frontend::roles)),
+            format!("Granted the {} role to {}", data.role, warwick_id),
+        ),
+        Err(_) => Flash::error(
+            Redirect::to(uri!(frontend::roles)),
+            "Failed to grant the role, does it exist?",
+        ),
+    }
+}
+
+/// Allows site administrators to revoke a role from a user.
+#[post("/roles/revoke", data = "<data>")]
+pub async fn role_revoke(
+    user: User<SiteAdmin>,
+    mut conn: Connection<Db>,
+    cache: &State<RoleCache>,
+    data: CsrfForm<forms::RoleChange>,
 ) -> Flash<Redirect> {
-    schema::ExecPosition::toggle_state(position_id, &mut *conn)
+    let data = data.into_inner();
+    let warwick_id = data.warwick_id.0;
+
+    // Stop administrators from locking themselves out
+    if warwick_id == user.id && Some(data.role.as_str()) == SiteAdmin::ROLE {
+        return Flash::error(
+            Redirect::to(uri!(frontend::roles)),
+            "You cannot revoke your own site administrator role.",
+        );
+    }
+
+    let result = schema::UserRole::revoke(Some(user.id), warwick_id, &data.role, &mut *conn).await;
+
+    cache.invalidate(warwick_id);
+
+    match result {
+        Ok(_) => Flash::success(
+            Redirect::to(uri!(frontend::roles)),
+            format!("Revoked the {} role from {}", data.role, warwick_id),
+        ),
+        Err(_) => Flash::error(
+            Redirect::to(uri!(frontend::roles)),
+            "Failed to revoke the role, try again or let me know if it keeps happening.",
+        ),
+    }
+}
+
+/// Allows site administrators to import memberships from the students' union export.
+///
+/// Shows how the export compares to the current members, only recording the memberships if the
+/// administrator chose to apply the import.
+#[post("/memberships/import", data = "<data>")]
+pub async fn memberships_import(
+    user: User<SiteAdmin>,
+    mut conn: Connection<Db>,
+    cache: &State<RoleCache>,
+    csrf_token: CsrfToken,
+    data: CsrfForm<forms::MembershipImport<'_>>,
+) -> Result<Template, Flash<Redirect>> {
+    let data = data.into_inner();
+    let error = |message: String| Flash::error(Redirect::to(uri!(frontend::memberships)), message);
+
+    let parse_date = |value: &str| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok();
+
+    let (valid_from, valid_to) = match (parse_date(&data.valid_from), parse_date(&data.valid_to)) {
+        (Some(from), Some(to)) if from <= to => (custom_types::Date(from), custom_types::Date(to)),
+        _ => {
+            return Err(error(String::from(
+                "Please enter valid dates, with memberships starting before they expire.",
+            )))
+        }
+    };
+
+    let contents = forms::read_upload(&data.export).await;
+
+    let export = match contents.map(|contents| su_export::parse(&contents)) {
+        Ok(Ok(export)) => export,
+        Ok(Err(message)) => return Err(error(message)),
+        Err(_) => {
+            return Err(error(String::from(
+                "The uploaded export was not valid text.",
+            )))
+        }
+    };
+
+    let current = schema::Membership::current(&mut *conn).await.unwrap();
+    let diff = schema::membership::MembershipDiff::between(&current, &export.members);
+
+    if data.apply {
+        schema::Membership::import(
+            &export.members,
+            valid_from,
+            valid_to,
+            "students-union",
+            &mut *conn,
+        )
         .await
         .unwrap();
 
+        // Anyone joining should be able to access member pages immediately
+        cache.clear();
+    }
+
+    let memberships = schema::Membership::get_results(&mut *conn).await.unwrap();
+
+    Ok(Template::render(
+        "memberships",
+        context::Memberships {
+            memberships,
+            import: Some(context::MembershipImport {
+                diff,
+                skipped: export.skipped,
+                applied: data.apply,
+            }),
+            message: None,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
+        },
+    ))
+}
+
+/// Creates a new API token for the user, showing it to them once.
+#[post("/tokens/create", data = "<data>")]
+pub async fn token_create(
+    user: User<Generic>,
+    mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
+    data: CsrfForm<forms::TokenCreate>,
+) -> Result<Template, Flash<Redirect>> {
+    let data = data.into_inner();
+    let error = |message: &str| Flash::error(Redirect::to(uri!(frontend::api_tokens)), message);
+
+    // Tokens outlive impersonation and aren't audited, so administrators can't create them for
+    // the user they are viewing the website as
+    if user.impersonator().is_some() {
+        return Err(error(
+            "You cannot create tokens while viewing the website as someone else.",
+        ));
+    }
+
+    let name = data.name.trim();
+
+    if name.is_empty() {
+        return Err(error("Please give the token a name."));
+    }
+
+    // Tokens can only use roles the user currently holds
+    if !data.scopes.iter().all(|scope| user.roles().contains(scope)) {
+        return Err(error("You can only give a token roles that you hold."));
+    }
+
+    let created = schema::ApiToken::create(user.id, &user.name, name, &data.scopes, &mut *conn)
+        .await
+        .unwrap();
+
+    let tokens = schema::ApiToken::for_user(user.id, &mut *conn)
+        .await
+        .unwrap();
+
+    Ok(Template::render(
+        "api_tokens",
+        context::ApiTokens {
+            tokens,
+            roles: user.roles().to_vec(),
+            created: Some(created),
+            message: None,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
+        },
+    ))
+}
+
+/// Revokes one of the user's API tokens.
+#[post("/tokens/revoke", data = "<data>")]
+pub async fn token_revoke(
+    user: User<Generic>,
+    mut conn: Connection<Db>,
+    data: CsrfForm<forms::TokenRevoke>,
+) -> Flash<Redirect> {
+    let result = schema::ApiToken::revoke(data.token_id, user.id, &mut *conn).await;
+
+    match result {
+        Ok(true) => Flash::success(
+            Redirect::to(uri!(frontend::api_tokens)),
+            "Successfully revoked the token.",
+        ),
+        _ => Flash::error(
+            Redirect::to(uri!(frontend::api_tokens)),
+            "Failed to revoke the token, has it already been revoked?",
+        ),
+    }
+}
+
+/// Allows site administrators to start viewing the website as another user.
+#[post("/impersonate/start", data = "<data>")]
+pub async fn impersonation_start(
+    user: User<SiteAdmin>,
+    mut conn: Connection<Db>,
+    cookies: &CookieJar<'_>,
+    data: CsrfForm<forms::Impersonate>,
+) -> Flash<Redirect> {
+    let warwick_id = data.warwick_id.0;
+
+    let error = |message| Flash::error(Redirect::to(uri!(frontend::impersonation)), message);
+
+    // Impersonating while already impersonating would lose track of who the administrator is
+    if user.impersonator().is_some() || warwick_id == user.id {
+        return error(
+            "You cannot view the website as yourself or while viewing it as someone else.",
+        );
+    }
+
+    // Other administrators could act with their own access while being blamed for it
+    let roles = schema::UserRole::roles_for(warwick_id, &mut *conn)
+        .await
+        .unwrap();
+
+    if roles.iter().any(|role| role == "site_admin") {
+        return error("You cannot view the website as another site administrator.");
+    }
+
+    let name = schema::ImpersonationEntry::find_name(warwick_id, &mut *conn)
+        .await
+        .unwrap()
+        .unwrap_or_else(|| format!("User {}", warwick_id));
+
+    schema::ImpersonationEntry::insert(user.id, warwick_id, "start", &mut *conn)
+        .await
+        .unwrap();
+
+    cookies.add_private(Cookie::new("impersonating_id", warwick_id.to_string()));
+    cookies.add_private(Cookie::new("impersonating_name", name));
+
+    Flash::success(
+        Redirect::to(uri!(frontend::sessions)),
+        format!("You are now viewing the website as {}.", warwick_id),
+    )
+}
+
+/// Allows site administrators to stop viewing the website as another user.
+#[post("/impersonate/stop", data = "<data>")]
+pub async fn impersonation_stop(
+    user: User<Generic>,
+    mut conn: Connection<Db>,
+    cookies: &CookieJar<'_>,
+    data: CsrfForm<forms::Impersonate>,
+) -> Flash<Redirect> {
+    let admin_id = match user.impersonator() {
+        Some(admin_id) if data.warwick_id.0 == user.id => admin_id,
+        _ => {
+            return Flash::error(
+                Redirect::to(uri!(frontend::blackboard(_))),
+                "You are not viewing the website as another user.",
+            )
+        }
+    };
+
+    schema::ImpersonationEntry::insert(admin_id, user.id, "stop", &mut *conn)
+        .await
+        .unwrap();
+
+    cookies.remove_private(Cookie::named("impersonating_id"));
+    cookies.remove_private(Cookie::named("impersonating_name"));
+
     Flash::success(
-        Redirect::to(uri!(frontend::election_settings)),
-        "Successfully toggled the state.",
+        Redirect::to(uri!(frontend::impersonation)),
+        "You are no longer viewing the website as another user.",
     )
 }
@@ -0,0 +1,250 @@
+//! Reads and writes ballots in the BLT format used by OpenSTV and other counting software.
+//!
+//! A BLT file starts with the number of candidates and seats, optionally followed by the
+//! candidates who withdrew as negative numbers. Each ballot is then given as a weight followed by
+//! the candidates' numbers in order of preference, ending with `0`. Another `0` ends the ballots,
+//! and is followed by each candidate's name and the title of the election in double quotes.
+
+/// The most ballots a file can hold once their weights are expanded, which is far more than any
+/// election held by the club.
+const MAX_BALLOTS: i64 = 100_000;
+
+/// The ballots for a single position, where candidates are numbered from 1.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Blt {
+    /// The title of the election.
+    pub title: String,
+    /// The number of candidates who can win.
+    pub seats: usize,
+    /// The names of the candidates, in the order they are numbered.
+    pub candidates: Vec<String>,
+    /// The ballots, each listing candidate numbers in order of preference.
+    pub ballots: Vec<Vec<i32>>,
+}
+
+impl Blt {
+    /// Writes out the ballots, with a line and a weight of 1 for each.
+    pub fn write(&self) -> String {
+        let mut output = format!("{} {}\n", self.candidates.len(), self.seats);
+
+        for ballot in &self.ballots {
+            output.push('1');
+
+            for preference in ballot {
+                output.push_str(&format!(" {}", preference));
+            }
+
+            output.push_str(" 0\n");
+        }
+
+        output.push_str("0\n");
+
+        for name in self.candidates.iter().chain(std::iter::once(&self.title)) {
+            output.push_str(&format!("\"{}\"\n", name.replace('"', "'")));
+        }
+
+        output
+    }
+}
+
+/// Splits the contents into numbers and quoted names, ignoring comments starting with `#`.
+fn tokens(contents: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = contents.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => {
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+            }
+            '"' => {
+                let name: String = chars.by_ref().take_while(|c| *c != '"').collect();
+                tokens.push(format!("\"{}", name));
+            }
+            c if c.is_whitespace() => (),
+            c => {
+                let mut token = String::from(c);
+
+                while let Some(next) = chars.next_if(|c| !c.is_whitespace() && *c != '"') {
+                    token.push(next);
+                }
+
+                tokens.push(token);
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Parses the contents of a BLT file, returning an error message if it is malformed.
+///
+/// Ballots with a weight are repeated that many times, up to [`MAX_BALLOTS`] in total, and any
+/// candidates who withdrew are left off the ballots.
+pub fn parse(contents: &str) -> Result<Blt, String> {
+    let tokens = tokens(contents);
+    let mut tokens = tokens.iter().map(String::as_str);
+
+    let mut number = |name: &str| -> Result<i64, String> {
+        let token = tokens
+            .next()
+            .ok_or_else(|| format!("The file ended before the {}", name))?;
+
+        token
+            .parse()
+            .map_err(|_| format!("Expected the {} but found `{}`", name, token))
+    };
+
+    let candidates = number("number of candidates")?;
+    let seats = number("number of seats")?;
+
+    if candidates < 1 || seats < 1 || seats > candidates {
+        return Err(String::from(
+            "There must be at least one candidate and one seat, and no more seats than candidates",
+        ));
+    }
+
+    let mut withdrawn = Vec::new();
+    let mut first = number("first ballot")?;
+
+    while first < 0 {
+        withdrawn.push(-first);
+        first = number("first ballot")?;
+    }
+
+    let mut blt = Blt {
+        seats: seats as usize,
+        ..Blt::default()
+    };
+
+    let mut weight = first;
+
+    while weight != 0 {
+        if weight < 0 {
+            return Err(format!("Ballot weights must be positive, not {}", weight));
+        }
+
+        if weight > MAX_BALLOTS - blt.ballots.len() as i64 {
+            return Err(format!(
+                "Files can hold at most {} ballots once their weights are added up",
+                MAX_BALLOTS
+            ));
+        }
+
+        let mut ballot = Vec::new();
+
+        loop {
+            match number("next preference")? {
+                0 => break,
+                preference if (1..=candidates).contains(&preference) => {
+                    let candidate = i32::try_from(preference)
+                        .map_err(|_| format!("Ballots cannot include candidate {}", preference))?;
+
+                    if !withdrawn.contains(&preference) {
+                        ballot.push(candidate);
+                    }
+                }
+                preference => {
+                    return Err(format!("Ballots cannot include candidate {}", preference))
+                }
+            }
+        }
+
+        for _ in 0..weight {
+            blt.ballots.push(ballot.clone());
+        }
+
+        weight = number("weight of the next ballot")?;
+    }
+
+    let mut names = tokens.map(|token| token.strip_prefix('"'));
+
+    for number in 1..=candidates {
+        match names.next().flatten() {
+            Some(name) => blt.candidates.push(String::from(name)),
+            None => return Err(format!("Expected the name of candidate {}", number)),
+        }
+    }
+
+    blt.title = names.next().flatten().unwrap_or_default().to_string();
+
+    Ok(blt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blt() -> Blt {
+        Blt {
+            title: String::from("President"),
+            seats: 1,
+            candidates: vec![String::from("Alex"), String::from("Bea")],
+            ballots: vec![vec![2, 1], vec![1], vec![1, 2]],
+        }
+    }
+
+    #[test]
+    fn ballots_are_written_one_per_line() {
+        let expected = "2 1\n1 2 1 0\n1 1 0\n1 1 2 0\n0\n\"Alex\"\n\"Bea\"\n\"President\"\n";
+
+        assert_eq!(blt().write(), expected);
+    }
+
+    #[test]
+    fn written_ballots_can_be_parsed() {
+        assert_eq!(parse(&blt().write()), Ok(blt()));
+    }
+
+    #[test]
+    fn weights_are_expanded_and_withdrawn_candidates_removed() {
+        let contents = "# Written by hand\n\
+                        3 2\n\
+                        -2\n\
+                        2 1 2 3 0\n\
+                        1 2 0\n\
+                        0\n\
+                        \"Alex\" \"Bea\" \"Charlie Brown\"\n\
+                        \"Committee\"\n";
+
+        let expected = Blt {
+            title: String::from("Committee"),
+            seats: 2,
+            candidates: vec![
+                String::from("Alex"),
+                String::from("Bea"),
+                String::from("Charlie Brown"),
+            ],
+            ballots: vec![vec![1, 3], vec![1, 3], vec![]],
+        };
+
+        assert_eq!(parse(contents), Ok(expected));
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        assert!(parse("").is_err());
+        assert!(parse("2 3\n0\n\"Alex\" \"Bea\"").is_err());
+        assert!(parse("2 1\n1 3 0\n0\n\"Alex\" \"Bea\"").is_err());
+        assert!(parse("2 1\n1 1 2\n").is_err());
+        assert!(parse("2 1\n0\n\"Alex\"").is_err());
+        assert!(parse("2 1\n0.5 1 0\n0\n\"Alex\" \"Bea\"").is_err());
+    }
+
+    #[test]
+    fn huge_weights_and_candidate_numbers_are_rejected() {
+        assert!(parse("2 1\n100001 1 0\n0\n\"Alex\" \"Bea\"").is_err());
+        assert!(parse("2 1\n60000 1 0\n60000 2 0\n0\n\"Alex\" \"Bea\"").is_err());
+        assert!(parse("2 1\n100000 1 0\n0\n\"Alex\" \"Bea\"").is_ok());
+
+        // Weights large enough to overflow when added to the ballots so far are still rejected
+        let contents = "2 1\n1 1 0\n9223372036854775807 1 0\n0\n\"Alex\" \"Bea\"";
+        assert!(parse(contents).is_err());
+
+        let contents = "3000000000 1\n1 3000000000 0\n0\n";
+        assert_eq!(
+            parse(contents),
+            Err(String::from("Ballots cannot include candidate 3000000000"))
+        );
+    }
+}
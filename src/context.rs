@@ -1,11 +1,16 @@
 //! Stores the Tera context's needed for rendering the frontend webpages.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use rocket::request::FlashMessage;
 use serde::Serialize;
 
+use crate::csrf::CsrfToken;
+use crate::forms;
+use crate::guards::{AccessControl, User};
+use crate::one_rep_max::RepMaxRow;
 use crate::schema::{self, custom_types};
+use crate::scoring::ScoredLifter;
 
 /// Represents the registrations for a given session.
 #[derive(Debug, Serialize)]
@@ -36,6 +41,25 @@ impl From<FlashMessage<'_>> for Message {
     }
 }
 
+/// Describes the user a site administrator is viewing the website as.
+#[derive(Debug, Serialize)]
+pub struct Impersonating {
+    /// The user's Warwick ID
+    pub warwick_id: i32,
+    /// The user's name
+    pub name: String,
+}
+
+impl Impersonating {
+    /// Describes the given user if a site administrator is viewing the website as them.
+    pub fn of<T: AccessControl>(user: &User<T>) -> Option<Self> {
+        user.impersonator().map(|_| Self {
+            warwick_id: user.id,
+            name: user.name.clone(),
+        })
+    }
+}
+
 /// Information needed to display a session on the frontend.
 #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
 pub struct Session {
@@ -63,6 +87,10 @@ pub struct Context {
     pub registrations: Option<Vec<Registrations>>,
     /// Whether or not the user is a site administrator.
     pub is_site_admin: bool,
+    /// The user a site administrator is viewing the website as, if any.
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms.
+    pub csrf_token: CsrfToken,
 }
 
 /// The context for managing upcoming sessions.
@@ -74,6 +102,10 @@ pub struct ManageSessions {
     pub current: Option<Session>,
     /// The message to display to the user, for errors.
     pub message: Option<Message>,
+    /// The user a site administrator is viewing the website as, if any.
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms.
+    pub csrf_token: CsrfToken,
 }
 
 /// The context for automatically redirecting on authentication.
@@ -92,19 +124,66 @@ pub struct Attendance {
     pub current: Option<Session>,
     /// The message to display to the user, for errors.
     pub message: Option<Message>,
+    /// The token to submit with any forms.
+    pub csrf_token: CsrfToken,
 }
 
 /// The context for the blackboards page.
 #[derive(Serialize)]
 pub struct Blackboard {
-    /// The recorded personal bests for each PL user
-    pub pl: Vec<schema::PersonalBest>,
-    /// The recorded personal bests for each WL user
-    pub wl: Vec<schema::PersonalBest>,
+    /// The recorded personal bests and scores for each PL user
+    pub pl: Vec<ScoredLifter>,
+    /// The recorded personal bests and scores for each WL user
+    pub wl: Vec<ScoredLifter>,
+    /// The lifts shown on the PL board, in order
+    pub pl_lifts: Vec<schema::Lift>,
+    /// The lifts shown on the WL board, in order
+    pub wl_lifts: Vec<schema::Lift>,
+    /// The boards for any other lifts in the catalogue
+    pub boards: Vec<schema::lift::Board>,
+    /// How the PL board is sorted
+    pub pl_sort: forms::BoardSort,
+    /// How the WL board is sorted
+    pub wl_sort: forms::BoardSort,
+    /// The category lifters are being filtered by, if any
+    pub sex: Option<custom_types::Sex>,
+    /// The weight class lifters are being filtered by, if any
+    pub class: Option<String>,
+    /// The weight classes for the male category
+    pub male_classes: Vec<String>,
+    /// The weight classes for the female category
+    pub female_classes: Vec<String>,
+    /// Whether one rep maxes estimated from sets of multiple reps are shown
+    pub estimated: bool,
+    /// The formula one rep maxes are estimated with
+    pub formula: custom_types::Formula,
+    /// The unit weights are shown in
+    pub unit: custom_types::Unit,
     /// The Warwick ID of the viewer if they are logged in
     pub user_id: Option<i32>,
     /// The message to display to the user, for errors
     pub message: Option<Message>,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
+}
+
+/// The context for the club records page.
+#[derive(Serialize)]
+pub struct ClubRecords {
+    /// The records for each lift, along with their previous holders
+    pub lifts: Vec<schema::club_record::LiftRecords>,
+    /// The unit weights are shown in
+    pub unit: custom_types::Unit,
+    /// The Warwick ID of the viewer if they are logged in
+    pub user_id: Option<i32>,
+    /// The message to display to the user, for errors
+    pub message: Option<Message>,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
 }
 
 /// The context for updating personal bests.
@@ -112,21 +191,121 @@ pub struct Blackboard {
 pub struct PersonalBests {
     /// The user's personal bests
     pub personal_bests: schema::PersonalBest,
+    /// The lifts that can be recorded, in the order they are displayed
+    pub lifts: Vec<schema::Lift>,
+    /// The bodyweight the user most recently recorded
+    pub latest_bodyweight: Option<schema::Bodyweight>,
+    /// The user's rep maxes for each lift, along with their estimated one rep maxes
+    pub rep_maxes: Vec<RepMaxRow>,
+    /// The formula the user's one rep maxes are estimated with
+    pub formula: custom_types::Formula,
+    /// The weight classes for the male category
+    pub male_classes: Vec<String>,
+    /// The weight classes for the female category
+    pub female_classes: Vec<String>,
+    /// The unit weights are entered and shown in
+    pub unit: custom_types::Unit,
     /// Any warning message to display to the user
     pub warning: Option<String>,
+    /// The problems with each field the user submitted, if any
+    pub errors: forms::FieldErrors,
+    /// Whether the user can review the lifts others have recorded
+    pub is_coach: bool,
+    /// The message to display to the user, for errors
+    pub message: Option<Message>,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
+}
+
+/// The context for viewing the history of a user's lifts.
+#[derive(Serialize)]
+pub struct LiftHistory {
+    /// The lifts the user has recorded, most recent first
+    pub records: Vec<schema::LiftRecord>,
+    /// How the user's best single for each lift has progressed
+    pub progression: BTreeMap<String, Vec<schema::lift_record::ProgressionPoint>>,
+    /// The lifts that can be recorded
+    pub lifts: Vec<schema::Lift>,
+    /// The unit weights are entered and shown in
+    pub unit: custom_types::Unit,
     /// The message to display to the user, for errors
     pub message: Option<Message>,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
+}
+
+/// The context for reviewing the lifts users have recorded.
+#[derive(Serialize)]
+pub struct LiftReview {
+    /// The lifts waiting to be reviewed, oldest first
+    pub pending: Vec<schema::lift_record::PendingLift>,
+    /// The Warwick ID of the coach, whose own lifts cannot be reviewed
+    pub user_id: i32,
+    /// The unit weights are shown in
+    pub unit: custom_types::Unit,
+    /// The message to display to the user, for errors
+    pub message: Option<Message>,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
 }
 
 /// The context for displaying the exec positions.
 #[derive(Serialize)]
 pub struct Elections {
+    /// The election being run, if there is one
+    pub election: Option<schema::Election>,
     /// The positions to show
     pub exec_positions: Vec<schema::ExecPosition>,
+    /// The positions whose ballots have been published, as voting for them has closed
+    pub published: Vec<i32>,
+    /// The elections that have finished, most recent first
+    pub archived: Vec<schema::Election>,
     /// The message to display to the user, for errors
     pub message: Option<Message>,
     /// Whether or not the user is an election administrator
     pub admin: bool,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
+}
+
+/// The context for setting up an election.
+#[derive(Serialize)]
+pub struct ElectionSetup {
+    /// The election being set up, if there is one
+    pub election: Option<schema::Election>,
+    /// The positions in the election
+    pub exec_positions: Vec<schema::ExecPosition>,
+    /// The candidates standing in the election, sorted by name
+    pub candidates: Vec<schema::Candidate>,
+    /// The candidates nominated for each position
+    pub nominations: Vec<schema::NamedNomination>,
+    /// The positions that have already received votes, which can no longer be removed
+    pub voted_positions: Vec<i32>,
+    /// The options that can be ranked on the casting vote for each position
+    pub casting_options: Vec<CastingOptions>,
+    /// The message to display to the user, for errors
+    pub message: Option<Message>,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
+}
+
+/// The options on the ballot for a position, which the casting vote ranks.
+#[derive(Serialize)]
+pub struct CastingOptions {
+    /// The position the options are for
+    pub position_id: i32,
+    /// The candidates on the ballot, including re-opening nominations if it is offered
+    pub candidates: Vec<schema::nomination::NamedNominationForPosition>,
 }
 
 /// The context for displaying the voting page.
@@ -138,10 +317,26 @@ pub struct Voting {
     pub position_title: String,
     /// The positions to show
     pub nominations: Vec<schema::nomination::NamedNominationForPosition>,
+    /// Whether the ballot ranks every candidate, or only those the voter chooses
+    pub ranked: bool,
+    /// Whether the voter chooses a single candidate, as for first past the post
+    pub single_choice: bool,
+    /// The hash of the seed for drawing lots, which is published with the results
+    pub lot_seed_hash: String,
     /// The user's current votes for this position, if they have voted
     pub current_ballot: Option<Vec<String>>,
+    /// The receipt code for the user's current ballot, if they have voted in this session
+    pub receipt_code: Option<String>,
+    /// What the receipt code is the hash of, so that the user can calculate it themselves
+    pub receipt_commitment: Option<String>,
+    /// Whether the user is on the voter roll, even if their ballot was cast from another session
+    pub has_voted: bool,
     /// The message to display to the user, for errors
     pub message: Option<Message>,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
 }
 
 /// The result of a single election on a position.
@@ -155,13 +350,178 @@ pub struct ElectionResult<'a> {
     pub winners: Vec<(i32, &'a str, usize)>,
     /// The number of people who voted
     pub voter_count: usize,
+    /// Whether re-opening nominations won any of the places
+    pub reopened: bool,
+    /// Whether voting has finished, as the winners are only counted afterwards
+    pub closed: bool,
+    /// Each stage of the count, once voting has finished
+    pub count_sheet: Option<CountSheet<'a>>,
+}
+
+/// The context for displaying a recount of ballots uploaded in the BLT format.
+#[derive(Serialize)]
+pub struct ElectionRecount<'a> {
+    /// The title given in the uploaded file
+    pub title: &'a str,
+    /// The number of candidates who can win
+    pub seats: usize,
+    /// The number of ballots counted
+    pub ballot_count: usize,
+    /// The candidates who won, including everyone tied for the last seat
+    pub winners: Vec<&'a str>,
+    /// Each stage of the count
+    pub count_sheet: CountSheet<'a>,
+}
+
+/// The context for checking receipt codes against the published ballots of a position.
+#[derive(Serialize)]
+pub struct ElectionReceipts {
+    /// The position the ballots are for
+    pub position_id: i32,
+    /// The title of the position
+    pub position_title: String,
+    /// The ballots cast for the position, in order of receipt code
+    pub ballots: Vec<schema::vote::PublishedBallot>,
+    /// The receipt code being checked, if any
+    pub code: Option<String>,
+    /// Whether the receipt code being checked was found
+    pub found: Option<bool>,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
+}
+
+/// The stages of counting the ballots for a position, with votes formatted for display.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct CountSheet<'a> {
+    /// The name of the method used to count the ballots
+    pub method: String,
+    /// The votes held by each candidate at each stage
+    pub rows: Vec<CountRow<'a>>,
+    /// The votes on exhausted ballots at each stage
+    pub exhausted: Vec<String>,
+    /// The quota at each stage, if the method uses one
+    pub quota: Vec<Option<String>>,
+    /// What happened at the end of each stage, such as who was elected
+    pub actions: Vec<String>,
+    /// The name of the method used to break ties, if the position has one
+    pub tie_break: Option<String>,
+    /// How each tied seat was filled
+    pub ties: Vec<String>,
+}
+
+/// The votes held by a single candidate throughout a count.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct CountRow<'a> {
+    /// The name of the candidate
+    pub name: &'a str,
+    /// The candidate's votes at each stage, if they were still in the count
+    pub stages: Vec<Option<CountCell>>,
+}
+
+/// The votes held by a candidate at a single stage of a count.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct CountCell {
+    /// The value of the votes held
+    pub votes: String,
+    /// The votes transferred since the previous stage, such as `+2.5`, if any
+    pub change: Option<String>,
 }
 
 /// The context for displaying the election results.
 #[derive(Serialize)]
 pub struct ElectionResults<'a> {
+    /// The election the results are for
+    pub election: schema::Election,
     /// The results of each election
     pub results: Vec<ElectionResult<'a>>,
+    /// Whether or not the user is an election administrator
+    pub admin: bool,
+}
+
+/// The context for managing the roles users hold.
+#[derive(Serialize)]
+pub struct Roles {
+    /// The roles that can be granted
+    pub roles: Vec<schema::Role>,
+    /// The roles currently held by each user
+    pub user_roles: Vec<schema::UserRole>,
+    /// The history of changes to roles
+    pub audit_log: Vec<schema::RoleAuditEntry>,
+    /// The message to display to the user, for errors
+    pub message: Option<Message>,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
+}
+
+/// The context for managing the lifts that can be recorded.
+#[derive(Serialize)]
+pub struct Lifts {
+    /// The lifts in the catalogue, in the order they are displayed
+    pub lifts: Vec<schema::Lift>,
+    /// The message to display to the user, for errors
+    pub message: Option<Message>,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
+}
+
+/// The context for viewing the membership register.
+#[derive(Serialize)]
+pub struct Memberships {
+    /// Every membership that has been recorded
+    pub memberships: Vec<schema::Membership>,
+    /// The result of an import, if one was just submitted
+    pub import: Option<MembershipImport>,
+    /// The message to display to the user, for errors
+    pub message: Option<Message>,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
+}
+
+/// The result of importing memberships from the students' union.
+#[derive(Serialize)]
+pub struct MembershipImport {
+    /// How the import compares to the current members
+    pub diff: schema::membership::MembershipDiff,
+    /// The number of rows in the export that could not be read
+    pub skipped: usize,
+    /// Whether the import was applied, or only previewed
+    pub applied: bool,
+}
+
+/// The context for managing a user's API tokens.
+#[derive(Serialize)]
+pub struct ApiTokens {
+    /// The user's tokens that have not been revoked
+    pub tokens: Vec<schema::ApiToken>,
+    /// The roles the user can give to new tokens
+    pub roles: Vec<String>,
+    /// The token that was just created, shown only once
+    pub created: Option<String>,
+    /// The message to display to the user, for errors
+    pub message: Option<Message>,
+    /// The user a site administrator is viewing the website as, if any
+    pub impersonating: Option<Impersonating>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
+}
+
+/// The context for viewing the website as another user.
+#[derive(Serialize)]
+pub struct Impersonation {
+    /// The history of site administrators viewing the website as other users
+    pub entries: Vec<schema::ImpersonationEntry>,
+    /// The message to display to the user, for errors
+    pub message: Option<Message>,
+    /// The token to submit with any forms
+    pub csrf_token: CsrfToken,
 }
 
 /// The context for displaying the `403 Forbidden` page.
@@ -0,0 +1,1244 @@
+//! Counts the ballots for a position using the counting method it was set up with.
+//!
+//! Every method produces a ranking of the candidates, where candidates who could not be
+//! separated share a rank, along with each stage of the count for the count sheet. Ballots list
+//! candidates in order of preference, except for approval voting where the order does not matter.
+//! Any tie for the last seats is then broken with the method chosen for the position.
+
+use std::collections::{BTreeSet, HashMap};
+
+use sha2::{Digest, Sha256};
+
+use crate::schema::custom_types::{CountingMethod, TieBreak};
+
+/// How close two totals need to be to count as equal, to avoid rounding errors in fractional
+/// transfers.
+const EPSILON: f64 = 1e-9;
+
+/// The number of iterations after which the keep values in a Meek count are assumed to have
+/// converged.
+const MAX_ITERATIONS: usize = 10_000;
+
+/// The value of a whole vote in a Scottish STV count, which keeps 5 decimal places.
+const SCOTTISH_UNIT: i64 = 100_000;
+
+/// A candidate's position in the result of a count.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Ranked {
+    /// The candidate's identifier
+    pub candidate: i32,
+    /// The candidate's rank, starting from 0 for the winner
+    pub rank: usize,
+}
+
+/// The votes held by a candidate at one stage of a count.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Total {
+    /// The candidate's identifier
+    pub candidate: i32,
+    /// The value of the votes the candidate holds
+    pub votes: f64,
+    /// The votes transferred to or from the candidate since the previous stage
+    pub change: f64,
+}
+
+/// A single stage of a count, along with what happened at the end of it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Round {
+    /// The votes held by each candidate still in the count
+    pub totals: Vec<Total>,
+    /// The value of the votes on ballots with no preferences left in the count
+    pub exhausted: f64,
+    /// The votes needed to be elected, for methods that use a quota
+    pub quota: Option<f64>,
+    /// The candidates elected at this stage
+    pub elected: Vec<i32>,
+    /// The candidates excluded at this stage
+    pub excluded: Vec<i32>,
+    /// The candidate whose surplus was transferred at this stage
+    pub surplus: Option<i32>,
+}
+
+/// The result of counting the ballots for a position.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Count {
+    /// The candidates in the order they finished
+    pub ranking: Vec<Ranked>,
+    /// Each stage of the count, in order
+    pub rounds: Vec<Round>,
+}
+
+impl Count {
+    /// Starts a new stage of the count with the given totals, working out the transfers since the
+    /// previous stage.
+    fn record(
+        &mut self,
+        totals: &HashMap<i32, f64>,
+        exhausted: f64,
+        quota: Option<f64>,
+    ) -> &mut Round {
+        let previous = self.rounds.last();
+
+        let mut totals: Vec<_> = totals
+            .iter()
+            .map(|(candidate, votes)| {
+                let before = previous
+                    .and_then(|round| round.totals.iter().find(|t| t.candidate == *candidate))
+                    .map_or(*votes, |total| total.votes);
+
+                Total {
+                    candidate: *candidate,
+                    votes: *votes,
+                    change: *votes - before,
+                }
+            })
+            .collect();
+
+        totals.sort_by_key(|total| total.candidate);
+
+        self.rounds.push(Round {
+            totals,
+            exhausted,
+            quota,
+            ..Round::default()
+        });
+
+        self.rounds.last_mut().unwrap()
+    }
+}
+
+/// Counts the ballots using the given method, for a position with the given number of seats.
+///
+/// Everyone standing is included in the count even if no ballot chooses them, along with anyone
+/// else found on the ballots.
+pub fn count(
+    method: CountingMethod,
+    standing: &[i32],
+    ballots: &[Vec<i32>],
+    seats: usize,
+) -> Count {
+    let candidates = candidates(standing, ballots);
+
+    match method {
+        CountingMethod::Irv => irv(candidates, ballots, seats),
+        CountingMethod::Meek => meek(candidates, ballots, seats),
+        CountingMethod::Scottish => scottish(candidates, ballots, seats),
+        CountingMethod::Approval => approval(candidates, ballots, seats),
+        CountingMethod::Fptp => fptp(candidates, ballots, seats),
+    }
+}
+
+/// Gets the candidates ranked highly enough to fill the given number of seats, including
+/// everyone tied for the last one.
+pub fn within_seats(ranking: &[Ranked], seats: usize) -> Vec<i32> {
+    let last = seats
+        .checked_sub(1)
+        .and_then(|index| ranking.get(index))
+        .map_or(usize::MAX, |ranked| ranked.rank);
+
+    ranking
+        .iter()
+        .filter(|ranked| ranked.rank <= last)
+        .map(|ranked| ranked.candidate)
+        .collect()
+}
+
+/// How a tie for one of the last seats was broken.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TiedSeat {
+    /// The candidates tied for the seat
+    pub tied: Vec<i32>,
+    /// The candidate given the seat
+    pub chosen: i32,
+    /// The method that separated the candidates, which is drawing lots if the method chosen for
+    /// the position could not
+    pub method: TieBreak,
+    /// The stage whose totals separated the candidates, for backwards tie-breaks
+    pub stage: Option<usize>,
+}
+
+/// The candidates filling the seats once any ties have been broken.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Elected {
+    /// The candidates elected, keeping their rank in the count
+    pub winners: Vec<Ranked>,
+    /// How each tied seat was filled, in order
+    pub ties: Vec<TiedSeat>,
+}
+
+/// Fills the seats from the ranking of a count, breaking any tie for the last seats with the given
+/// method.
+///
+/// Backwards tie-breaks prefer whoever had the most votes at the latest stage where the tied
+/// candidates' totals differ, and casting votes prefer whoever is ranked highest on the casting
+/// vote. If neither separates the candidates, lots are drawn by ordering them by the SHA-256 hash
+/// of the seed and their identifier, such as `seed:1702502`, lowest first.
+pub fn break_ties(
+    count: &Count,
+    seats: usize,
+    method: TieBreak,
+    casting_vote: &[i32],
+    seed: &str,
+) -> Elected {
+    let mut elected = Elected::default();
+    let mut rank = 0;
+
+    for group in group_ranks(count.ranking.clone()) {
+        let left = seats.saturating_sub(elected.winners.len());
+
+        if left == 0 {
+            break;
+        }
+
+        if group.len() <= left {
+            elected.winners.extend(group.iter().map(|candidate| Ranked {
+                candidate: *candidate,
+                rank,
+            }));
+        } else {
+            let mut tied = group.clone();
+
+            for _ in 0..left {
+                let seat = choose(&tied, count, method, casting_vote, seed);
+                tied.retain(|candidate| *candidate != seat.chosen);
+
+                elected.winners.push(Ranked {
+                    candidate: seat.chosen,
+                    rank,
+                });
+                elected.ties.push(seat);
+            }
+        }
+
+        rank += group.len();
+    }
+
+    elected
+}
+
+/// Gives one seat to one of the tied candidates.
+fn choose(
+    tied: &[i32],
+    count: &Count,
+    method: TieBreak,
+    casting_vote: &[i32],
+    seed: &str,
+) -> TiedSeat {
+    let mut remaining = tied.to_vec();
+    let mut stage = None;
+
+    match method {
+        TieBreak::Backwards => {
+            for (index, round) in count.rounds.iter().enumerate().rev() {
+                let votes = |candidate: &i32| {
+                    round
+                        .totals
+                        .iter()
+                        .find(|total| total.candidate == *candidate)
+                        .map_or(0.0, |total| total.votes)
+                };
+
+                let most = remaining
+                    .iter()
+                    .map(votes)
+                    .fold(f64::NEG_INFINITY, f64::max);
+                let before = remaining.len();
+
+                remaining.retain(|candidate| most - votes(candidate) < EPSILON);
+
+                if remaining.len() < before {
+                    stage = Some(index + 1);
+                }
+
+                if remaining.len() == 1 {
+                    break;
+                }
+            }
+        }
+        TieBreak::Casting => {
+            if let Some(first) = casting_vote.iter().find(|c| remaining.contains(c)) {
+                remaining = vec![*first];
+            }
+        }
+        TieBreak::Lot => (),
+    }
+
+    if let [chosen] = remaining[..] {
+        return TiedSeat {
+            tied: tied.to_vec(),
+            chosen,
+            method,
+            stage,
+        };
+    }
+
+    TiedSeat {
+        tied: tied.to_vec(),
+        chosen: remaining
+            .into_iter()
+            .min_by_key(|candidate| lot(seed, *candidate))
+            .unwrap(),
+        method: TieBreak::Lot,
+        stage: None,
+    }
+}
+
+/// Gets the value a candidate draws in a lot with the given seed, where the lowest wins.
+fn lot(seed: &str, candidate: i32) -> String {
+    format!("{:x}", Sha256::digest(format!("{}:{}", seed, candidate)))
+}
+
+/// Gets everyone standing along with every candidate appearing on any of the ballots, in a
+/// consistent order.
+fn candidates(standing: &[i32], ballots: &[Vec<i32>]) -> Vec<i32> {
+    ballots
+        .iter()
+        .flatten()
+        .chain(standing)
+        .copied()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Ranks groups of candidates in order, where each group shares a rank.
+fn rank_groups(groups: impl IntoIterator<Item = Vec<i32>>) -> Vec<Ranked> {
+    let mut ranking = Vec::new();
+
+    for group in groups {
+        let rank = ranking.len();
+        ranking.extend(
+            group
+                .into_iter()
+                .map(|candidate| Ranked { candidate, rank }),
+        );
+    }
+
+    ranking
+}
+
+/// Ranks candidates by their totals, highest first, where equal totals share a rank.
+fn rank_by_totals(mut totals: Vec<(i32, f64)>) -> Vec<Ranked> {
+    totals.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut groups: Vec<(f64, Vec<i32>)> = Vec::new();
+
+    for (candidate, total) in totals {
+        match groups.last_mut() {
+            Some((last, group)) if (*last - total).abs() < EPSILON => group.push(candidate),
+            _ => groups.push((total, vec![candidate])),
+        }
+    }
+
+    rank_groups(groups.into_iter().map(|(_, group)| group))
+}
+
+/// Counts a single stage with the given totals, electing the candidates with the most votes.
+fn single_stage(totals: HashMap<i32, f64>, ballots: &[Vec<i32>], seats: usize) -> Count {
+    let mut count = Count::default();
+    let exhausted = ballots.iter().filter(|ballot| ballot.is_empty()).count();
+
+    count.ranking = rank_by_totals(totals.iter().map(|(c, total)| (*c, *total)).collect());
+    let elected = within_seats(&count.ranking, seats);
+    count.record(&totals, exhausted as f64, None).elected = elected;
+
+    count
+}
+
+/// Elects the candidates with the most first preferences.
+fn fptp(candidates: Vec<i32>, ballots: &[Vec<i32>], seats: usize) -> Count {
+    let mut totals: HashMap<i32, f64> = candidates.into_iter().map(|c| (c, 0.0)).collect();
+
+    for first in ballots.iter().filter_map(|ballot| ballot.first()) {
+        *totals.get_mut(first).unwrap() += 1.0;
+    }
+
+    single_stage(totals, ballots, seats)
+}
+
+/// Elects the candidates approved of by the most voters.
+fn approval(candidates: Vec<i32>, ballots: &[Vec<i32>], seats: usize) -> Count {
+    let mut totals: HashMap<i32, f64> = candidates.into_iter().map(|c| (c, 0.0)).collect();
+
+    for ballot in ballots {
+        for candidate in ballot.iter().collect::<BTreeSet<_>>() {
+            *totals.get_mut(candidate).unwrap() += 1.0;
+        }
+    }
+
+    single_stage(totals, ballots, seats)
+}
+
+/// Repeatedly eliminates the candidates with the fewest votes, transferring their ballots to the
+/// next preference, and ranks candidates by how long they remained.
+///
+/// Candidates tied for the fewest votes are eliminated together and share a rank, so positions
+/// with several seats are filled by the candidates eliminated last. The count sheet stops once
+/// the candidates left can fill the seats, or for a single seat once a candidate holds a majority
+/// of the votes still in the count. A majority can never be overtaken, so the remaining
+/// eliminations only rank the other candidates.
+fn irv(candidates: Vec<i32>, ballots: &[Vec<i32>], seats: usize) -> Count {
+    let mut count = Count::default();
+    let mut continuing = candidates;
+    let mut eliminated: Vec<Vec<i32>> = Vec::new();
+    let mut filled = false;
+
+    while !continuing.is_empty() {
+        let mut totals: HashMap<i32, f64> = continuing.iter().map(|c| (*c, 0.0)).collect();
+        let mut exhausted = 0.0;
+
+        for ballot in ballots {
+            match ballot.iter().find(|c| continuing.contains(c)) {
+                Some(candidate) => *totals.get_mut(candidate).unwrap() += 1.0,
+                None => exhausted += 1.0,
+            }
+        }
+
+        let fewest = totals.values().copied().fold(f64::INFINITY, f64::min);
+        let (losers, remaining): (Vec<_>, Vec<_>) = continuing
+            .iter()
+            .copied()
+            .partition(|candidate| totals[candidate] == fewest);
+
+        if !filled {
+            let active: f64 = totals.values().sum();
+            let majority = continuing
+                .iter()
+                .copied()
+                .find(|candidate| seats == 1 && totals[candidate] * 2.0 > active);
+
+            let round = count.record(&totals, exhausted, None);
+
+            if let Some(winner) = majority {
+                round.elected = vec![winner];
+                filled = true;
+            } else if remaining.len() < seats {
+                round.elected = continuing.clone();
+                filled = true;
+            } else {
+                round.excluded = losers.clone();
+            }
+        }
+
+        eliminated.push(losers);
+        continuing = remaining;
+    }
+
+    count.ranking = rank_groups(eliminated.into_iter().rev());
+    count
+}
+
+/// The state of a candidate during a single transferable vote count.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum State {
+    Hopeful,
+    Elected,
+    Excluded,
+}
+
+/// Keeps track of the candidates during a single transferable vote count and ranks them at the
+/// end.
+struct Stv {
+    seats: usize,
+    states: Vec<(i32, State)>,
+    elected: Vec<Vec<i32>>,
+    excluded: Vec<Vec<i32>>,
+    history: Vec<HashMap<i32, f64>>,
+}
+
+impl Stv {
+    fn new(candidates: Vec<i32>, seats: usize) -> Self {
+        Self {
+            seats,
+            states: candidates
+                .into_iter()
+                .map(|candidate| (candidate, State::Hopeful))
+                .collect(),
+            elected: Vec::new(),
+            excluded: Vec::new(),
+            history: Vec::new(),
+        }
+    }
+
+    fn state(&self, candidate: i32) -> State {
+        self.states.iter().find(|(c, _)| *c == candidate).unwrap().1
+    }
+
+    fn set_state(&mut self, candidate: i32, state: State) {
+        self.states
+            .iter_mut()
+            .find(|(c, _)| *c == candidate)
+            .unwrap()
+            .1 = state;
+    }
+
+    fn hopeful(&self) -> Vec<i32> {
+        self.states
+            .iter()
+            .filter_map(|(c, state)| (*state == State::Hopeful).then_some(*c))
+            .collect()
+    }
+
+    fn seats_left(&self) -> usize {
+        // Candidates tied on the quota can all be elected, filling more than the seats
+        self.seats
+            .saturating_sub(self.elected.iter().map(Vec::len).sum::<usize>())
+    }
+
+    /// Elects the given candidates, where those with equal totals share a rank.
+    fn elect(&mut self, candidates: Vec<i32>, totals: &HashMap<i32, f64>) {
+        let ranked = rank_by_totals(candidates.iter().map(|c| (*c, totals[c])).collect());
+
+        for group in group_ranks(ranked) {
+            for candidate in &group {
+                self.set_state(*candidate, State::Elected);
+            }
+
+            self.elected.push(group);
+        }
+    }
+
+    /// Finds the hopeful candidates with the fewest votes, using the totals at earlier stages to
+    /// separate any ties.
+    fn lowest(&self, totals: &HashMap<i32, f64>) -> Vec<i32> {
+        let mut lowest = self.hopeful();
+
+        for totals in std::iter::once(totals).chain(self.history.iter().rev()) {
+            let fewest = lowest
+                .iter()
+                .map(|c| totals.get(c).copied().unwrap_or_default())
+                .fold(f64::INFINITY, f64::min);
+
+            lowest.retain(|c| totals.get(c).copied().unwrap_or_default() - fewest < EPSILON);
+
+            if lowest.len() == 1 {
+                break;
+            }
+        }
+
+        lowest
+    }
+
+    /// Excludes the hopeful candidates with the fewest votes, returning whether the count should
+    /// continue.
+    ///
+    /// If candidates cannot be separated and excluding them all would leave too few to fill the
+    /// seats, the others are elected and the tied candidates share the next rank.
+    fn exclude_lowest(&mut self, totals: &HashMap<i32, f64>) -> bool {
+        let lowest = self.lowest(totals);
+        let hopeful = self.hopeful();
+
+        if hopeful.len() - lowest.len() >= self.seats_left() {
+            for candidate in &lowest {
+                self.set_state(*candidate, State::Excluded);
+            }
+
+            self.excluded.push(lowest);
+            return true;
+        }
+
+        let others = hopeful
+            .into_iter()
+            .filter(|c| !lowest.contains(c))
+            .collect();
+        self.elect(others, totals);
+
+        for candidate in &lowest {
+            self.set_state(*candidate, State::Elected);
+        }
+
+        self.elected.push(lowest);
+
+        false
+    }
+
+    /// Records the candidates elected or excluded since the states were last saved.
+    fn record_changes(&self, before: &[(i32, State)], round: &mut Round) {
+        for ((candidate, state), (_, previous)) in self.states.iter().zip(before) {
+            match (previous, state) {
+                (State::Hopeful, State::Elected) => round.elected.push(*candidate),
+                (State::Hopeful, State::Excluded) => round.excluded.push(*candidate),
+                _ => (),
+            }
+        }
+    }
+
+    /// Ranks the elected candidates in the order they were elected, followed by those still
+    /// hopeful by their final totals and then those excluded, most recent first.
+    fn ranking(self, totals: &HashMap<i32, f64>) -> Vec<Ranked> {
+        let hopeful = group_ranks(rank_by_totals(
+            self.states
+                .iter()
+                .filter(|(_, state)| *state == State::Hopeful)
+                .map(|(c, _)| (*c, totals.get(c).copied().unwrap_or_default()))
+                .collect(),
+        ));
+
+        rank_groups(
+            self.elected
+                .into_iter()
+                .chain(hopeful)
+                .chain(self.excluded.into_iter().rev()),
+        )
+    }
+}
+
+/// Splits a ranking back into the groups of candidates sharing each rank.
+fn group_ranks(ranking: Vec<Ranked>) -> Vec<Vec<i32>> {
+    let mut groups: Vec<(usize, Vec<i32>)> = Vec::new();
+
+    for ranked in ranking {
+        match groups.last_mut() {
+            Some((rank, group)) if *rank == ranked.rank => group.push(ranked.candidate),
+            _ => groups.push((ranked.rank, vec![ranked.candidate])),
+        }
+    }
+
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+/// Counts the ballots using Meek's method of the single transferable vote.
+///
+/// Each elected candidate keeps only enough of each vote to reach the quota, with the rest
+/// passing to the next preference. These keep values are recalculated until they settle, so
+/// votes are never stuck with a candidate who no longer needs them.
+fn meek(candidates: Vec<i32>, ballots: &[Vec<i32>], seats: usize) -> Count {
+    let mut count = Count::default();
+    let mut stv = Stv::new(candidates, seats);
+    let mut keep: HashMap<i32, f64> = stv.states.iter().map(|(c, _)| (*c, 1.0)).collect();
+
+    let distribute = |keep: &HashMap<i32, f64>| {
+        let mut totals: HashMap<i32, f64> = keep.keys().map(|c| (*c, 0.0)).collect();
+        let mut exhausted = 0.0;
+
+        for ballot in ballots {
+            let mut weight = 1.0;
+
+            for candidate in ballot {
+                let kept = weight * keep[candidate];
+                *totals.get_mut(candidate).unwrap() += kept;
+                weight -= kept;
+            }
+
+            exhausted += weight;
+        }
+
+        (totals, exhausted)
+    };
+
+    let mut totals = HashMap::new();
+
+    while stv.seats_left() > 0 {
+        let hopeful = stv.hopeful();
+        let before = stv.states.clone();
+
+        // Recalculate the keep values of the elected candidates until they settle
+        let mut quota = 0.0;
+        let mut exhausted = 0.0;
+
+        for _ in 0..MAX_ITERATIONS {
+            (totals, exhausted) = distribute(&keep);
+            quota = (ballots.len() as f64 - exhausted) / (seats + 1) as f64;
+
+            let mut settled = true;
+
+            for (candidate, state) in &stv.states {
+                if *state == State::Elected && totals[candidate] > 0.0 {
+                    if (totals[candidate] - quota).abs() > EPSILON {
+                        settled = false;
+                    }
+
+                    let value = keep.get_mut(candidate).unwrap();
+                    *value = (*value * quota / totals[candidate]).min(1.0);
+                }
+            }
+
+            if settled {
+                break;
+            }
+        }
+
+        let in_count = totals
+            .iter()
+            .filter(|(c, _)| stv.state(**c) != State::Excluded)
+            .map(|(c, total)| (*c, *total))
+            .collect();
+
+        let round = count.record(&in_count, exhausted, Some(quota));
+
+        let reached: Vec<_> = hopeful
+            .iter()
+            .copied()
+            .filter(|c| totals[c] >= quota - EPSILON)
+            .collect();
+
+        let continues = if hopeful.len() <= stv.seats_left() {
+            stv.elect(hopeful, &totals);
+            false
+        } else if reached.is_empty() {
+            let continues = stv.exclude_lowest(&totals);
+
+            for (candidate, state) in &stv.states {
+                if *state == State::Excluded {
+                    keep.insert(*candidate, 0.0);
+                }
+            }
+
+            continues
+        } else {
+            stv.elect(reached, &totals);
+            true
+        };
+
+        stv.record_changes(&before, round);
+
+        if !continues {
+            break;
+        }
+
+        stv.history.push(totals.clone());
+    }
+
+    count.ranking = stv.ranking(&totals);
+    count
+}
+
+/// A ballot paper in a Scottish STV count, along with its current value.
+struct Paper<'a> {
+    preferences: &'a [i32],
+    value: i64,
+}
+
+/// Moves a paper to its next preference that is still hopeful, if it has one, or otherwise sets
+/// it aside as non-transferable.
+fn place<'a>(paper: Paper<'a>, stv: &Stv, piles: &mut HashMap<i32, Vec<Paper<'a>>>) {
+    let next = paper
+        .preferences
+        .iter()
+        .position(|c| stv.state(*c) == State::Hopeful);
+
+    if let Some(index) = next {
+        let candidate = paper.preferences[index];
+
+        piles.get_mut(&candidate).unwrap().push(Paper {
+            preferences: &paper.preferences[index + 1..],
+            value: paper.value,
+        });
+    }
+}
+
+/// Gets the total value of the papers held by each candidate, where candidates whose surplus has
+/// been transferred keep exactly the quota.
+fn pile_totals(
+    piles: &HashMap<i32, Vec<Paper>>,
+    transferred: &[i32],
+    quota: i64,
+) -> HashMap<i32, f64> {
+    piles
+        .iter()
+        .map(|(c, pile)| {
+            let total = if transferred.contains(c) {
+                quota
+            } else {
+                pile.iter().map(|paper| paper.value).sum()
+            };
+
+            (*c, total as f64 / SCOTTISH_UNIT as f64)
+        })
+        .collect()
+}
+
+/// Counts the ballots using the single transferable vote as used for Scottish local government
+/// elections, transferring surpluses with the weighted inclusive Gregory method.
+///
+/// The quota is the Droop quota, and every paper held by an elected candidate is transferred at a
+/// reduced value, truncated to 5 decimal places. Surpluses are transferred largest first, one per
+/// stage, before any candidate is excluded.
+fn scottish(candidates: Vec<i32>, ballots: &[Vec<i32>], seats: usize) -> Count {
+    let mut count = Count::default();
+    let mut stv = Stv::new(candidates, seats);
+    let mut piles: HashMap<i32, Vec<Paper>> =
+        stv.states.iter().map(|(c, _)| (*c, Vec::new())).collect();
+    let mut transferred: Vec<i32> = Vec::new();
+
+    let valid = ballots.iter().filter(|ballot| !ballot.is_empty()).count() as i64;
+    let quota = (valid / (seats as i64 + 1) + 1) * SCOTTISH_UNIT;
+
+    for ballot in ballots {
+        let paper = Paper {
+            preferences: ballot,
+            value: SCOTTISH_UNIT,
+        };
+
+        place(paper, &stv, &mut piles);
+    }
+
+    let mut totals = pile_totals(&piles, &transferred, quota);
+
+    while stv.seats_left() > 0 {
+        let hopeful = stv.hopeful();
+        let before = stv.states.clone();
+
+        let in_count = totals
+            .iter()
+            .filter(|(c, _)| stv.state(**c) != State::Excluded)
+            .map(|(c, total)| (*c, *total))
+            .collect();
+
+        let exhausted = valid as f64 - totals.values().sum::<f64>();
+        let unit = SCOTTISH_UNIT as f64;
+        let round = count.record(&in_count, exhausted, Some(quota as f64 / unit));
+
+        let reached: Vec<_> = hopeful
+            .iter()
+            .copied()
+            .filter(|c| piles[c].iter().map(|paper| paper.value).sum::<i64>() >= quota)
+            .collect();
+
+        let filled = if !reached.is_empty() {
+            stv.elect(reached, &totals);
+            stv.seats_left() == 0
+        } else if hopeful.len() <= stv.seats_left() {
+            stv.elect(hopeful, &totals);
+            true
+        } else {
+            false
+        };
+
+        if filled {
+            stv.record_changes(&before, round);
+            break;
+        }
+
+        // Transfer the largest surplus that has not been transferred yet
+        let surplus = stv
+            .states
+            .iter()
+            .filter(|(c, state)| *state == State::Elected && !transferred.contains(c))
+            .map(|(c, _)| {
+                (
+                    *c,
+                    piles[c].iter().map(|paper| paper.value).sum::<i64>() - quota,
+                )
+            })
+            .filter(|(_, surplus)| *surplus > 0)
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)));
+
+        if let Some((candidate, surplus)) = surplus {
+            let pile = std::mem::take(piles.get_mut(&candidate).unwrap());
+            let total: i64 = pile.iter().map(|paper| paper.value).sum();
+
+            for paper in pile {
+                let value = paper.value * surplus / total;
+
+                let paper = Paper {
+                    preferences: paper.preferences,
+                    value,
+                };
+
+                place(paper, &stv, &mut piles);
+            }
+
+            transferred.push(candidate);
+            round.surplus = Some(candidate);
+            stv.record_changes(&before, round);
+        } else {
+            // Elected candidates without a surplus keep the quota
+            for (c, state) in &stv.states {
+                if *state == State::Elected && !transferred.contains(c) {
+                    transferred.push(*c);
+                }
+            }
+
+            let continues = stv.exclude_lowest(&totals);
+            stv.record_changes(&before, round);
+
+            if !continues {
+                break;
+            }
+
+            for candidate in stv.excluded.last().unwrap().clone() {
+                for paper in std::mem::take(piles.get_mut(&candidate).unwrap()) {
+                    place(paper, &stv, &mut piles);
+                }
+            }
+        }
+
+        stv.history.push(totals);
+        totals = pile_totals(&piles, &transferred, quota);
+    }
+
+    count.ranking = stv.ranking(&totals);
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Repeats each ballot the given number of times.
+    fn ballots(groups: &[(usize, &[i32])]) -> Vec<Vec<i32>> {
+        groups
+            .iter()
+            .flat_map(|(count, ballot)| std::iter::repeat_n(ballot.to_vec(), *count))
+            .collect()
+    }
+
+    // Memphis, Nashville, Chattanooga and Knoxville choosing a capital for Tennessee
+    const MEMPHIS: i32 = 1;
+    const NASHVILLE: i32 = 2;
+    const CHATTANOOGA: i32 = 3;
+    const KNOXVILLE: i32 = 4;
+
+    fn tennessee() -> Vec<Vec<i32>> {
+        ballots(&[
+            (42, &[MEMPHIS, NASHVILLE, CHATTANOOGA, KNOXVILLE]),
+            (26, &[NASHVILLE, CHATTANOOGA, KNOXVILLE, MEMPHIS]),
+            (15, &[CHATTANOOGA, KNOXVILLE, NASHVILLE, MEMPHIS]),
+            (17, &[KNOXVILLE, CHATTANOOGA, NASHVILLE, MEMPHIS]),
+        ])
+    }
+
+    // A party choosing three foods for its guests
+    const ORANGES: i32 = 1;
+    const PEARS: i32 = 2;
+    const CHOCOLATE: i32 = 3;
+    const STRAWBERRIES: i32 = 4;
+    const HAMBURGERS: i32 = 5;
+
+    fn food() -> Vec<Vec<i32>> {
+        ballots(&[
+            (4, &[ORANGES]),
+            (2, &[PEARS, ORANGES]),
+            (8, &[CHOCOLATE, STRAWBERRIES]),
+            (4, &[CHOCOLATE, HAMBURGERS]),
+            (1, &[STRAWBERRIES]),
+            (1, &[HAMBURGERS]),
+        ])
+    }
+
+    #[test]
+    fn fptp_elects_the_most_first_preferences() {
+        let ranking = count(CountingMethod::Fptp, &[], &tennessee(), 1).ranking;
+
+        assert_eq!(within_seats(&ranking, 1), vec![MEMPHIS]);
+        assert_eq!(
+            ranking[1],
+            Ranked {
+                candidate: NASHVILLE,
+                rank: 1
+            }
+        );
+    }
+
+    #[test]
+    fn fptp_ties_share_a_rank() {
+        let ballots = ballots(&[(2, &[1, 2]), (2, &[2, 1]), (1, &[3, 1])]);
+        let ranking = count(CountingMethod::Fptp, &[], &ballots, 1).ranking;
+
+        assert_eq!(within_seats(&ranking, 1), vec![1, 2]);
+        assert_eq!(
+            ranking[2],
+            Ranked {
+                candidate: 3,
+                rank: 2
+            }
+        );
+    }
+
+    #[test]
+    fn candidates_nobody_chose_are_counted_with_no_votes() {
+        let ballots = ballots(&[(3, &[1]), (2, &[2])]);
+
+        for method in [
+            CountingMethod::Fptp,
+            CountingMethod::Approval,
+            CountingMethod::Irv,
+        ] {
+            let count = count(method, &[1, 2, 3], &ballots, 1);
+
+            assert_eq!(
+                count.ranking.last(),
+                Some(&Ranked {
+                    candidate: 3,
+                    rank: 2
+                })
+            );
+            assert_eq!(total(&count.rounds[0], 3).votes, 0.0);
+        }
+    }
+
+    #[test]
+    fn approval_counts_every_candidate_on_a_ballot() {
+        // Every ballot ranks every city, so they are all approved of equally
+        let ranking = count(CountingMethod::Approval, &[], &tennessee(), 1).ranking;
+        assert_eq!(within_seats(&ranking, 1).len(), 4);
+
+        let ballots = ballots(&[(3, &[1]), (2, &[2, 3]), (2, &[3, 2, 2])]);
+        let ranking = count(CountingMethod::Approval, &[], &ballots, 1).ranking;
+
+        assert_eq!(
+            ranking,
+            vec![
+                Ranked {
+                    candidate: 2,
+                    rank: 0
+                },
+                Ranked {
+                    candidate: 3,
+                    rank: 0
+                },
+                Ranked {
+                    candidate: 1,
+                    rank: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn irv_transfers_eliminated_ballots() {
+        let ranking = count(CountingMethod::Irv, &[], &tennessee(), 1).ranking;
+
+        assert_eq!(within_seats(&ranking, 1), vec![KNOXVILLE]);
+        assert_eq!(within_seats(&ranking, 2), vec![KNOXVILLE, MEMPHIS]);
+        assert_eq!(
+            ranking[3],
+            Ranked {
+                candidate: CHATTANOOGA,
+                rank: 3
+            }
+        );
+    }
+
+    #[test]
+    fn single_seat_stv_matches_irv() {
+        for method in [CountingMethod::Meek, CountingMethod::Scottish] {
+            let ranking = count(method, &[], &tennessee(), 1).ranking;
+            assert_eq!(within_seats(&ranking, 1), vec![KNOXVILLE], "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn meek_transfers_surpluses_and_recalculates_the_quota() {
+        let ranking = count(CountingMethod::Meek, &[], &food(), 3).ranking;
+
+        assert_eq!(
+            within_seats(&ranking, 3),
+            vec![CHOCOLATE, STRAWBERRIES, ORANGES]
+        );
+    }
+
+    #[test]
+    fn scottish_stv_transfers_surpluses_before_excluding() {
+        let ranking = count(CountingMethod::Scottish, &[], &food(), 3).ranking;
+
+        assert_eq!(
+            within_seats(&ranking, 3),
+            vec![CHOCOLATE, ORANGES, STRAWBERRIES]
+        );
+        assert_eq!(ranking.last().unwrap().candidate, PEARS);
+    }
+
+    #[test]
+    fn scottish_stv_truncates_transfer_values() {
+        // The quota is 4, so a surplus of 2 spread over 6 papers is worth 0.33333 each
+        let ballots = ballots(&[(6, &[1, 2]), (3, &[3]), (2, &[4])]);
+        let count = count(CountingMethod::Scottish, &[], &ballots, 2);
+
+        assert_eq!(count.rounds[0].surplus, Some(1));
+        assert_eq!(total(&count.rounds[1], 2).votes, 1.99998);
+
+        // Without truncation 2 would tie with 4, but falls just short and is excluded first
+        assert_eq!(count.rounds[1].excluded, vec![2]);
+        assert_eq!(within_seats(&count.ranking, 2), vec![1, 3]);
+        assert_eq!(count.ranking.last().unwrap().candidate, 2);
+    }
+
+    #[test]
+    fn stv_ties_for_the_last_seat_share_a_rank() {
+        let ballots = ballots(&[(2, &[1]), (2, &[2])]);
+
+        for method in [CountingMethod::Meek, CountingMethod::Scottish] {
+            let ranking = count(method, &[], &ballots, 1).ranking;
+            assert_eq!(within_seats(&ranking, 1), vec![1, 2], "{:?}", method);
+            assert_eq!(ranking.len(), 2, "{:?}", method);
+        }
+    }
+
+    /// Gets the votes held by a candidate at a stage of the count.
+    fn total(round: &Round, candidate: i32) -> Total {
+        *round
+            .totals
+            .iter()
+            .find(|total| total.candidate == candidate)
+            .unwrap()
+    }
+
+    #[test]
+    fn single_stage_counts_have_one_round() {
+        let count = count(CountingMethod::Fptp, &[], &tennessee(), 1);
+
+        assert_eq!(count.rounds.len(), 1);
+        assert_eq!(total(&count.rounds[0], MEMPHIS).votes, 42.0);
+        assert_eq!(count.rounds[0].elected, vec![MEMPHIS]);
+        assert_eq!(count.rounds[0].quota, None);
+    }
+
+    #[test]
+    fn irv_rounds_show_each_elimination() {
+        let count = count(CountingMethod::Irv, &[], &tennessee(), 1);
+        let rounds = &count.rounds;
+
+        assert_eq!(rounds.len(), 3);
+        assert_eq!(rounds[0].excluded, vec![CHATTANOOGA]);
+        assert_eq!(
+            total(&rounds[1], KNOXVILLE),
+            Total {
+                candidate: KNOXVILLE,
+                votes: 32.0,
+                change: 15.0
+            }
+        );
+        assert_eq!(rounds[1].totals.len(), 3);
+        assert_eq!(rounds[1].excluded, vec![NASHVILLE]);
+
+        // Knoxville has a majority once Nashville's ballots are transferred, so the count stops
+        assert_eq!(rounds[2].elected, vec![KNOXVILLE]);
+        assert!(rounds[2].excluded.is_empty());
+        assert_eq!(total(&rounds[2], KNOXVILLE).votes, 58.0);
+    }
+
+    #[test]
+    fn irv_stops_at_a_first_round_majority() {
+        let ballots = ballots(&[(3, &[1, 2]), (1, &[2, 3]), (1, &[3, 2])]);
+        let count = count(CountingMethod::Irv, &[], &ballots, 1);
+
+        assert_eq!(count.rounds.len(), 1);
+        assert_eq!(count.rounds[0].elected, vec![1]);
+        assert_eq!(within_seats(&count.ranking, 1), vec![1]);
+    }
+
+    #[test]
+    fn meek_rounds_show_the_quota_shrinking() {
+        let count = count(CountingMethod::Meek, &[], &food(), 3);
+        let rounds = &count.rounds;
+
+        assert_eq!(rounds[0].quota, Some(5.0));
+        assert_eq!(rounds[0].elected, vec![CHOCOLATE]);
+        assert!((total(&rounds[1], CHOCOLATE).votes - 5.0).abs() < 1e-6);
+        assert!((total(&rounds[1], STRAWBERRIES).change - 14.0 / 3.0).abs() < 1e-6);
+        assert_eq!(rounds[1].elected, vec![STRAWBERRIES]);
+
+        // Ballots for chocolate and then strawberries have nowhere to go once both are elected
+        let last = rounds.last().unwrap();
+        assert!(last.quota.unwrap() < 5.0);
+        assert!(last.exhausted > 0.0);
+        assert_eq!(last.elected, vec![ORANGES]);
+    }
+
+    #[test]
+    fn scottish_stv_rounds_show_surpluses_and_exclusions() {
+        let count = count(CountingMethod::Scottish, &[], &food(), 3);
+        let rounds = &count.rounds;
+
+        assert_eq!(rounds.len(), 4);
+        assert_eq!(rounds[0].quota, Some(6.0));
+        assert_eq!(rounds[0].elected, vec![CHOCOLATE]);
+        assert_eq!(rounds[0].surplus, Some(CHOCOLATE));
+
+        assert_eq!(total(&rounds[1], CHOCOLATE).change, -6.0);
+        assert_eq!(total(&rounds[1], STRAWBERRIES).change, 4.0);
+        assert_eq!(rounds[1].excluded, vec![PEARS]);
+
+        assert_eq!(rounds[2].elected, vec![ORANGES]);
+        assert_eq!(rounds[2].excluded, vec![HAMBURGERS]);
+
+        assert_eq!(rounds[3].elected, vec![STRAWBERRIES]);
+        assert_eq!(rounds[3].exhausted, 3.0);
+        assert!(rounds[3]
+            .totals
+            .iter()
+            .all(|total| total.candidate != PEARS && total.candidate != HAMBURGERS));
+    }
+
+    #[test]
+    fn seats_without_ties_are_filled_in_order() {
+        let count = count(CountingMethod::Irv, &[], &tennessee(), 2);
+        let elected = break_ties(&count, 2, TieBreak::Lot, &[], "seed");
+
+        assert_eq!(elected.winners, count.ranking[..2].to_vec());
+        assert!(elected.ties.is_empty());
+    }
+
+    #[test]
+    fn backwards_tie_breaks_use_earlier_stages() {
+        // Candidates 1 and 2 are tied once candidate 3 is excluded, but 1 had more votes before
+        let ballots = ballots(&[(3, &[1]), (2, &[2]), (1, &[3, 2])]);
+        let count = count(CountingMethod::Irv, &[], &ballots, 1);
+        let elected = break_ties(&count, 1, TieBreak::Backwards, &[], "seed");
+
+        assert_eq!(within_seats(&count.ranking, 1), vec![1, 2]);
+        assert_eq!(
+            elected.ties,
+            vec![TiedSeat {
+                tied: vec![1, 2],
+                chosen: 1,
+                method: TieBreak::Backwards,
+                stage: Some(1),
+            }]
+        );
+    }
+
+    #[test]
+    fn casting_votes_prefer_the_highest_ranked_candidate() {
+        let ballots = ballots(&[(3, &[1]), (2, &[2]), (1, &[3, 2])]);
+        let count = count(CountingMethod::Irv, &[], &ballots, 1);
+
+        let elected = break_ties(&count, 1, TieBreak::Casting, &[3, 2, 1], "seed");
+        assert_eq!(elected.ties[0].chosen, 2);
+        assert_eq!(elected.ties[0].method, TieBreak::Casting);
+
+        // Casting votes without any of the tied candidates fall back to drawing lots
+        let elected = break_ties(&count, 1, TieBreak::Casting, &[3], "seed");
+        assert_eq!(elected.ties[0].method, TieBreak::Lot);
+    }
+
+    #[test]
+    fn lots_are_drawn_using_the_seed() {
+        let ballots = ballots(&[(2, &[1, 2]), (2, &[2, 1]), (1, &[3, 1])]);
+        let count = count(CountingMethod::Fptp, &[], &ballots, 1);
+
+        // The totals never differ, so backwards tie-breaks draw lots too
+        for method in [TieBreak::Lot, TieBreak::Backwards] {
+            let elected = break_ties(&count, 1, method, &[], "seed");
+
+            assert_eq!(
+                elected.winners,
+                vec![Ranked {
+                    candidate: 1,
+                    rank: 0
+                }]
+            );
+            assert_eq!(elected.ties[0].method, TieBreak::Lot);
+        }
+
+        let elected = break_ties(&count, 1, TieBreak::Lot, &[], "published");
+        assert_eq!(elected.ties[0].chosen, 2);
+    }
+
+    #[test]
+    fn each_tied_seat_is_filled_in_turn() {
+        let ballots = ballots(&[(1, &[1, 2, 3])]);
+        let count = count(CountingMethod::Approval, &[], &ballots, 2);
+        let elected = break_ties(&count, 2, TieBreak::Lot, &[], "seed");
+
+        let ties: Vec<_> = elected
+            .ties
+            .iter()
+            .map(|seat| (seat.tied.clone(), seat.chosen))
+            .collect();
+
+        assert_eq!(ties, vec![(vec![1, 2, 3], 3), (vec![1, 2], 1)]);
+    }
+}
@@ -0,0 +1,211 @@
+//! Protects forms that change state against cross-site request forgery.
+//!
+//! Each user is issued a random token, stored in a private cookie, which is rendered into every
+//! form as a hidden `csrf_token` field. Routes that change state accept a [`CsrfForm`] instead of
+//! a [`Form`], which rejects the request with `403 Forbidden` if the submitted token does not match
+//! the one in the cookie. Requests authenticated with a valid API token are not checked.
+//!
+//! Site administrators viewing the website as another user can't submit any forms other than the
+//! one to stop doing so, as nothing would record who really made the change.
+
+use std::ops::Deref;
+
+use rand::RngCore;
+use rocket::data::{Data, FromData, Outcome};
+use rocket::form::{self, DataField, Form, FromForm, Options, ValueField};
+use rocket::http::{Cookie, Status};
+use rocket::request::{self, FromRequest, Request};
+use serde::Serialize;
+
+use crate::guards::{impersonating, token_authenticated};
+
+/// The name of both the cookie and the form field containing the token.
+const FIELD_NAME: &str = "csrf_token";
+
+/// The number of random bytes in each token.
+const TOKEN_LENGTH: usize = 32;
+
+/// The only form that can be submitted while viewing the website as another user.
+const IMPERSONATION_STOP: &str = "/impersonate/stop";
+
+/// Represents the user's CSRF token, to be rendered into forms.
+#[derive(Clone, Debug, Serialize)]
+#[serde(transparent)]
+pub struct CsrfToken(pub String);
+
+impl CsrfToken {
+    /// Generates a new random token.
+    fn generate() -> Self {
+        let mut bytes = [0u8; TOKEN_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+
+        Self(base64::encode_config(bytes, base64::URL_SAFE_NO_PAD))
+    }
+
+    /// Gets the token stored in the user's cookies, if they have one.
+    fn from_cookies(request: &Request<'_>) -> Option<Self> {
+        request
+            .cookies()
+            .get_private(FIELD_NAME)
+            .map(|cookie| Self(cookie.value().to_string()))
+    }
+
+    /// Checks whether a submitted token matches this one, in constant time.
+    fn matches(&self, submitted: &str) -> bool {
+        let expected = self.0.as_bytes();
+        let submitted = submitted.as_bytes();
+
+        expected.len() == submitted.len()
+            && expected
+                .iter()
+                .zip(submitted)
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CsrfToken {
+    type Error = ();
+
+    /// Gets the user's token, issuing them a new one if they do not have one yet.
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, ()> {
+        let token = request.local_cache(|| {
+            Self::from_cookies(request).unwrap_or_else(|| {
+                let token = Self::generate();
+                request
+                    .cookies()
+                    .add_private(Cookie::new(FIELD_NAME, token.0.clone()));
+
+                token
+            })
+        });
+
+        request::Outcome::Success(token.clone())
+    }
+}
+
+/// Parses a form, separating out the CSRF token from the other fields.
+struct WithToken<T> {
+    token: Option<String>,
+    inner: T,
+}
+
+#[rocket::async_trait]
+impl<'r, T: FromForm<'r>> FromForm<'r> for WithToken<T> {
+    type Context = (Option<String>, T::Context);
+
+    fn init(opts: Options) -> Self::Context {
+        (None, T::init(opts))
+    }
+
+    fn push_value(ctxt: &mut Self::Context, field: ValueField<'r>) {
+        if field.name.source() == FIELD_NAME {
+            ctxt.0 = Some(field.value.to_string());
+        } else {
+            T::push_value(&mut ctxt.1, field);
+        }
+    }
+
+    async fn push_data(ctxt: &mut Self::Context, field: DataField<'r, '_>) {
+        T::push_data(&mut ctxt.1, field).await;
+    }
+
+    fn push_error(ctxt: &mut Self::Context, error: form::Error<'r>) {
+        T::push_error(&mut ctxt.1, error);
+    }
+
+    fn finalize((token, inner): Self::Context) -> form::Result<'r, Self> {
+        Ok(Self {
+            token,
+            inner: T::finalize(inner)?,
+        })
+    }
+}
+
+/// A form that has been submitted along with the user's CSRF token.
+#[derive(Debug)]
+pub struct CsrfForm<T>(T);
+
+impl<T> CsrfForm<T> {
+    /// Consumes the form, returning the inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for CsrfForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: FromForm<'r>> FromData<'r> for CsrfForm<T> {
+    type Error = form::Errors<'r>;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r, Self> {
+        let form = match Form::<WithToken<T>>::from_data(request, data).await {
+            Outcome::Success(form) => form.into_inner(),
+            Outcome::Failure(failure) => return Outcome::Failure(failure),
+            Outcome::Forward(data) => return Outcome::Forward(data),
+        };
+
+        // Browsers never add the `Authorization` header themselves, so requests using API tokens
+        // cannot be forged and have no cookie to compare against. Only tokens the user guard has
+        // already accepted count, so sending any header can't skip the check
+        let valid = match (CsrfToken::from_cookies(request), &form.token) {
+            _ if token_authenticated(request) => true,
+            (Some(expected), Some(submitted)) => expected.matches(submitted),
+            _ => false,
+        };
+
+        if !valid {
+            tracing::warn!(uri = %request.uri(), "Rejecting a form with an invalid CSRF token");
+
+            let error = form::Error::validation("Invalid CSRF token").with_name(FIELD_NAME);
+            return Outcome::Failure((Status::Forbidden, error.into()));
+        }
+
+        if impersonating(request) && request.uri().path().as_str() != IMPERSONATION_STOP {
+            tracing::warn!(uri = %request.uri(), "Rejecting a form submitted while impersonating");
+
+            let error = form::Error::validation("Forms cannot be submitted while impersonating");
+            return Outcome::Failure((Status::Forbidden, error.into()));
+        }
+
+        Outcome::Success(Self(form.inner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_tokens_are_unique() {
+        let first = CsrfToken::generate();
+        let second = CsrfToken::generate();
+
+        assert_ne!(first.0, second.0);
+    }
+
+    #[test]
+    fn tokens_match_themselves() {
+        let token = CsrfToken::generate();
+        let submitted = token.0.clone();
+
+        assert!(token.matches(&submitted));
+    }
+
+    #[test]
+    fn different_tokens_do_not_match() {
+        let token = CsrfToken(String::from("abcdef"));
+
+        assert!(!token.matches("abcdeg"));
+        assert!(!token.matches("abcde"));
+        assert!(!token.matches(""));
+    }
+}
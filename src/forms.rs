@@ -1,6 +1,19 @@
 //! Stores the expected structure of various forms for the user to submit.
 
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::RangeInclusive;
+
+use chrono::{DateTime, NaiveDate};
+use itertools::Itertools;
 use rocket::form::{self, FromFormField, ValueField};
+use rocket::fs::TempFile;
+use serde::{Serialize, Serializer};
+
+use crate::schema::custom_types::{CountingMethod, Formula, LiftStatus, Sex, TieBreak, Unit};
+use crate::schema::lift::{POWERLIFTING, WEIGHTLIFTING};
+use crate::schema::Lift;
+use crate::weight_class;
 
 /// Defines a custom struct that can only contain a valid Warwick ID.
 #[derive(Copy, Clone, Debug)]
@@ -65,23 +78,639 @@ pub struct Attendance {
     pub warwick_id: WarwickId,
 }
 
+/// The errors for each field of a form, keyed by the name of the field.
+pub type FieldErrors = BTreeMap<String, String>;
+
+/// The heaviest lift that is accepted, in kilograms.
+const MAX_LIFT: f32 = 600.0;
+
+/// Converts the weight of a lift entered in the given unit into kilograms, checking that it could
+/// be loaded onto a bar.
+///
+/// Weights in kilograms must be a multiple of 0.5kg, whereas weights in pounds are rounded to the
+/// nearest 0.5kg once converted.
+pub fn lift_in_kg(weight: f32, unit: Unit) -> Result<f32, String> {
+    let kg = unit.to_kg(weight);
+    let rounded = (kg * 2.0).round() / 2.0;
+
+    if !rounded.is_finite() || rounded <= 0.0 {
+        return Err(String::from("Please enter a positive weight."));
+    }
+
+    if unit == Unit::Kg && rounded != kg {
+        return Err(String::from("Please enter a multiple of 0.5kg."));
+    }
+
+    if rounded > MAX_LIFT {
+        return Err(format!(
+            "Please enter a weight of at most {}{}.",
+            unit.from_kg(MAX_LIFT),
+            unit
+        ));
+    }
+
+    Ok(rounded)
+}
+
 /// Defines the contents of the personal bests form.
 #[derive(Clone, Debug, FromForm)]
 pub struct PersonalBests {
-    /// The user's best squat.
-    pub squat: Option<f32>,
-    /// The user's best bench.
-    pub bench: Option<f32>,
-    /// The user's best deadlift.
-    pub deadlift: Option<f32>,
-    /// The user's best snatch.
-    pub snatch: Option<f32>,
-    /// The user's best clean and jerk.
-    pub clean_and_jerk: Option<f32>,
+    /// The user's new bests, keyed by the name of the lift in the catalogue, such as
+    /// `lifts[squat]`.
+    pub lifts: BTreeMap<String, Option<f32>>,
     /// Whether to display the user on the PL board.
     pub show_pl: bool,
     /// Whether to display the user on the WL board.
     pub show_wl: bool,
+    /// Whether to display the user on the boards for the other lifts in the catalogue.
+    pub show_other: bool,
+    /// The user's current bodyweight.
+    pub bodyweight: Option<f32>,
+    /// The day the bodyweight was measured, defaulting to today.
+    pub bodyweight_recorded_on: Option<String>,
+    /// The category the user competes in.
+    pub sex: Option<Sex>,
+    /// The weight class the user competes in, worked out from their bodyweight if not chosen.
+    pub weight_class: Option<String>,
+    /// The unit the weights were entered in, which becomes the user's preference if given.
+    /// Kilograms are assumed otherwise.
+    pub unit: Option<Unit>,
+    /// The formula to estimate the user's one rep maxes with, which becomes their preference if
+    /// given.
+    pub formula: Option<Formula>,
+}
+
+impl PersonalBests {
+    /// The range of bodyweights that are accepted, in kilograms.
+    const BODYWEIGHT_RANGE: RangeInclusive<f32> = 30.0..=300.0;
+
+    /// Gets the submitted weight class, if one was chosen.
+    pub fn weight_class(&self) -> Option<&str> {
+        self.weight_class
+            .as_deref()
+            .map(str::trim)
+            .filter(|class| !class.is_empty())
+    }
+
+    /// Gets the day the bodyweight was measured, if one was given.
+    pub fn bodyweight_recorded_on(&self) -> Option<NaiveDate> {
+        self.bodyweight_recorded_on
+            .as_deref()
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+    }
+
+    /// Gets the lifts that were given a weight, by the name of the lift.
+    pub fn lifts(&self) -> BTreeMap<String, f32> {
+        self.lifts
+            .iter()
+            .filter_map(|(lift, weight)| weight.map(|weight| (lift.clone(), weight)))
+            .collect()
+    }
+
+    /// Checks that the submitted values make sense, converting every weight into kilograms.
+    ///
+    /// Every lift must be in the given catalogue. If any values are invalid, the problem with
+    /// each of them is returned instead.
+    pub fn validate(mut self, catalogue: &[Lift]) -> Result<Self, FieldErrors> {
+        let mut errors = FieldErrors::new();
+        let unit = self.unit.unwrap_or_default();
+
+        for (lift, weight) in &mut self.lifts {
+            let value = match *weight {
+                Some(value) => value,
+                None => continue,
+            };
+
+            if !catalogue.iter().any(|known| known.name == *lift) {
+                errors.insert(lift.clone(), format!("{} is not in the catalogue.", lift));
+                continue;
+            }
+
+            match lift_in_kg(value, unit) {
+                Ok(kg) => *weight = Some(kg),
+                Err(message) => {
+                    errors.insert(lift.clone(), message);
+                }
+            }
+        }
+
+        if let Some(bodyweight) = self.bodyweight {
+            let kg = unit.to_kg(bodyweight);
+
+            if Self::BODYWEIGHT_RANGE.contains(&kg) {
+                self.bodyweight = Some(kg);
+            } else {
+                let (min, max) = Self::BODYWEIGHT_RANGE.into_inner();
+
+                errors.insert(
+                    String::from("bodyweight"),
+                    format!(
+                        "Please enter a bodyweight between {}{} and {}{}.",
+                        unit.from_kg(min),
+                        unit,
+                        unit.from_kg(max),
+                        unit
+                    ),
+                );
+            }
+        }
+
+        let has_date = self
+            .bodyweight_recorded_on
+            .as_deref()
+            .map(str::trim)
+            .filter(|date| !date.is_empty())
+            .is_some();
+
+        if has_date && self.bodyweight_recorded_on().is_none() {
+            errors.insert(
+                String::from("bodyweight_recorded_on"),
+                String::from("Please enter a valid date for your bodyweight."),
+            );
+        }
+
+        match (self.sex, self.weight_class()) {
+            (None, Some(_)) => {
+                errors.insert(
+                    String::from("weight_class"),
+                    String::from("Please choose a category before choosing a weight class."),
+                );
+            }
+            (Some(sex), Some(class)) if !weight_class::is_valid(sex, class) => {
+                let message = format!("{} is not a weight class in the {} category.", class, sex);
+                errors.insert(String::from("weight_class"), message);
+            }
+            _ => (),
+        }
+
+        // Lifters who don't choose a class compete in the one for their bodyweight
+        if self.weight_class().is_none() && !errors.contains_key("bodyweight") {
+            if let (Some(sex), Some(kg)) = (self.sex, self.bodyweight) {
+                self.weight_class = Some(weight_class::for_bodyweight(sex, kg));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Defines the columns the boards can be sorted by.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BoardSort {
+    /// Sorts lifters by their best for a lift in the catalogue, given its name.
+    Lift(String),
+    /// Sorts lifters by their total for the board.
+    Total,
+    /// Sorts lifters by their DOTS score.
+    Dots,
+    /// Sorts lifters by their IPF GL points.
+    IpfGl,
+    /// Sorts lifters by their original Wilks score.
+    Wilks,
+    /// Sorts lifters by their 2020 Wilks score.
+    Wilks2020,
+    /// Sorts lifters by their Sinclair score.
+    Sinclair,
+}
+
+impl BoardSort {
+    /// Gets the value used for the sort in the query string.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Lift(lift) => lift,
+            Self::Total => "total",
+            Self::Dots => "dots",
+            Self::IpfGl => "ipf_gl",
+            Self::Wilks => "wilks",
+            Self::Wilks2020 => "wilks_2020",
+            Self::Sinclair => "sinclair",
+        }
+    }
+
+    /// Checks whether a board can be sorted this way, given the lifts in the catalogue.
+    pub fn is_for(&self, board: &str, catalogue: &[Lift]) -> bool {
+        match self {
+            Self::Lift(name) => catalogue
+                .iter()
+                .any(|lift| lift.name == *name && lift.board == board),
+            Self::Total => true,
+            Self::Dots | Self::IpfGl | Self::Wilks | Self::Wilks2020 => board == POWERLIFTING,
+            Self::Sinclair => board == WEIGHTLIFTING,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for BoardSort {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(match field.value {
+            "total" => Self::Total,
+            "dots" => Self::Dots,
+            "ipf_gl" => Self::IpfGl,
+            "wilks" => Self::Wilks,
+            "wilks_2020" => Self::Wilks2020,
+            "sinclair" => Self::Sinclair,
+            lift => Self::Lift(String::from(lift)),
+        })
+    }
+}
+
+impl Serialize for BoardSort {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// Defines the query parameters for sorting and filtering the boards.
+#[derive(Debug, Default, FromForm)]
+pub struct BoardQuery {
+    /// How to sort the powerlifting board.
+    pub pl_sort: Option<BoardSort>,
+    /// How to sort the weightlifting board.
+    pub wl_sort: Option<BoardSort>,
+    /// The category to show lifters from.
+    pub sex: Option<Sex>,
+    /// The weight class to show lifters from.
+    pub class: Option<String>,
+    /// Whether to show one rep maxes estimated from sets of multiple reps.
+    pub estimated: Option<bool>,
+    /// The formula to estimate one rep maxes with.
+    pub formula: Option<Formula>,
+}
+
+impl BoardQuery {
+    /// Gets how to sort the powerlifting board, defaulting to the total.
+    pub fn pl_sort(&self, catalogue: &[Lift]) -> BoardSort {
+        self.pl_sort
+            .clone()
+            .filter(|sort| sort.is_for(POWERLIFTING, catalogue))
+            .unwrap_or(BoardSort::Total)
+    }
+
+    /// Gets how to sort the weightlifting board, defaulting to the total.
+    pub fn wl_sort(&self, catalogue: &[Lift]) -> BoardSort {
+        self.wl_sort
+            .clone()
+            .filter(|sort| sort.is_for(WEIGHTLIFTING, catalogue))
+            .unwrap_or(BoardSort::Total)
+    }
+
+    /// Checks whether to show one rep maxes estimated from sets of multiple reps.
+    pub fn estimated(&self) -> bool {
+        self.estimated.unwrap_or(false)
+    }
+
+    /// Gets the formula to estimate one rep maxes with, defaulting to Epley.
+    pub fn formula(&self) -> Formula {
+        self.formula.unwrap_or_default()
+    }
+
+    /// Gets the weight class to show lifters from, if one was chosen.
+    pub fn class(&self) -> Option<&str> {
+        self.class
+            .as_deref()
+            .map(str::trim)
+            .filter(|class| !class.is_empty())
+    }
+}
+
+/// Defines the contents of the form for recording a single lift.
+#[derive(Debug, FromForm)]
+pub struct LiftRecord {
+    /// The lift that was performed.
+    pub lift: String,
+    /// The weight lifted.
+    pub weight: f32,
+    /// The number of repetitions, if more than a single.
+    pub reps: Option<i32>,
+    /// How hard the set was on the RPE scale, if the user recorded it.
+    pub rpe: Option<f32>,
+    /// The day the lift was performed.
+    pub recorded_on: String,
+    /// Any note to keep with the lift.
+    pub note: Option<String>,
+    /// A link to a video of the lift, for a coach to verify it with.
+    pub video_url: Option<String>,
+}
+
+impl LiftRecord {
+    /// Gets the submitted video link, if one was given.
+    pub fn video_url(&self) -> Option<&str> {
+        self.video_url
+            .as_deref()
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+    }
+}
+
+/// Defines the contents of the form for deleting a recorded lift.
+#[derive(Debug, FromForm)]
+pub struct LiftRecordDelete {
+    /// The identifier for the record.
+    pub record_id: i32,
+}
+
+/// Defines the contents of the form for verifying or rejecting a recorded lift.
+#[derive(Debug, FromForm)]
+pub struct LiftReview {
+    /// The identifier for the record.
+    pub record_id: i32,
+    /// Whether the lift is verified or rejected.
+    pub status: LiftStatus,
+}
+
+/// Defines the contents of the form for adding a lift to the catalogue, or changing how one is
+/// displayed.
+#[derive(Debug, FromForm)]
+pub struct LiftCreate {
+    /// The name the lift is recorded under, such as `log_press`.
+    pub name: String,
+    /// The name to display for the lift.
+    pub display_name: String,
+    /// The abbreviation used in the column headers of the boards.
+    pub abbreviation: String,
+    /// The board the lift is shown on.
+    pub board: String,
+    /// Where the lift appears relative to the others.
+    pub position: Option<i32>,
+}
+
+impl LiftCreate {
+    /// Checks the lift can be saved, returning a message describing the problem if not.
+    ///
+    /// The boards with scores have their totals worked out from their lifts, so lifts cannot be
+    /// moved onto or off them. The lift already in the catalogue is given when changing one.
+    pub fn validate(&self, existing: Option<&Lift>) -> Result<(), &'static str> {
+        let mut chars = self.name.chars();
+        let valid_name = matches!(chars.next(), Some(c) if c.is_ascii_lowercase())
+            && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+
+        if !valid_name {
+            return Err("Lift names must start with a letter and only use lowercase letters, digits and underscores.");
+        }
+
+        let required = [&self.display_name, &self.abbreviation, &self.board];
+
+        if required.iter().any(|value| value.trim().is_empty()) {
+            return Err("Please enter a display name, abbreviation and board for the lift.");
+        }
+
+        let board = self.board.trim();
+        let scored = [POWERLIFTING, WEIGHTLIFTING]
+            .iter()
+            .any(|scored| board.eq_ignore_ascii_case(scored));
+
+        match existing {
+            Some(lift) if lift.is_scored() && lift.board != board => {
+                Err("Lifts on the powerlifting and weightlifting boards cannot be moved.")
+            }
+            Some(lift) if lift.is_scored() => Ok(()),
+            _ if scored => Err("Lifts cannot be added to the powerlifting or weightlifting boards, since their totals depend on them."),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Defines the contents of the form for removing a lift from the catalogue.
+#[derive(Debug, FromForm)]
+pub struct LiftDelete {
+    /// The name the lift is recorded under.
+    pub name: String,
+}
+
+/// Defines the contents of the form for opening or closing voting for a position.
+#[derive(Debug, FromForm)]
+pub struct PositionToggle {
+    /// The identifier for the position.
+    pub position_id: i32,
+}
+
+/// Defines the information needed to add or change an exec position.
+#[derive(Debug, FromForm)]
+pub struct Position {
+    /// The title of the position.
+    pub title: String,
+    /// The number of people who can win the position.
+    pub num_winners: i32,
+    /// Whether re-opening nominations is offered on the ballot.
+    pub ron: bool,
+    /// The method used to count the ballots.
+    pub counting_method: CountingMethod,
+    /// How ties for the last seats are broken.
+    pub tie_break: TieBreak,
+}
+
+impl Position {
+    /// Checks the position can be saved, returning a message describing the problem if not.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.title.trim().is_empty() {
+            return Err("Please enter a title for the position.");
+        }
+
+        if self.num_winners < 1 {
+            return Err("Positions must have at least one winner.");
+        }
+
+        Ok(())
+    }
+}
+
+/// Defines the contents of the form for recording the casting vote used to break ties.
+#[derive(Debug, FromForm)]
+pub struct CastingVote {
+    /// The identifier for the position.
+    pub position_id: i32,
+    /// The candidates in order of preference.
+    pub casting_vote: Vec<i32>,
+}
+
+impl CastingVote {
+    /// Checks the casting vote ranks every one of the given candidates exactly once, returning a
+    /// message describing the problem if not.
+    pub fn validate(&self, candidates: &[i32]) -> Result<(), &'static str> {
+        if self.casting_vote.iter().unique().count() != self.casting_vote.len() {
+            return Err("Each candidate can only be ranked once on the casting vote.");
+        }
+
+        if self.casting_vote.len() != candidates.len()
+            || !self.casting_vote.iter().all(|c| candidates.contains(c))
+        {
+            return Err("The casting vote needs to rank every candidate for the position.");
+        }
+
+        Ok(())
+    }
+}
+
+/// Defines the contents of the form for scheduling when voting for a position opens and closes.
+#[derive(Debug, FromForm)]
+pub struct PositionSchedule {
+    /// The identifier for the position.
+    pub position_id: i32,
+    /// When voting opens, as `YYYY-MM-DDTHH:MM+HH:MM` with the UTC offset of the administrator's
+    /// browser, if it opens automatically.
+    pub opens_at: Option<String>,
+    /// When voting closes, as `YYYY-MM-DDTHH:MM+HH:MM` with the UTC offset of the administrator's
+    /// browser, if it closes automatically.
+    pub closes_at: Option<String>,
+}
+
+impl PositionSchedule {
+    /// Converts the submitted times into Unix timestamps, checking that voting closes after it
+    /// opens. Empty times are left out of the schedule.
+    ///
+    /// Times without a UTC offset are rejected rather than guessing which time zone they are in,
+    /// since the server's may differ from the administrator's.
+    pub fn timestamps(&self) -> Result<(Option<i64>, Option<i64>), &'static str> {
+        let parse = |value: &Option<String>| match value.as_deref().map(str::trim) {
+            None | Some("") => Ok(None),
+            Some(value) => DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M%:z")
+                .map(|datetime| Some(datetime.timestamp()))
+                .map_err(|_| {
+                    "Please enter the times as a date and time along with their UTC offset."
+                }),
+        };
+
+        let opens_at = parse(&self.opens_at)?;
+        let closes_at = parse(&self.closes_at)?;
+
+        if let (Some(opens_at), Some(closes_at)) = (opens_at, closes_at) {
+            if closes_at <= opens_at {
+                return Err("Voting must close after it opens.");
+            }
+        }
+
+        Ok((opens_at, closes_at))
+    }
+}
+
+/// Defines the contents of the form for recounting ballots from a BLT file.
+#[derive(Debug, FromForm)]
+pub struct BallotRecount<'r> {
+    /// The ballots in the BLT format.
+    pub ballots: TempFile<'r>,
+    /// The method to count the ballots with.
+    pub counting_method: CountingMethod,
+}
+
+/// Defines the information needed to change an existing exec position.
+#[derive(Debug, FromForm)]
+pub struct PositionEdit {
+    /// The identifier for the position.
+    pub position_id: i32,
+    /// The new details of the position.
+    pub position: Position,
+}
+
+/// Defines the information needed to add a candidate to the election.
+#[derive(Debug, FromForm)]
+pub struct CandidateCreate {
+    /// The candidate's Warwick ID.
+    pub warwick_id: WarwickId,
+    /// The candidate's name.
+    pub name: String,
+}
+
+/// Defines the information needed to remove a candidate from the election.
+#[derive(Debug, FromForm)]
+pub struct CandidateDelete {
+    /// The candidate's Warwick ID.
+    pub warwick_id: i32,
+}
+
+/// Defines the contents of the forms for nominating a candidate for a position or withdrawing
+/// the nomination.
+#[derive(Debug, FromForm)]
+pub struct NominationChange {
+    /// The identifier for the position.
+    pub position_id: i32,
+    /// The candidate's Warwick ID.
+    pub warwick_id: i32,
+}
+
+/// Defines the information needed to start a new election.
+#[derive(Debug, FromForm)]
+pub struct ElectionCreate {
+    /// The name of the election, such as `2023 AGM`.
+    pub name: String,
+    /// The day the election is held.
+    pub held_on: String,
+}
+
+impl ElectionCreate {
+    /// Gets the day the election is held, if it is a valid date.
+    pub fn held_on(&self) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(&self.held_on, "%Y-%m-%d").ok()
+    }
+}
+
+/// Defines the contents of the form for finishing the current election.
+#[derive(Debug, FromForm)]
+pub struct ElectionArchive {
+    /// Whether the administrator has confirmed the election should no longer change.
+    pub confirm: bool,
+}
+
+/// Defines the contents of the form for granting or revoking a role.
+#[derive(Debug, FromForm)]
+pub struct RoleChange {
+    /// The Warwick ID of the user whose roles are changing.
+    pub warwick_id: WarwickId,
+    /// The name of the role.
+    pub role: String,
+}
+
+/// Defines the contents of the forms for viewing the website as another user.
+#[derive(Debug, FromForm)]
+pub struct Impersonate {
+    /// The Warwick ID of the user to start or stop viewing the website as.
+    pub warwick_id: WarwickId,
+}
+
+/// Defines the contents of the form for creating an API token.
+#[derive(Debug, FromForm)]
+pub struct TokenCreate {
+    /// The name to give the token, to help the user recognise it.
+    pub name: String,
+    /// The roles the token is allowed to use.
+    pub scopes: Vec<String>,
+}
+
+/// Defines the contents of the form for revoking an API token.
+#[derive(Debug, FromForm)]
+pub struct TokenRevoke {
+    /// The identifier for the token.
+    pub token_id: i32,
+}
+
+/// Reads the contents of an uploaded file from wherever Rocket stored it, so that uploads don't
+/// need copying anywhere else where they could collide.
+pub async fn read_upload(file: &TempFile<'_>) -> io::Result<String> {
+    match (file.path(), file) {
+        (Some(path), _) => rocket::tokio::fs::read_to_string(path).await,
+        (None, TempFile::Buffered { content }) => Ok(String::from(*content)),
+        (None, _) => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "The upload has no contents",
+        )),
+    }
+}
+
+/// Defines the contents of the form for importing memberships from the students' union.
+#[derive(Debug, FromForm)]
+pub struct MembershipImport<'r> {
+    /// The CSV export from the students' union.
+    pub export: TempFile<'r>,
+    /// The first day the memberships are valid.
+    pub valid_from: String,
+    /// The last day the memberships are valid.
+    pub valid_to: String,
+    /// Whether to apply the changes, rather than just previewing them.
+    pub apply: bool,
 }
 
 #[cfg(test)]
@@ -89,6 +718,81 @@ mod tests {
     use rocket::form::name::NameView;
 
     use super::*;
+    use crate::schema::lift::original_lifts;
+
+    #[test]
+    fn positions_need_a_title_and_a_winner() {
+        let mut position = Position {
+            title: String::from("  "),
+            num_winners: 1,
+            ron: true,
+            counting_method: CountingMethod::Irv,
+            tie_break: TieBreak::Backwards,
+        };
+
+        assert!(position.validate().is_err());
+
+        position.title = String::from("Treasurer");
+        assert!(position.validate().is_ok());
+
+        position.num_winners = 0;
+        assert!(position.validate().is_err());
+    }
+
+    #[test]
+    fn casting_votes_rank_every_candidate_once() {
+        let vote = |casting_vote: &[i32]| CastingVote {
+            position_id: 1,
+            casting_vote: casting_vote.to_vec(),
+        };
+
+        assert!(vote(&[2, 0, 1]).validate(&[0, 1, 2]).is_ok());
+        assert!(vote(&[2, 2, 1]).validate(&[0, 1, 2]).is_err());
+        assert!(vote(&[2, 1]).validate(&[0, 1, 2]).is_err());
+        assert!(vote(&[2, 3, 1]).validate(&[0, 1, 2]).is_err());
+    }
+
+    fn schedule(opens_at: &str, closes_at: &str) -> PositionSchedule {
+        PositionSchedule {
+            position_id: 1,
+            opens_at: Some(String::from(opens_at)),
+            closes_at: Some(String::from(closes_at)),
+        }
+    }
+
+    #[test]
+    fn schedules_can_leave_out_either_time() {
+        let (opens_at, closes_at) = schedule("", "2023-03-01T18:00+00:00").timestamps().unwrap();
+
+        assert_eq!(opens_at, None);
+        assert_eq!(closes_at, Some(1677693600));
+    }
+
+    #[test]
+    fn schedules_must_close_after_they_open() {
+        assert!(schedule("2023-03-01T18:00+00:00", "2023-03-01T17:00+00:00")
+            .timestamps()
+            .is_err());
+        assert!(schedule("2023-03-01T18:00+00:00", "tomorrow")
+            .timestamps()
+            .is_err());
+        assert!(schedule("2023-03-01T18:00+00:00", "2023-03-01T19:30+00:00")
+            .timestamps()
+            .is_ok());
+    }
+
+    #[test]
+    fn schedules_use_the_submitted_utc_offset() {
+        let (opens_at, closes_at) = schedule("2023-03-01T18:00+01:00", "2023-03-01T18:00-05:00")
+            .timestamps()
+            .unwrap();
+
+        assert_eq!(opens_at, Some(1677690000));
+        assert_eq!(closes_at, Some(1677711600));
+
+        // The server's time zone is never assumed
+        assert!(schedule("2023-03-01T18:00", "").timestamps().is_err());
+    }
 
     #[test]
     fn invalid_identifiers_are_not_parsed() {
@@ -104,6 +808,158 @@ mod tests {
         }
     }
 
+    fn personal_bests() -> PersonalBests {
+        PersonalBests {
+            lifts: BTreeMap::new(),
+            show_pl: false,
+            show_wl: false,
+            show_other: false,
+            bodyweight: None,
+            bodyweight_recorded_on: None,
+            sex: None,
+            weight_class: None,
+            unit: None,
+            formula: None,
+        }
+    }
+
+    #[test]
+    fn personal_bests_with_valid_classes_are_accepted() {
+        let mut data = personal_bests();
+        data.bodyweight = Some(82.5);
+        data.bodyweight_recorded_on = Some(String::from("2022-10-01"));
+        data.sex = Some(Sex::Female);
+        data.weight_class = Some(String::from("84+"));
+
+        assert!(data.validate(&original_lifts()).is_ok());
+    }
+
+    #[test]
+    fn weight_classes_must_match_the_category() {
+        let mut data = personal_bests();
+        data.weight_class = Some(String::from("120+"));
+
+        assert!(data.clone().validate(&original_lifts()).is_err());
+
+        data.sex = Some(Sex::Female);
+        let errors = data.clone().validate(&original_lifts()).unwrap_err();
+        assert!(errors.contains_key("weight_class"));
+
+        data.sex = Some(Sex::Male);
+        assert!(data.validate(&original_lifts()).is_ok());
+    }
+
+    #[test]
+    fn weight_classes_default_to_the_class_for_the_bodyweight() {
+        let mut data = personal_bests();
+        data.bodyweight = Some(180.0);
+        data.sex = Some(Sex::Male);
+        data.unit = Some(Unit::Lb);
+
+        let validated = data.clone().validate(&original_lifts()).unwrap();
+        assert_eq!(validated.weight_class(), Some("83"));
+
+        // Chosen classes are kept
+        data.weight_class = Some(String::from("93"));
+        let validated = data.validate(&original_lifts()).unwrap();
+        assert_eq!(validated.weight_class(), Some("93"));
+    }
+
+    #[test]
+    fn empty_weight_classes_are_ignored() {
+        let mut data = personal_bests();
+        data.weight_class = Some(String::new());
+
+        assert_eq!(data.weight_class(), None);
+        assert!(data.validate(&original_lifts()).is_ok());
+    }
+
+    #[test]
+    fn unrealistic_bodyweights_are_rejected() {
+        let mut data = personal_bests();
+        data.bodyweight = Some(5.0);
+
+        assert!(data.clone().validate(&original_lifts()).is_err());
+
+        data.bodyweight = Some(75.0);
+        data.bodyweight_recorded_on = Some(String::from("yesterday"));
+
+        let errors = data.validate(&original_lifts()).unwrap_err();
+        assert_eq!(
+            errors.keys().collect::<Vec<_>>(),
+            vec!["bodyweight_recorded_on"]
+        );
+    }
+
+    #[test]
+    fn invalid_lifts_are_reported_for_each_field() {
+        let mut data = personal_bests();
+        data.lifts.insert(String::from("squat"), Some(-100.0));
+        data.lifts.insert(String::from("bench"), Some(100.25));
+        data.lifts.insert(String::from("deadlift"), Some(1000.0));
+        data.lifts.insert(String::from("snatch"), Some(80.0));
+        data.lifts.insert(String::from("yoke"), Some(200.0));
+
+        let errors = data.validate(&original_lifts()).unwrap_err();
+
+        assert_eq!(errors["squat"], "Please enter a positive weight.");
+        assert_eq!(errors["bench"], "Please enter a multiple of 0.5kg.");
+        assert!(errors.contains_key("deadlift"));
+        assert!(!errors.contains_key("snatch"));
+        assert_eq!(errors["yoke"], "yoke is not in the catalogue.");
+    }
+
+    #[test]
+    fn pounds_are_converted_to_kilograms() {
+        let mut data = personal_bests();
+        data.unit = Some(Unit::Lb);
+        data.lifts.insert(String::from("squat"), Some(315.0));
+        data.lifts.insert(String::from("bench"), None);
+        data.bodyweight = Some(180.0);
+
+        let data = data.validate(&original_lifts()).unwrap();
+
+        // 315lb is 142.88kg, which is rounded to the nearest 0.5kg, and empty lifts are skipped
+        assert_eq!(
+            data.lifts().into_iter().collect::<Vec<_>>(),
+            vec![(String::from("squat"), 143.0)]
+        );
+        assert!((data.bodyweight.unwrap() - 81.65).abs() < 0.01);
+    }
+
+    #[test]
+    fn lifts_are_limited_in_the_chosen_unit() {
+        assert_eq!(lift_in_kg(100.0, Unit::Kg), Ok(100.0));
+        assert!(lift_in_kg(0.2, Unit::Lb).is_err());
+        assert_eq!(
+            lift_in_kg(1500.0, Unit::Lb),
+            Err(String::from("Please enter a weight of at most 1322.8lb."))
+        );
+    }
+
+    #[test]
+    fn board_sorts_default_to_the_total() {
+        let lifts = original_lifts();
+        let clean_and_jerk = BoardSort::Lift(String::from("clean_and_jerk"));
+        let query = BoardQuery {
+            pl_sort: Some(BoardSort::Sinclair),
+            wl_sort: Some(clean_and_jerk.clone()),
+            ..BoardQuery::default()
+        };
+
+        assert_eq!(query.pl_sort(&lifts), BoardSort::Total);
+        assert_eq!(query.wl_sort(&lifts), clean_and_jerk);
+        assert_eq!(BoardQuery::default().wl_sort(&lifts), BoardSort::Total);
+
+        // Lifts can only sort the board they are shown on
+        let query = BoardQuery {
+            pl_sort: Some(clean_and_jerk),
+            ..BoardQuery::default()
+        };
+
+        assert_eq!(query.pl_sort(&lifts), BoardSort::Total);
+    }
+
     #[test]
     fn valid_identifiers_are_parsed() {
         let identifiers = vec!["1702502", "1820900"];
@@ -117,4 +973,45 @@ mod tests {
             assert!(WarwickId::from_value(value_field).is_ok());
         }
     }
+
+    fn lift(name: &str, board: &str) -> LiftCreate {
+        LiftCreate {
+            name: String::from(name),
+            display_name: String::from("Log Press"),
+            abbreviation: String::from("LOG"),
+            board: String::from(board),
+            position: None,
+        }
+    }
+
+    #[test]
+    fn lift_names_must_be_lowercase_identifiers() {
+        assert!(lift("log_press", "Strongman").validate(None).is_ok());
+        assert!(lift("Log Press", "Strongman").validate(None).is_err());
+        assert!(lift("1rm", "Strongman").validate(None).is_err());
+        assert!(lift("", "Strongman").validate(None).is_err());
+    }
+
+    #[test]
+    fn lifts_need_a_board() {
+        assert!(lift("log_press", " ").validate(None).is_err());
+    }
+
+    #[test]
+    fn lifts_stay_on_or_off_the_scored_boards() {
+        assert!(lift("log_press", POWERLIFTING).validate(None).is_err());
+        assert!(lift("log_press", "weightlifting").validate(None).is_err());
+
+        let squat = Lift::new("squat", "Squat", "SQ", POWERLIFTING);
+        assert!(lift("squat", POWERLIFTING).validate(Some(&squat)).is_ok());
+        assert!(lift("squat", "Strongman").validate(Some(&squat)).is_err());
+
+        let log_press = Lift::new("log_press", "Log Press", "LOG", "Strongman");
+        assert!(lift("log_press", "Accessories")
+            .validate(Some(&log_press))
+            .is_ok());
+        assert!(lift("log_press", POWERLIFTING)
+            .validate(Some(&log_press))
+            .is_err());
+    }
 }
@@ -1,20 +1,22 @@
 //! Handles the routes that return Templates for the user to view.
 
 use std::collections::{BTreeMap, HashMap};
-use std::env;
-use std::str::FromStr;
 
 use itertools::Itertools;
 use rand::seq::SliceRandom;
+use rocket::http::{ContentType, CookieJar, Header};
 use rocket::request::FlashMessage;
 use rocket::response::{Flash, Redirect};
 use rocket_db_pools::Connection;
 use rocket_dyn_templates::Template;
-use tallystick::{irv::Tally, Transfer};
 
-use crate::{context, schema};
+use crate::csrf::CsrfToken;
+use crate::schema::custom_types::{self, Sex};
+use crate::schema::exec_position::{RON_ID, RON_NAME};
+use crate::{blt, context, counting, forms, one_rep_max, schema, weight_class};
 
-use crate::guards::{Db, ElectionAdmin, Generic, Member, SiteAdmin, User};
+use crate::guards::{Coach, Db, ElectionAdmin, Generic, Member, SiteAdmin, User};
+use crate::scoring::{self, ScoredLifter, SinclairPeriod};
 use crate::session_window::SessionWindow;
 
 fn format_registrations(
@@ -59,6 +61,7 @@ async fn get_registrations(
 pub async fn sessions(
     user: User<Generic>,
     mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
     flash: Option<FlashMessage<'_>>,
 ) -> Template {
     let window = SessionWindow::from_current_time();
@@ -79,6 +82,8 @@ pub async fn sessions(
             message,
             registrations,
             is_site_admin,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
         },
     )
 }
@@ -86,8 +91,9 @@ pub async fn sessions(
 /// Allows site administrators to manage the upcoming sessions.
 #[get("/sessions/manage")]
 pub async fn manage_sessions(
-    _user: User<SiteAdmin>,
+    user: User<SiteAdmin>,
     mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
     flash: Option<FlashMessage<'_>>,
 ) -> Template {
     let window = SessionWindow::from_current_time();
@@ -104,6 +110,8 @@ pub async fn manage_sessions(
             sessions,
             current: None,
             message,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
         },
     )
 }
@@ -111,8 +119,9 @@ pub async fn manage_sessions(
 /// Allows site administrators to manage a specific session.
 #[get("/sessions/manage/<session_id>")]
 pub async fn manage_specific_session(
-    _user: User<SiteAdmin>,
+    user: User<SiteAdmin>,
     mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
     flash: Option<FlashMessage<'_>>,
     session_id: i32,
 ) -> Template {
@@ -135,6 +144,8 @@ pub async fn manage_specific_session(
             sessions,
             current,
             message,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
         },
     )
 }
@@ -144,6 +155,7 @@ pub async fn manage_specific_session(
 pub async fn specific_session(
     user: User<Generic>,
     mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
     session_id: i32,
 ) -> Template {
     let window = SessionWindow::from_current_time();
@@ -168,13 +180,15 @@ pub async fn specific_session(
             message: None,
             registrations,
             is_site_admin,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
         },
     )
 }
 
 /// Gets the information needed for the attendance recording dashboard and renders the template.
 #[get("/attendance")]
-pub async fn attendance(mut conn: Connection<Db>) -> Template {
+pub async fn attendance(mut conn: Connection<Db>, csrf_token: CsrfToken) -> Template {
     let sessions = schema::Session::get_results(&mut *conn).await.unwrap();
 
     Template::render(
@@ -183,6 +197,7 @@ pub async fn attendance(mut conn: Connection<Db>) -> Template {
             sessions,
             current: None,
             message: None,
+            csrf_token,
         },
     )
 }
@@ -191,6 +206,7 @@ pub async fn attendance(mut conn: Connection<Db>) -> Template {
 #[get("/attendance/<session_id>")]
 pub async fn session_attendance(
     mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
     flash: Option<FlashMessage<'_>>,
     session_id: i32,
 ) -> Template {
@@ -208,6 +224,7 @@ pub async fn session_attendance(
             sessions,
             current,
             message,
+            csrf_token,
         },
     )
 }
@@ -226,7 +243,11 @@ pub fn authenticated(uri: &str) -> Template {
 
 /// Displays a small splash page after authenticating.
 #[get("/bookings")]
-pub async fn bookings(user: User<Member>, mut conn: Connection<Db>) -> Template {
+pub async fn bookings(
+    user: User<Member>,
+    mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
+) -> Template {
     let is_site_admin = user.is_also::<SiteAdmin>();
 
     let window = SessionWindow::from_current_time();
@@ -242,20 +263,98 @@ pub async fn bookings(user: User<Member>, mut conn: Connection<Db>) -> Template
             message: None,
             registrations: None,
             is_site_admin,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
         },
     )
 }
 
-/// Displays the PB board for people to view.
-#[get("/")]
+/// Displays the PB board for people to view, sorted and filtered as requested.
+#[get("/?<query..>")]
 pub async fn blackboard(
     user: Option<User<Generic>>,
     mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
     flash: Option<FlashMessage<'_>>,
+    query: Option<forms::BoardQuery>,
 ) -> Template {
-    let (pl, wl) = schema::PersonalBest::get_results(&mut *conn).await.unwrap();
+    let query = query.unwrap_or_default();
+    let lifts = schema::Lift::get_results(&mut *conn).await.unwrap();
+    let (pl, wl) = schema::PersonalBest::get_results(&query, &lifts, &mut *conn)
+        .await
+        .unwrap();
+
+    let period = SinclairPeriod::from_env();
+    let pl_sort = query.pl_sort(&lifts);
+    let wl_sort = query.wl_sort(&lifts);
+
+    // Only fetch the sets of multiple reps if estimated maxes are being shown
+    let mut rep_maxes: HashMap<i32, Vec<_>> = HashMap::new();
+
+    if query.estimated() {
+        let all = schema::LiftRecord::rep_maxes(None, &mut *conn)
+            .await
+            .unwrap();
+
+        for rep_max in all {
+            rep_maxes
+                .entry(rep_max.warwick_id)
+                .or_default()
+                .push(rep_max);
+        }
+    }
+
+    let formula = query.formula();
+    let estimate = |mut pb: schema::PersonalBest| {
+        let estimated = match rep_maxes.get(&pb.warwick_id) {
+            Some(rep_maxes) => one_rep_max::apply_estimates(&mut pb, rep_maxes, formula),
+            None => Vec::new(),
+        };
 
-    let user_id = user.map(|user| user.id);
+        (pb, estimated)
+    };
+
+    let mut pl: Vec<_> = pl
+        .into_iter()
+        .map(estimate)
+        .map(|(pb, estimated)| ScoredLifter::powerlifting(pb, &lifts).with_estimated(estimated))
+        .collect();
+    let mut wl: Vec<_> = wl
+        .into_iter()
+        .map(estimate)
+        .map(|(pb, estimated)| {
+            ScoredLifter::weightlifting(pb, &lifts, period).with_estimated(estimated)
+        })
+        .collect();
+
+    scoring::rank(&mut pl, &pl_sort);
+    scoring::rank(&mut wl, &wl_sort);
+
+    // Anyone who isn't logged in sees weights in kilograms
+    let unit = match &user {
+        Some(user) => {
+            schema::Preferences::find(user.id, &mut *conn)
+                .await
+                .unwrap()
+                .unit
+        }
+        None => custom_types::Unit::default(),
+    };
+
+    let pl = pl.into_iter().map(|lifter| lifter.in_unit(unit)).collect();
+    let wl = wl.into_iter().map(|lifter| lifter.in_unit(unit)).collect();
+
+    let bests = schema::lift::BoardBest::get_results(&query, &mut *conn)
+        .await
+        .unwrap();
+
+    let boards = schema::lift::boards(&lifts, &bests)
+        .into_iter()
+        .map(|board| board.in_unit(unit))
+        .collect();
+
+    let user_id = user.as_ref().map(|user| user.id);
+    let impersonating = user.as_ref().and_then(context::Impersonating::of);
     let message = flash.map(context::Message::from);
 
     Template::render(
@@ -263,32 +362,212 @@ pub async fn blackboard(
         context::Blackboard {
             pl,
             wl,
+            pl_lifts: schema::lift::on_board(&lifts, schema::lift::POWERLIFTING),
+            wl_lifts: schema::lift::on_board(&lifts, schema::lift::WEIGHTLIFTING),
+            boards,
+            pl_sort,
+            wl_sort,
+            sex: query.sex,
+            class: query.class().map(String::from),
+            male_classes: weight_class::classes(Sex::Male),
+            female_classes: weight_class::classes(Sex::Female),
+            estimated: query.estimated(),
+            formula,
+            unit,
             user_id,
             message,
+            impersonating,
+            csrf_token,
         },
     )
 }
 
-/// Allows the user to change their personal bests.
-#[get("/pbs")]
-pub async fn personal_bests(
-    user: User<Member>,
+/// Shows the best lift ever recorded in each category and weight class, along with the people
+/// who held each record before.
+#[get("/records")]
+pub async fn club_records(
+    user: Option<User<Generic>>,
     mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
     flash: Option<FlashMessage<'_>>,
 ) -> Template {
+    let lifts = schema::Lift::get_results(&mut *conn).await.unwrap();
+    let history = schema::ClubRecord::get_results(&mut *conn).await.unwrap();
+
+    // Anyone who isn't logged in sees weights in kilograms
+    let unit = match &user {
+        Some(user) => {
+            schema::Preferences::find(user.id, &mut *conn)
+                .await
+                .unwrap()
+                .unit
+        }
+        None => custom_types::Unit::default(),
+    };
+
+    let lifts = schema::club_record::group(&lifts, history)
+        .into_iter()
+        .map(|records| records.in_unit(unit))
+        .collect();
+
+    Template::render(
+        "club_records",
+        context::ClubRecords {
+            lifts,
+            unit,
+            user_id: user.as_ref().map(|user| user.id),
+            message: flash.map(context::Message::from),
+            impersonating: user.as_ref().and_then(context::Impersonating::of),
+            csrf_token,
+        },
+    )
+}
+
+/// Renders the personal bests page for a user, along with any problems with the values they
+/// submitted.
+pub async fn render_personal_bests(
+    user: &User<Member>,
+    conn: &mut schema::Pool,
+    csrf_token: CsrfToken,
+    message: Option<context::Message>,
+    errors: forms::FieldErrors,
+) -> Template {
+    let schema::Preferences { unit, formula, .. } = schema::Preferences::find(user.id, &mut *conn)
+        .await
+        .unwrap();
+
     let personal_bests = schema::PersonalBest::find(user.id, &user.name, &mut *conn)
         .await
         .unwrap();
 
-    let message = flash.map(context::Message::from);
-    let warning = personal_bests.check_for_show_without_values();
+    let latest_bodyweight = schema::Bodyweight::latest(user.id, &mut *conn)
+        .await
+        .unwrap()
+        .map(|bodyweight| bodyweight.in_unit(unit));
+
+    let rep_maxes = schema::LiftRecord::rep_maxes(Some(user.id), &mut *conn)
+        .await
+        .unwrap();
+
+    let lifts = schema::Lift::get_results(&mut *conn).await.unwrap();
+    let rep_maxes = one_rep_max::table(&lifts, &rep_maxes, formula)
+        .into_iter()
+        .map(|row| row.in_unit(unit))
+        .collect();
+
+    let warning = personal_bests.check_for_show_without_values(&lifts);
 
     Template::render(
         "personal_bests",
         context::PersonalBests {
-            personal_bests,
+            personal_bests: personal_bests.in_unit(unit),
+            lifts,
+            latest_bodyweight,
+            rep_maxes,
+            formula,
+            male_classes: weight_class::classes(Sex::Male),
+            female_classes: weight_class::classes(Sex::Female),
+            unit,
             warning,
+            errors,
+            is_coach: user.is_also::<Coach>(),
+            message,
+            impersonating: context::Impersonating::of(user),
+            csrf_token,
+        },
+    )
+}
+
+/// Allows the user to change their personal bests.
+#[get("/pbs")]
+pub async fn personal_bests(
+    user: User<Member>,
+    mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
+    flash: Option<FlashMessage<'_>>,
+) -> Template {
+    let message = flash.map(context::Message::from);
+
+    render_personal_bests(
+        &user,
+        &mut conn,
+        csrf_token,
+        message,
+        forms::FieldErrors::new(),
+    )
+    .await
+}
+
+/// Shows the history of the user's lifts and how they have progressed.
+#[get("/pbs/history")]
+pub async fn lift_history(
+    user: User<Member>,
+    mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
+    flash: Option<FlashMessage<'_>>,
+) -> Template {
+    let unit = schema::Preferences::find(user.id, &mut *conn)
+        .await
+        .unwrap()
+        .unit;
+
+    let mut records: Vec<_> = schema::LiftRecord::for_user(user.id, &mut *conn)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|record| record.in_unit(unit))
+        .collect();
+
+    let progression = schema::lift_record::progression(&records);
+    records.reverse();
+
+    let message = flash.map(context::Message::from);
+
+    Template::render(
+        "lift_history",
+        context::LiftHistory {
+            records,
+            progression,
+            lifts: schema::Lift::get_results(&mut *conn).await.unwrap(),
+            unit,
+            message,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
+        },
+    )
+}
+
+/// Shows the lifts waiting to be verified by a coach.
+#[get("/pbs/review")]
+pub async fn lift_review(
+    user: User<Coach>,
+    mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
+    flash: Option<FlashMessage<'_>>,
+) -> Template {
+    let unit = schema::Preferences::find(user.id, &mut *conn)
+        .await
+        .unwrap()
+        .unit;
+
+    let pending = schema::LiftRecord::pending(&mut *conn)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|lift| lift.in_unit(unit))
+        .collect();
+
+    let message = flash.map(context::Message::from);
+
+    Template::render(
+        "lift_review",
+        context::LiftReview {
+            pending,
+            user_id: user.id,
+            unit,
             message,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
         },
     )
 }
@@ -298,18 +577,44 @@ pub async fn personal_bests(
 pub async fn elections(
     user: User<Generic>,
     mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
     flash: Option<FlashMessage<'_>>,
 ) -> Template {
-    let exec_positions = schema::ExecPosition::get_results(&mut *conn).await.unwrap();
+    let election = schema::Election::current(&mut *conn).await.unwrap();
+    let (exec_positions, voted) = match &election {
+        Some(election) => (
+            schema::ExecPosition::for_election(election.id, &mut *conn)
+                .await
+                .unwrap(),
+            schema::ExecPosition::voted_identifiers(election.id, &mut *conn)
+                .await
+                .unwrap(),
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    // Ballots are published once voting for their position has closed
+    let now = chrono::Utc::now().timestamp();
+    let published = exec_positions
+        .iter()
+        .filter(|position| position.has_closed(now) && voted.contains(&position.id))
+        .map(|position| position.id)
+        .collect();
+    let archived = schema::Election::archived(&mut *conn).await.unwrap();
 
     let message = flash.map(context::Message::from);
 
     Template::render(
         "elections",
         context::Elections {
+            election,
             exec_positions,
+            published,
+            archived,
             message,
             admin: user.is_also::<ElectionAdmin>(),
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
         },
     )
 }
@@ -319,6 +624,8 @@ pub async fn elections(
 pub async fn election_voting(
     user: User<Member>,
     mut conn: Connection<Db>,
+    cookies: &CookieJar<'_>,
+    csrf_token: CsrfToken,
     flash: Option<FlashMessage<'_>>,
     position_id: i32,
 ) -> Result<Template, Flash<Redirect>> {
@@ -333,8 +640,9 @@ pub async fn election_voting(
         ));
     }
 
-    let position_title = schema::ExecPosition::get_title(position_id, &mut *conn)
+    let position = schema::ExecPosition::find(position_id, &mut *conn)
         .await
+        .unwrap()
         .unwrap();
 
     let mut nominations = schema::Nomination::for_position_with_names(position_id, &mut *conn)
@@ -343,7 +651,30 @@ pub async fn election_voting(
 
     let message = flash.map(context::Message::from);
 
-    let current_ballot = schema::Vote::get_current_ballot(user.id, position_id, &mut *conn)
+    // Ballots can only be found with the secret kept in the session they were cast from
+    let secret = cookies.get_private(&schema::vote::secret_cookie(user.id, position_id));
+
+    let (current_ballot, receipt) = match secret {
+        Some(secret) => {
+            let current_ballot =
+                schema::Vote::get_current_ballot(secret.value(), position_id, &mut *conn)
+                    .await
+                    .unwrap();
+
+            let receipt = schema::Vote::get_receipt(secret.value(), position_id, &mut *conn)
+                .await
+                .unwrap();
+
+            (current_ballot, receipt)
+        }
+        None => (None, None),
+    };
+
+    let (receipt_code, receipt_commitment) = receipt
+        .map(|receipt| (receipt.code, receipt.commitment))
+        .unzip();
+
+    let has_voted = schema::Vote::has_voted(user.id, position_id, &mut *conn)
         .await
         .unwrap();
 
@@ -351,63 +682,58 @@ pub async fn election_voting(
     let mut rng = rand::thread_rng();
     nominations.shuffle(&mut rng);
 
+    // Publish the hash of the seed for drawing lots before voting, so the seed can't be changed
+    let lot_seed_hash = position.lot_seed_hash();
+
     Ok(Template::render(
         "election_voting",
         context::Voting {
             position_id,
-            position_title,
+            position_title: position.title,
             nominations,
+            ranked: position.counting_method.is_ranked(),
+            single_choice: position.counting_method == custom_types::CountingMethod::Fptp,
+            lot_seed_hash,
             current_ballot,
+            receipt_code,
+            receipt_commitment,
+            has_voted,
             message,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
         },
     ))
 }
 
-/// Resolves the winners of a tie based on a presidential vote ballot.
-fn resolve_ties<'a, 'b>(
-    winners: Vec<(i32, &'a str, usize)>,
-    num_winners: usize,
-    ballot: &'b [i32],
-) -> Vec<(i32, &'a str, usize)> {
-    // The given ballot is ordered, so we only need to use it for tie breaks
-    let mut resolved = Vec::new();
-
-    for (_, group) in &winners.into_iter().group_by(|k| k.2) {
-        if num_winners == resolved.len() {
-            break;
-        }
-
-        let mut group: Vec<_> = group.collect();
-        let remaining = num_winners - resolved.len();
-
-        // If we can include everyone here, do so
-        if group.len() <= remaining {
-            resolved.extend(group.into_iter());
-            continue;
-        }
-
-        // Pick from here in order of preference
-        for _ in 0..remaining {
-            for id in ballot {
-                if let Some(pos) = group.iter().position(|x| x.0 == *id) {
-                    resolved.push(group.remove(pos));
-                }
-
-                if num_winners == resolved.len() {
-                    return resolved;
-                }
-            }
-        }
+/// Gets the name of a candidate, including the built-in candidate for re-opening nominations.
+fn candidate_name(id: i32, nominees: &HashMap<i32, String>) -> &str {
+    match id {
+        RON_ID => RON_NAME,
+        id => nominees[&id].as_str(),
     }
+}
 
-    resolved
+/// Groups the votes for a position into their ballots, in order of preference.
+fn ballots_by_id(votes: &mut [schema::Vote]) -> HashMap<String, Vec<i32>> {
+    // Sort the votes by `ballot_id` and then `ranking`
+    votes.sort_by(|a, b| {
+        a.ballot_id
+            .cmp(&b.ballot_id)
+            .then(a.ranking.cmp(&b.ranking))
+    });
+
+    votes
+        .iter()
+        .map(|v| (v.ballot_id.clone(), v.candidate_id))
+        .into_group_map()
 }
 
-/// Counts all the ballots for a given position.
+/// Counts all the ballots for a given position, out of the candidates `standing` for it.
 fn count_position_ballots<'a>(
     position_id: i32,
     votes: &mut Vec<schema::Vote>,
     positions: &'a BTreeMap<i32, schema::ExecPosition>,
+    standing: &[i32],
     nominees: &'a HashMap<i32, String>,
 ) -> context::ElectionResult<'a> {
     if votes.is_empty() {
@@ -416,88 +742,186 @@ fn count_position_ballots<'a>(
             title: positions[&position_id].title.clone(),
             winners: Vec::new(),
             voter_count: 0,
+            reopened: false,
+            closed: true,
+            count_sheet: None,
         };
     }
 
-    // Sort the votes by `warwick_id` and then `ranking`
-    votes.sort_by(|a, b| {
-        a.warwick_id
-            .cmp(&b.warwick_id)
-            .then(a.ranking.cmp(&b.ranking))
-    });
-
-    let map = votes
-        .iter_mut()
-        .map(|v| (v.warwick_id, v.candidate_id))
-        .into_group_map();
+    let map = ballots_by_id(votes);
 
     let voter_count = map.len();
     let collected: Vec<_> = map.values().map(Vec::clone).collect();
 
-    let num_winners = positions[&position_id].num_winners as usize;
-    let mut tally: Tally<i32, usize> = Tally::new(Transfer::Meek);
+    let position = &positions[&position_id];
+    let num_winners = position.num_winners as usize;
 
-    for vote in collected {
-        tally.add_ref(&vote);
-    }
+    let seed = position.lot_seed.as_str();
 
-    // Get the (candidate, rank) pairs
-    let ranked = tally.tally_ranked();
+    let count = counting::count(position.counting_method, standing, &collected, num_winners);
+    let name_of = |id| candidate_name(id, nominees);
 
-    // Iterate once to find the rank of the last winner
-    let last_winner_rank = ranked.get(num_winners - 1).map_or(usize::MAX, |r| r.rank);
+    // Fill the seats, breaking any ties with the method chosen for the position
+    let elected = counting::break_ties(
+        &count,
+        num_winners,
+        position.tie_break,
+        &position.casting_vote,
+        seed,
+    );
 
-    // Find all people with this rank or less
-    let mut winners: Vec<_> = ranked
+    let winners: Vec<_> = elected
+        .winners
         .iter()
-        .filter_map(|rc| {
-            (rc.rank <= last_winner_rank)
-                .then(|| (rc.candidate, nominees[&rc.candidate].as_str(), rc.rank))
-        })
+        .map(|r| (r.candidate, name_of(r.candidate), r.rank))
         .collect();
 
-    // Resolve any ties
-    if winners.len() > num_winners {
-        // Get the identifier of the president and resolve the ties
-        let id = i32::from_str(&env::var("PRESIDENT_ID").unwrap()).unwrap();
-        winners = resolve_ties(winners, num_winners, &map[&id]);
-    }
+    let mut sheet = count_sheet(position.counting_method, &count, name_of);
+    sheet.tie_break = Some(position.tie_break.to_string());
+    sheet.ties = elected
+        .ties
+        .iter()
+        .map(|seat| describe_tie(seat, seed, name_of))
+        .collect();
 
     let title = positions[&position_id].title.clone();
+    let reopened = winners.iter().any(|winner| winner.0 == RON_ID);
 
-    tracing::info!(%title, ?winners, "Votes have been tallied for a position");
+    tracing::info!(%title, ?winners, %reopened, "Votes have been tallied for a position");
 
     context::ElectionResult {
         position_id,
         title,
         winners,
         voter_count,
+        reopened,
+        closed: true,
+        count_sheet: Some(sheet),
     }
 }
 
-/// Calculates the results of the elections won so far.
-#[get("/elections/results")]
-pub async fn election_results(_user: User<ElectionAdmin>, mut conn: Connection<Db>) -> Template {
-    // Get all the available positions
-    let positions: BTreeMap<i32, schema::ExecPosition> =
-        schema::ExecPosition::get_results(&mut *conn)
-            .await
-            .unwrap()
-            .into_iter()
-            .map(|pos| (pos.id, pos))
-            .collect();
+/// Explains how a tied seat was filled, so that the tie-break can be checked.
+fn describe_tie<'a>(
+    seat: &counting::TiedSeat,
+    seed: &str,
+    name_of: impl Fn(i32) -> &'a str,
+) -> String {
+    let tied = seat.tied.iter().map(|c| name_of(*c)).join(", ");
+    let chosen = name_of(seat.chosen);
+
+    let reason = match (seat.method, seat.stage) {
+        (custom_types::TieBreak::Backwards, Some(stage)) => {
+            format!("had the most votes at stage {}", stage)
+        }
+        (custom_types::TieBreak::Casting, _) => {
+            String::from("was ranked highest on the casting vote")
+        }
+        _ => format!("was drawn by lot with the seed {}", seed),
+    };
 
-    // Map all the nominees from `warwick_id` -> `name`
-    let nominees: HashMap<_, _> = schema::Candidate::get_results(&mut *conn)
-        .await
-        .unwrap()
+    format!("{} were tied, and {} {}", tied, chosen, reason)
+}
+
+/// Formats a number of votes, only showing as many decimal places as needed.
+fn format_votes(votes: f64) -> String {
+    let formatted = format!("{:.5}", votes);
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+
+    match formatted {
+        "-0" => String::from("0"),
+        _ => String::from(formatted),
+    }
+}
+
+/// Lays out each stage of a count for display, with a row for each candidate.
+pub fn count_sheet<'a>(
+    method: custom_types::CountingMethod,
+    count: &counting::Count,
+    name_of: impl Fn(i32) -> &'a str,
+) -> context::CountSheet<'a> {
+    let mut candidates: Vec<_> = count
+        .ranking
+        .iter()
+        .map(|ranked| ranked.candidate)
+        .collect();
+    candidates.sort_unstable();
+
+    let rows = candidates
         .into_iter()
-        .map(|n| (n.warwick_id, n.name))
+        .map(|candidate| context::CountRow {
+            name: name_of(candidate),
+            stages: count
+                .rounds
+                .iter()
+                .map(|round| {
+                    let total = round.totals.iter().find(|t| t.candidate == candidate)?;
+                    let sign = if total.change > 0.0 { "+" } else { "" };
+                    let change = (total.change.abs() > 1e-9)
+                        .then(|| format!("{}{}", sign, format_votes(total.change)));
+
+                    Some(context::CountCell {
+                        votes: format_votes(total.votes),
+                        change,
+                    })
+                })
+                .collect(),
+        })
+        .collect();
+
+    let names = |candidates: &[i32]| candidates.iter().map(|c| name_of(*c)).join(", ");
+
+    let actions = count
+        .rounds
+        .iter()
+        .map(|round| {
+            let mut actions = Vec::new();
+
+            if !round.elected.is_empty() {
+                actions.push(format!("Elected {}", names(&round.elected)));
+            }
+
+            if let Some(candidate) = round.surplus {
+                actions.push(format!("Transferred the surplus of {}", name_of(candidate)));
+            }
+
+            if !round.excluded.is_empty() {
+                actions.push(format!("Excluded {}", names(&round.excluded)));
+            }
+
+            actions.join(". ")
+        })
         .collect();
 
-    // Pull all the votes so far
-    let votes = schema::Vote::get_results(&mut *conn).await.unwrap();
+    context::CountSheet {
+        method: method.to_string(),
+        rows,
+        exhausted: count
+            .rounds
+            .iter()
+            .map(|round| format_votes(round.exhausted))
+            .collect(),
+        quota: count
+            .rounds
+            .iter()
+            .map(|round| round.quota.map(format_votes))
+            .collect(),
+        actions,
+        tie_break: None,
+        ties: Vec::new(),
+    }
+}
 
+/// Counts the ballots for every position in an election.
+///
+/// Positions where voting has not finished yet only have their voters counted, so that nobody
+/// can see who is winning before voting closes.
+fn count_election_ballots<'a>(
+    votes: Vec<schema::Vote>,
+    positions: &'a BTreeMap<i32, schema::ExecPosition>,
+    standing: &HashMap<i32, Vec<i32>>,
+    nominees: &'a HashMap<i32, String>,
+    now: i64,
+) -> Vec<context::ElectionResult<'a>> {
     // Sort all the votes by position they are voting for
     let mut by_position: BTreeMap<i32, Vec<schema::Vote>> =
         positions.keys().map(|k| (*k, Vec::new())).collect();
@@ -506,56 +930,639 @@ pub async fn election_results(_user: User<ElectionAdmin>, mut conn: Connection<D
         by_position.get_mut(&vote.position_id).unwrap().push(vote);
     }
 
-    let results: Vec<_> = by_position
+    by_position
         .iter_mut()
-        .map(|(id, votes)| count_position_ballots(*id, votes, &positions, &nominees))
+        .map(|(id, votes)| {
+            let position = &positions[id];
+
+            if position.has_closed(now) {
+                return count_position_ballots(*id, votes, positions, &standing[id], nominees);
+            }
+
+            context::ElectionResult {
+                position_id: *id,
+                title: position.title.clone(),
+                winners: Vec::new(),
+                voter_count: votes.iter().map(|v| &v.ballot_id).unique().count(),
+                reopened: false,
+                closed: false,
+                count_sheet: None,
+            }
+        })
+        .collect()
+}
+
+/// Gets the positions, candidates and votes of an election, ready for counting.
+///
+/// Alongside the names of the nominees, this gives everyone standing for each position, including
+/// re-opening nominations where it is offered, so that those nobody voted for are still counted.
+async fn election_ballots(
+    election_id: i32,
+    conn: &mut schema::Pool,
+) -> (
+    BTreeMap<i32, schema::ExecPosition>,
+    HashMap<i32, Vec<i32>>,
+    HashMap<i32, String>,
+    Vec<schema::Vote>,
+) {
+    let positions: BTreeMap<_, _> = schema::ExecPosition::for_election(election_id, &mut *conn)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|pos| (pos.id, pos))
+        .collect();
+
+    let mut standing: HashMap<i32, Vec<i32>> = positions
+        .values()
+        .map(|pos| (pos.id, pos.ron.then_some(RON_ID).into_iter().collect()))
         .collect();
 
-    let closed_positions = schema::ExecPosition::closed_identifiers(&mut *conn)
+    let nominations = schema::Nomination::for_election_with_names(election_id, &mut *conn)
         .await
         .unwrap();
 
-    tracing::debug!(
-        ?closed_positions,
-        "Only some positions have closed, so ignoring results for all others"
-    );
+    for nomination in nominations {
+        if let Some(candidates) = standing.get_mut(&nomination.position_id) {
+            candidates.push(nomination.warwick_id);
+        }
+    }
+
+    // Map all the nominees from `warwick_id` -> `name`
+    let nominees = schema::Candidate::for_election(election_id, &mut *conn)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|n| (n.warwick_id, n.name))
+        .collect();
 
-    // All ties should have been resolved by the presidential vote, so elect users
+    let votes = schema::Vote::for_election(election_id, &mut *conn)
+        .await
+        .unwrap();
+
+    (positions, standing, nominees, votes)
+}
+
+/// Calculates the results of the current election so far.
+#[get("/elections/results")]
+pub async fn election_results(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+) -> Result<Template, Flash<Redirect>> {
+    let election = match schema::Election::current(&mut *conn).await.unwrap() {
+        Some(election) => election,
+        None => {
+            return Err(Flash::error(
+                Redirect::to(uri!(election_setup)),
+                "There is no election being run at the moment.",
+            ))
+        }
+    };
+
+    let (positions, standing, nominees, votes) = election_ballots(election.id, &mut *conn).await;
+    let now = chrono::Utc::now().timestamp();
+    let results = count_election_ballots(votes, &positions, &standing, &nominees, now);
+
+    // All ties have been broken with the method chosen for each position, so elect users
     let all_winners: Vec<_> = results
         .iter()
-        .filter_map(|r| {
-            closed_positions
-                .contains(&r.position_id)
-                .then(|| &r.winners)
-        })
+        .filter_map(|r| r.closed.then(|| &r.winners))
         .flatten()
         .map(|w| w.0)
+        .filter(|id| *id != RON_ID)
         .collect();
 
-    schema::Candidate::mark_elected(&all_winners, &mut *conn)
+    schema::Candidate::mark_elected(election.id, &all_winners, &mut *conn)
         .await
         .unwrap();
 
-    Template::render("election_results", context::ElectionResults { results })
+    Ok(Template::render(
+        "election_results",
+        context::ElectionResults {
+            election,
+            results,
+            admin: true,
+        },
+    ))
+}
+
+/// Shows the final results of an election that has finished.
+#[get("/elections/archive/<election_id>")]
+pub async fn election_archive(
+    user: User<Member>,
+    mut conn: Connection<Db>,
+    election_id: i32,
+) -> Result<Template, Flash<Redirect>> {
+    let election = match schema::Election::find(election_id, &mut *conn)
+        .await
+        .unwrap()
+    {
+        Some(election) if election.is_archived() => election,
+        _ => {
+            return Err(Flash::error(
+                Redirect::to(uri!(elections)),
+                "The results of an election can only be viewed once it has finished.",
+            ))
+        }
+    };
+
+    // Archived elections have no schedules left, so every position has closed
+    let (positions, standing, nominees, votes) = election_ballots(election.id, &mut *conn).await;
+    let now = chrono::Utc::now().timestamp();
+    let results = count_election_ballots(votes, &positions, &standing, &nominees, now);
+
+    Ok(Template::render(
+        "election_results",
+        context::ElectionResults {
+            election,
+            results,
+            admin: user.is_also::<ElectionAdmin>(),
+        },
+    ))
+}
+
+/// A file for the user to download, saved under the given file name.
+#[derive(Responder)]
+pub struct Download {
+    content: (ContentType, String),
+    disposition: Header<'static>,
+}
+
+impl Download {
+    /// Creates a download of the given content, suggesting a file name to save it as.
+    ///
+    /// File names come from election and position titles, so they are never put in the header
+    /// as they are. Browsers use the percent-encoded `filename*` from RFC 5987, and older clients
+    /// fall back to a copy with anything outside of a few safe ASCII characters replaced.
+    fn new(content_type: ContentType, content: String, file_name: &str) -> Self {
+        let disposition = content_disposition(file_name);
+
+        Self {
+            content: (content_type, content),
+            disposition: Header::new("Content-Disposition", disposition),
+        }
+    }
+}
+
+/// Builds the value of a `Content-Disposition` header for downloading a file under the given
+/// name.
+fn content_disposition(file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | ' ' | '-' | '_' | '.' | '(' | ')' => c,
+            _ => '_',
+        })
+        .collect();
+
+    // Everything other than the `attr-char`s of RFC 5987 is percent-encoded as UTF-8
+    let encoded: String = file_name
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => char::from(byte).to_string(),
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    )
+}
+
+/// Writes out a count sheet as CSV, with a row for each candidate and two columns for each stage
+/// holding their votes and the votes transferred.
+fn count_sheet_csv(sheet: &context::CountSheet<'_>) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let stages = sheet.actions.len();
+
+    let mut header = vec![String::from("Candidate")];
+
+    for stage in 1..=stages {
+        header.push(format!("Stage {}", stage));
+        header.push(format!("Stage {} Transfers", stage));
+    }
+
+    writer.write_record(&header).unwrap();
+
+    for row in &sheet.rows {
+        let mut record = vec![row.name];
+
+        for cell in &row.stages {
+            match cell {
+                Some(cell) => {
+                    record.push(cell.votes.as_str());
+                    record.push(cell.change.as_deref().unwrap_or_default());
+                }
+                None => record.extend(["", ""]),
+            }
+        }
+
+        writer.write_record(&record).unwrap();
+    }
+
+    let quota: Vec<_> = sheet
+        .quota
+        .iter()
+        .map(|quota| quota.clone().unwrap_or_default())
+        .collect();
+
+    for (name, values) in [
+        ("Exhausted", &sheet.exhausted),
+        ("Quota", &quota),
+        ("Actions", &sheet.actions),
+    ] {
+        let mut record = vec![name];
+
+        for value in values {
+            record.extend([value.as_str(), ""]);
+        }
+
+        writer.write_record(&record).unwrap();
+    }
+
+    // Every record needs as many fields as the header
+    for tie in &sheet.ties {
+        let mut record = vec!["Tie Break", tie.as_str()];
+        record.resize(header.len(), "");
+
+        writer.write_record(&record).unwrap();
+    }
+
+    String::from_utf8(writer.into_inner().unwrap()).unwrap()
+}
+
+/// Downloads the count sheet for a position as CSV.
+///
+/// Members can download the count sheets of finished elections, and election administrators can
+/// also download them for the current election once voting for the position has closed.
+#[get("/elections/<election_id>/count/<position_id>")]
+pub async fn election_count_sheet(
+    user: User<Member>,
+    mut conn: Connection<Db>,
+    election_id: i32,
+    position_id: i32,
+) -> Result<Download, Flash<Redirect>> {
+    let error = |message: &'static str| Err(Flash::error(Redirect::to(uri!(elections)), message));
+
+    let election = match schema::Election::find(election_id, &mut *conn)
+        .await
+        .unwrap()
+    {
+        Some(election) if election.is_archived() || user.is_also::<ElectionAdmin>() => election,
+        _ => return error("The results of an election can only be viewed once it has finished."),
+    };
+
+    let (positions, standing, nominees, votes) = election_ballots(election.id, &mut *conn).await;
+    let now = chrono::Utc::now().timestamp();
+    let results = count_election_ballots(votes, &positions, &standing, &nominees, now);
+
+    let result = results
+        .into_iter()
+        .find(|result| result.position_id == position_id);
+
+    match result.and_then(|result| result.count_sheet.map(|sheet| (result.title, sheet))) {
+        Some((title, sheet)) => {
+            let file_name = format!("{} {}.csv", election.name, title);
+            Ok(Download::new(
+                ContentType::CSV,
+                count_sheet_csv(&sheet),
+                &file_name,
+            ))
+        }
+        None => error("That position has not been counted yet."),
+    }
+}
+
+/// Downloads the ballots for a position in the BLT format, so that the count can be checked with
+/// other counting software.
+///
+/// The ballots are shuffled and nothing about the voters is included, so that nobody can be
+/// linked to their ballot.
+#[get("/elections/<election_id>/ballots/<position_id>")]
+pub async fn election_ballot_export(
+    _user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    election_id: i32,
+    position_id: i32,
+) -> Result<Download, Flash<Redirect>> {
+    let error = |message: &'static str| Err(Flash::error(Redirect::to(uri!(elections)), message));
+
+    let election = match schema::Election::find(election_id, &mut *conn)
+        .await
+        .unwrap()
+    {
+        Some(election) => election,
+        None => return error("That election could not be found."),
+    };
+
+    let (positions, standing, nominees, mut votes) =
+        election_ballots(election.id, &mut *conn).await;
+    let now = chrono::Utc::now().timestamp();
+
+    let position = match positions.get(&position_id) {
+        Some(position) if position.has_closed(now) => position,
+        _ => {
+            return error("Ballots can only be downloaded once voting for the position has closed.")
+        }
+    };
+
+    votes.retain(|vote| vote.position_id == position_id);
+
+    let mut ballots: Vec<_> = ballots_by_id(&mut votes).into_values().collect();
+    ballots.shuffle(&mut rand::thread_rng());
+
+    // Number everyone standing for the position, along with anyone else on the ballots
+    let candidates: Vec<_> = standing[&position_id]
+        .iter()
+        .copied()
+        .chain(ballots.iter().flatten().copied())
+        .sorted()
+        .dedup()
+        .collect();
+
+    let number = |id: &i32| candidates.iter().position(|c| c == id).unwrap() as i32 + 1;
+
+    let blt = blt::Blt {
+        title: format!("{} {}", election.name, position.title),
+        seats: position.num_winners as usize,
+        candidates: candidates
+            .iter()
+            .map(|id| String::from(candidate_name(*id, &nominees)))
+            .collect(),
+        ballots: ballots
+            .iter()
+            .map(|ballot| ballot.iter().map(number).collect())
+            .collect(),
+    };
+
+    let file_name = format!("{}.blt", blt.title);
+
+    Ok(Download::new(ContentType::Plain, blt.write(), &file_name))
+}
+
+/// Shows the ballots for a position by their receipt codes once voting has closed, so that voters
+/// can check the ballot with the code they were given holds the preferences they cast.
+#[get("/elections/receipts/<position_id>?<code>")]
+pub async fn election_receipts(
+    user: User<Member>,
+    mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
+    position_id: i32,
+    code: Option<&str>,
+) -> Result<Template, Flash<Redirect>> {
+    let now = chrono::Utc::now().timestamp();
+
+    let position = match schema::ExecPosition::find(position_id, &mut *conn)
+        .await
+        .unwrap()
+    {
+        Some(position) if position.has_closed(now) => position,
+        _ => {
+            return Err(Flash::error(
+                Redirect::to(uri!(elections)),
+                "Ballots are only published once voting for the position has closed.",
+            ))
+        }
+    };
+
+    let ballots = schema::Vote::published(position_id, &mut *conn)
+        .await
+        .unwrap();
+
+    let code = code
+        .map(|code| code.trim().to_lowercase())
+        .filter(|code| !code.is_empty());
+    let found = code
+        .as_ref()
+        .map(|code| ballots.iter().any(|ballot| &ballot.receipt == code));
+
+    Ok(Template::render(
+        "election_receipts",
+        context::ElectionReceipts {
+            position_id,
+            position_title: position.title,
+            ballots,
+            code,
+            found,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
+        },
+    ))
 }
 
 /// Shows the elections settings page.
 #[get("/elections/settings")]
 pub async fn election_settings(
-    _user: User<ElectionAdmin>,
+    user: User<ElectionAdmin>,
     mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
     flash: Option<FlashMessage<'_>>,
 ) -> Template {
-    let exec_positions = schema::ExecPosition::get_results(&mut *conn).await.unwrap();
+    let election = schema::Election::current(&mut *conn).await.unwrap();
+    let exec_positions = match &election {
+        Some(election) => schema::ExecPosition::for_election(election.id, &mut *conn)
+            .await
+            .unwrap(),
+        None => Vec::new(),
+    };
 
     let message = flash.map(context::Message::from);
 
     Template::render(
         "election_settings",
         context::Elections {
+            election,
             exec_positions,
+            archived: Vec::new(),
             message,
             admin: true,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
+        },
+    )
+}
+
+/// Allows election administrators to set up the positions, candidates and nominations.
+#[get("/elections/setup")]
+pub async fn election_setup(
+    user: User<ElectionAdmin>,
+    mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
+    flash: Option<FlashMessage<'_>>,
+) -> Template {
+    let election = schema::Election::current(&mut *conn).await.unwrap();
+
+    let (exec_positions, candidates, nominations, voted_positions) = match &election {
+        Some(election) => (
+            schema::ExecPosition::for_election(election.id, &mut *conn)
+                .await
+                .unwrap(),
+            schema::Candidate::for_election(election.id, &mut *conn)
+                .await
+                .unwrap(),
+            schema::Nomination::for_election_with_names(election.id, &mut *conn)
+                .await
+                .unwrap(),
+            schema::ExecPosition::voted_identifiers(election.id, &mut *conn)
+                .await
+                .unwrap(),
+        ),
+        None => Default::default(),
+    };
+
+    let mut casting_options = Vec::new();
+
+    for position in &exec_positions {
+        casting_options.push(context::CastingOptions {
+            position_id: position.id,
+            candidates: schema::Nomination::for_position_with_names(position.id, &mut *conn)
+                .await
+                .unwrap(),
+        });
+    }
+
+    let message = flash.map(context::Message::from);
+
+    Template::render(
+        "election_setup",
+        context::ElectionSetup {
+            election,
+            exec_positions,
+            candidates,
+            nominations,
+            voted_positions,
+            casting_options,
+            message,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
+        },
+    )
+}
+
+/// Allows site administrators to manage the roles users hold.
+#[get("/roles")]
+pub async fn roles(
+    user: User<SiteAdmin>,
+    mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
+    flash: Option<FlashMessage<'_>>,
+) -> Template {
+    let roles = schema::Role::get_results(&mut *conn).await.unwrap();
+    let user_roles = schema::UserRole::get_results(&mut *conn).await.unwrap();
+    let audit_log = schema::RoleAuditEntry::get_results(&mut *conn)
+        .await
+        .unwrap();
+
+    let message = flash.map(context::Message::from);
+
+    Template::render(
+        "roles",
+        context::Roles {
+            roles,
+            user_roles,
+            audit_log,
+            message,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
+        },
+    )
+}
+
+/// Allows site administrators to manage the lifts that can be recorded.
+#[get("/lifts")]
+pub async fn lifts(
+    user: User<SiteAdmin>,
+    mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
+    flash: Option<FlashMessage<'_>>,
+) -> Template {
+    let lifts = schema::Lift::get_results(&mut *conn).await.unwrap();
+    let message = flash.map(context::Message::from);
+
+    Template::render(
+        "lifts",
+        context::Lifts {
+            lifts,
+            message,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
+        },
+    )
+}
+
+/// Allows site administrators to view the membership register.
+#[get("/memberships")]
+pub async fn memberships(
+    user: User<SiteAdmin>,
+    mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
+    flash: Option<FlashMessage<'_>>,
+) -> Template {
+    let memberships = schema::Membership::get_results(&mut *conn).await.unwrap();
+    let message = flash.map(context::Message::from);
+
+    Template::render(
+        "memberships",
+        context::Memberships {
+            memberships,
+            import: None,
+            message,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
+        },
+    )
+}
+
+/// Allows site administrators to view the website as another user.
+#[get("/impersonate")]
+pub async fn impersonation(
+    _user: User<SiteAdmin>,
+    mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
+    flash: Option<FlashMessage<'_>>,
+) -> Template {
+    let entries = schema::ImpersonationEntry::get_results(&mut *conn)
+        .await
+        .unwrap();
+
+    let message = flash.map(context::Message::from);
+
+    Template::render(
+        "impersonation",
+        context::Impersonation {
+            entries,
+            message,
+            csrf_token,
+        },
+    )
+}
+
+/// Allows users to manage their API tokens.
+#[get("/tokens")]
+pub async fn api_tokens(
+    user: User<Generic>,
+    mut conn: Connection<Db>,
+    csrf_token: CsrfToken,
+    flash: Option<FlashMessage<'_>>,
+) -> Template {
+    let tokens = schema::ApiToken::for_user(user.id, &mut *conn)
+        .await
+        .unwrap();
+
+    let message = flash.map(context::Message::from);
+
+    Template::render(
+        "api_tokens",
+        context::ApiTokens {
+            tokens,
+            roles: user.roles().to_vec(),
+            created: None,
+            message,
+            impersonating: context::Impersonating::of(&user),
+            csrf_token,
         },
     )
 }
@@ -564,48 +1571,44 @@ pub async fn election_settings(
 mod tests {
     use std::collections::{BTreeMap, HashMap};
 
-    use crate::context::ElectionResult;
-    use crate::frontend::{count_position_ballots, resolve_ties};
+    use crate::context::{CountCell, CountRow, CountSheet, ElectionResult};
+    use crate::frontend::{
+        content_disposition, count_position_ballots, count_sheet_csv, format_votes,
+    };
+    use crate::schema::custom_types::{CountingMethod, TieBreak};
+    use crate::schema::exec_position::{RON_ID, RON_NAME};
     use crate::schema::{ExecPosition, Vote};
 
-    #[test]
-    fn ties_are_resolved_correctly() {
-        let tie = vec![(1, "Candidate A", 2), (2, "Candidate B", 2)];
-        let num_winners = 1;
-        let ballot = [2, 1];
-
-        let resolved = resolve_ties(tie, num_winners, &ballot);
-        let expected = vec![(2, "Candidate B", 2)];
-
-        assert_eq!(resolved, expected);
+    fn cell(votes: &str, change: Option<&str>) -> CountCell {
+        CountCell {
+            votes: String::from(votes),
+            change: change.map(String::from),
+        }
     }
 
     #[test]
-    fn ties_are_resolved_correctly_with_multiple_winners() {
-        let tie = vec![
-            (1, "Candidate A", 2),
-            (2, "Candidate B", 2),
-            (3, "Candidate C", 3),
-        ];
-        let num_winners = 2;
-        let ballot = [2, 1, 3];
-
-        let resolved = resolve_ties(tie, num_winners, &ballot);
-        let expected = vec![(1, "Candidate A", 2), (2, "Candidate B", 2)];
-
-        assert_eq!(resolved, expected);
+    fn votes_are_formatted_with_as_few_decimal_places_as_needed() {
+        assert_eq!(format_votes(12.0), "12");
+        assert_eq!(format_votes(4.5), "4.5");
+        assert_eq!(format_votes(14.0 / 3.0), "4.66667");
+        assert_eq!(format_votes(-6.0), "-6");
+        assert_eq!(format_votes(-0.000_000_1), "0");
     }
 
     #[test]
-    fn low_ranked_candidates_cannot_win_in_high_ranked_ties() {
-        let tie = vec![(1, "Candidate A", 2), (2, "Candidate B", 2)];
-        let num_winners = 1;
-        let ballot = [3, 2, 1];
-
-        let resolved = resolve_ties(tie, num_winners, &ballot);
-        let expected = vec![(2, "Candidate B", 2)];
-
-        assert_eq!(resolved, expected);
+    fn download_names_cannot_break_out_of_the_header() {
+        assert_eq!(
+            content_disposition("AGM 2023 President.csv"),
+            "attachment; filename=\"AGM 2023 President.csv\"; filename*=UTF-8''AGM%202023%20President.csv"
+        );
+        assert_eq!(
+            content_disposition("Vice-\"President\"\r\nSet-Cookie: a=b;.blt"),
+            "attachment; filename=\"Vice-_President___Set-Cookie_ a_b_.blt\"; filename*=UTF-8''Vice-%22President%22%0D%0ASet-Cookie%3A%20a%3Db%3B.blt"
+        );
+        assert_eq!(
+            content_disposition("Trésorier.csv"),
+            "attachment; filename=\"Tr_sorier.csv\"; filename*=UTF-8''Tr%C3%A9sorier.csv"
+        );
     }
 
     #[test]
@@ -624,6 +1627,14 @@ mod tests {
                 title: String::from("pos"),
                 num_winners: 1,
                 open: true,
+                election_id: 1,
+                ron: false,
+                opens_at: None,
+                closes_at: None,
+                counting_method: CountingMethod::Irv,
+                tie_break: TieBreak::Backwards,
+                casting_vote: vec![],
+                lot_seed: String::from("seed"),
             },
         );
 
@@ -632,12 +1643,35 @@ mod tests {
         nominees.insert(2, String::from("Candidate 2"));
         nominees.insert(3, String::from("Candidate 3"));
 
-        let result = count_position_ballots(position_id, &mut votes, &positions, &nominees);
+        let result = count_position_ballots(position_id, &mut votes, &positions, &[], &nominees);
         let expected = ElectionResult {
             position_id: 1,
             title: String::from("pos"),
             winners: vec![(2, "Candidate 2", 0)],
             voter_count: 1,
+            reopened: false,
+            closed: true,
+            count_sheet: Some(CountSheet {
+                method: String::from("Instant-Runoff Voting"),
+                rows: vec![
+                    CountRow {
+                        name: "Candidate 2",
+                        stages: vec![Some(cell("1", None)), Some(cell("1", None))],
+                    },
+                    CountRow {
+                        name: "Candidate 3",
+                        stages: vec![Some(cell("0", None)), None],
+                    },
+                ],
+                exhausted: vec![String::from("0"), String::from("0")],
+                quota: vec![None, None],
+                actions: vec![
+                    String::from("Excluded Candidate 3"),
+                    String::from("Elected Candidate 2"),
+                ],
+                tie_break: Some(String::from("Backwards Tie-Break")),
+                ties: vec![],
+            }),
         };
 
         assert_eq!(result, expected);
@@ -659,6 +1693,14 @@ mod tests {
                 title: String::from("pos"),
                 num_winners: 1,
                 open: true,
+                election_id: 1,
+                ron: false,
+                opens_at: None,
+                closes_at: None,
+                counting_method: CountingMethod::Irv,
+                tie_break: TieBreak::Backwards,
+                casting_vote: vec![],
+                lot_seed: String::from("seed"),
             },
         );
 
@@ -667,14 +1709,221 @@ mod tests {
         nominees.insert(2, String::from("Candidate 2"));
         nominees.insert(3, String::from("Candidate 3"));
 
-        let result = count_position_ballots(position_id, &mut votes, &positions, &nominees);
+        let result = count_position_ballots(position_id, &mut votes, &positions, &[], &nominees);
         let expected = ElectionResult {
             position_id: 1,
             title: String::from("pos"),
             winners: vec![(3, "Candidate 3", 0)],
             voter_count: 3,
+            reopened: false,
+            closed: true,
+            count_sheet: Some(CountSheet {
+                method: String::from("Instant-Runoff Voting"),
+                rows: vec![
+                    CountRow {
+                        name: "Candidate 2",
+                        stages: vec![Some(cell("1", None)), None],
+                    },
+                    CountRow {
+                        name: "Candidate 3",
+                        stages: vec![Some(cell("2", None)), Some(cell("3", Some("+1")))],
+                    },
+                ],
+                exhausted: vec![String::from("0"), String::from("0")],
+                quota: vec![None, None],
+                actions: vec![
+                    String::from("Excluded Candidate 2"),
+                    String::from("Elected Candidate 3"),
+                ],
+                tie_break: Some(String::from("Backwards Tie-Break")),
+                ties: vec![],
+            }),
         };
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn positions_won_by_ron_are_reopened() {
+        let position_id = 1;
+        let mut votes = vec![(1, 1, RON_ID, 1), (1, 1, 2, 2), (1, 2, RON_ID, 1)]
+            .into_iter()
+            .map(Vote::from)
+            .collect();
+
+        let mut positions = BTreeMap::new();
+        positions.insert(
+            1,
+            ExecPosition {
+                id: 1,
+                title: String::from("pos"),
+                num_winners: 1,
+                open: false,
+                election_id: 1,
+                ron: true,
+                opens_at: None,
+                closes_at: None,
+                counting_method: CountingMethod::Irv,
+                tie_break: TieBreak::Backwards,
+                casting_vote: vec![],
+                lot_seed: String::from("seed"),
+            },
+        );
+
+        let mut nominees = HashMap::new();
+        nominees.insert(2, String::from("Candidate 2"));
+
+        let result = count_position_ballots(position_id, &mut votes, &positions, &[], &nominees);
+
+        assert_eq!(result.winners, vec![(RON_ID, RON_NAME, 0)]);
+        assert!(result.reopened);
+    }
+
+    #[test]
+    fn positions_are_counted_with_their_own_method() {
+        let ballots: [&[i32]; 9] = [
+            &[1],
+            &[1],
+            &[1],
+            &[1],
+            &[2, 3],
+            &[2, 3],
+            &[2, 3],
+            &[3, 2],
+            &[3, 2],
+        ];
+
+        let votes: Vec<_> = (1..)
+            .zip(ballots)
+            .flat_map(|(ballot_id, ballot)| {
+                (1..).zip(ballot).map(move |(ranking, candidate_id)| {
+                    Vote::from((1, ballot_id, *candidate_id, ranking))
+                })
+            })
+            .collect();
+
+        let mut nominees = HashMap::new();
+        nominees.insert(1, String::from("Candidate 1"));
+        nominees.insert(2, String::from("Candidate 2"));
+        nominees.insert(3, String::from("Candidate 3"));
+
+        for (counting_method, winner) in [
+            (CountingMethod::Fptp, (1, "Candidate 1", 0)),
+            (CountingMethod::Irv, (2, "Candidate 2", 0)),
+        ] {
+            let mut positions = BTreeMap::new();
+            positions.insert(
+                1,
+                ExecPosition {
+                    id: 1,
+                    title: String::from("pos"),
+                    num_winners: 1,
+                    open: false,
+                    election_id: 1,
+                    ron: false,
+                    opens_at: None,
+                    closes_at: None,
+                    counting_method,
+                    tie_break: TieBreak::Backwards,
+                    casting_vote: vec![],
+                    lot_seed: String::from("seed"),
+                },
+            );
+
+            let result = count_position_ballots(1, &mut votes.clone(), &positions, &[], &nominees);
+
+            assert_eq!(result.winners, vec![winner]);
+            assert_eq!(result.voter_count, 9);
+        }
+    }
+
+    #[test]
+    fn ties_are_broken_with_the_method_chosen_for_the_position() {
+        let position_id = 1;
+        let votes: Vec<_> = vec![(1, 1, 1, 1), (1, 2, 2, 1)]
+            .into_iter()
+            .map(Vote::from)
+            .collect();
+
+        let mut nominees = HashMap::new();
+        nominees.insert(1, String::from("Candidate 1"));
+        nominees.insert(2, String::from("Candidate 2"));
+
+        for (tie_break, casting_vote, winner) in [
+            (TieBreak::Casting, vec![2, 1], (2, "Candidate 2", 0)),
+            (TieBreak::Casting, vec![1, 2], (1, "Candidate 1", 0)),
+        ] {
+            let mut positions = BTreeMap::new();
+            positions.insert(
+                1,
+                ExecPosition {
+                    id: 1,
+                    title: String::from("pos"),
+                    num_winners: 1,
+                    open: false,
+                    election_id: 1,
+                    ron: false,
+                    opens_at: None,
+                    closes_at: None,
+                    counting_method: CountingMethod::Fptp,
+                    tie_break,
+                    casting_vote,
+                    lot_seed: String::from("seed"),
+                },
+            );
+
+            let result =
+                count_position_ballots(position_id, &mut votes.clone(), &positions, &[], &nominees);
+            let sheet = result.count_sheet.unwrap();
+
+            assert_eq!(result.winners, vec![winner]);
+            assert_eq!(sheet.tie_break.as_deref(), Some("Casting Vote"));
+            assert_eq!(
+                sheet.ties,
+                vec![format!(
+                    "Candidate 1, Candidate 2 were tied, and {} was ranked highest on the casting vote",
+                    winner.1
+                )]
+            );
+        }
+    }
+
+    #[test]
+    fn count_sheets_are_written_as_csv() {
+        let sheet = CountSheet {
+            method: String::from("Scottish STV"),
+            rows: vec![
+                CountRow {
+                    name: "Candidate 1",
+                    stages: vec![Some(cell("3", None)), Some(cell("2", Some("-1")))],
+                },
+                CountRow {
+                    name: "Candidate 2",
+                    stages: vec![Some(cell("1", None)), None],
+                },
+            ],
+            exhausted: vec![String::from("0"), String::from("0")],
+            quota: vec![Some(String::from("2")), Some(String::from("2"))],
+            actions: vec![
+                String::from("Elected Candidate 1. Transferred the surplus of Candidate 1"),
+                String::from("Excluded Candidate 2"),
+            ],
+            tie_break: Some(String::from("Drawing Lots")),
+            ties: vec![String::from(
+                "Candidate 1, Candidate 2 were tied, and Candidate 2 was drawn by lot with the seed seed",
+            )],
+        };
+
+        let expected = "\
+Candidate,Stage 1,Stage 1 Transfers,Stage 2,Stage 2 Transfers
+Candidate 1,3,,2,-1
+Candidate 2,1,,,
+Exhausted,0,,0,
+Quota,2,,2,
+Actions,Elected Candidate 1. Transferred the surplus of Candidate 1,,Excluded Candidate 2,
+Tie Break,\"Candidate 1, Candidate 2 were tied, and Candidate 2 was drawn by lot with the seed seed\",,,
+";
+
+        assert_eq!(count_sheet_csv(&sheet), expected);
+    }
 }
@@ -3,14 +3,19 @@
 // This is only really for `DatabaseConnection`
 #![allow(missing_docs)]
 
+use std::collections::HashMap;
+use std::marker::PhantomData;
 use std::str::FromStr;
-use std::{env, marker::PhantomData};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
 
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
 use rocket_db_pools::Database;
 use serde::Deserialize;
 
+use crate::schema;
+
 #[derive(Database)]
 #[database("blackboards")]
 pub struct Db(sqlx::PgPool);
@@ -23,26 +28,101 @@ pub struct Member;
 pub struct ElectionAdmin;
 /// Represents a site administrator.
 pub struct SiteAdmin;
+/// Represents a coach who can verify lifts.
+pub struct Coach;
 
 /// Methods for allowing access control.
-pub trait AccessControl {
-    /// The name of the environment variable to check for this user.
-    const KEY: Option<&'static str>;
+pub trait AccessControl: Send + Sync {
+    /// The name of the role in the database that a user needs to hold.
+    const ROLE: Option<&'static str>;
 }
 
 macro_rules! control_vars {
     ($($struct:path => $statement:expr,)*) => {
         $(impl AccessControl for $struct {
-            const KEY: Option<&'static str> = $statement;
+            const ROLE: Option<&'static str> = $statement;
         })*
     };
 }
 
 control_vars! {
     Generic => None,
-    Member => Some("BARBELL_MEMBERS"),
-    ElectionAdmin => Some("ELECTION_ADMINS"),
-    SiteAdmin => Some("SITE_ADMINS"),
+    Member => Some("member"),
+    ElectionAdmin => Some("election_admin"),
+    SiteAdmin => Some("site_admin"),
+    Coach => Some("coach"),
+}
+
+/// Caches the roles held by each user, to avoid querying the database on every request.
+#[derive(Debug, Default)]
+pub struct RoleCache {
+    entries: RwLock<HashMap<i32, (Instant, Vec<String>)>>,
+}
+
+impl RoleCache {
+    /// How long a user's roles are cached for before being queried again.
+    const LIFETIME: Duration = Duration::from_secs(60);
+
+    /// Gets the cached roles for a user, if they are still fresh.
+    pub fn get(&self, warwick_id: i32) -> Option<Vec<String>> {
+        let entries = self.entries.read().unwrap();
+
+        entries
+            .get(&warwick_id)
+            .filter(|(cached_at, _)| cached_at.elapsed() < Self::LIFETIME)
+            .map(|(_, roles)| roles.clone())
+    }
+
+    /// Caches the roles for a user.
+    pub fn insert(&self, warwick_id: i32, roles: Vec<String>) {
+        let mut entries = self.entries.write().unwrap();
+        entries.insert(warwick_id, (Instant::now(), roles));
+    }
+
+    /// Removes the cached roles for a user, such as when they change.
+    pub fn invalidate(&self, warwick_id: i32) {
+        let mut entries = self.entries.write().unwrap();
+        entries.remove(&warwick_id);
+    }
+
+    /// Removes the cached roles for every user, such as when memberships are imported.
+    pub fn clear(&self) {
+        let mut entries = self.entries.write().unwrap();
+        entries.clear();
+    }
+}
+
+/// Gets the API token from the `Authorization` header, if the request has one.
+pub fn bearer_token<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    request
+        .headers()
+        .get_one("Authorization")?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Whether a request was authenticated by a valid API token, cached for the rest of the request.
+#[derive(Copy, Clone, Debug)]
+struct TokenAuthenticated(bool);
+
+/// Checks whether a request was authenticated by a valid API token, rather than just sending one.
+///
+/// This is only known once a [`User`] guard has checked the token, so it is false for routes
+/// without one.
+pub fn token_authenticated(request: &Request<'_>) -> bool {
+    request.local_cache(|| TokenAuthenticated(false)).0
+}
+
+/// Whether a request was made by a site administrator viewing the website as another user,
+/// cached for the rest of the request.
+#[derive(Copy, Clone, Debug)]
+struct Impersonating(bool);
+
+/// Checks whether a request was made by a site administrator viewing the website as another user.
+///
+/// This is only known once a [`User`] guard has run, so it is false for routes without one.
+pub fn impersonating(request: &Request<'_>) -> bool {
+    request.local_cache(|| Impersonating(false)).0
 }
 
 /// Represents an authorised user for a given route.
@@ -52,40 +132,91 @@ pub struct User<T: AccessControl> {
     pub id: i32,
     /// The user's name
     pub name: String,
+    /// The roles the user holds.
+    roles: Vec<String>,
+    /// The Warwick ID of the site administrator viewing the website as this user, if any.
+    impersonator: Option<i32>,
     /// The privilege level of the user.
     level: PhantomData<T>,
 }
 
 impl<T: AccessControl> User<T> {
-    /// Checks whether the given user is also a member of another environment variable.
-    pub fn is_also<U: AccessControl>(&self) -> bool {
-        let id = self.id.to_string();
+    /// Gets the names of the roles the user holds.
+    pub fn roles(&self) -> &[String] {
+        &self.roles
+    }
 
-        U::KEY
-            .map(|key| env::var(key).expect("Failed to get environment variable"))
-            .map(|value| value.split(',').any(|v| v == id))
+    /// Gets the Warwick ID of the site administrator viewing the website as this user, if any.
+    pub fn impersonator(&self) -> Option<i32> {
+        self.impersonator
+    }
+
+    /// Checks whether the given user also holds the role for another access level.
+    pub fn is_also<U: AccessControl>(&self) -> bool {
+        U::ROLE
+            .map(|role| self.roles.iter().any(|r| r == role))
             .unwrap_or_default()
     }
 
-    fn environment_contains(value: &str) -> bool {
-        T::KEY
-            .map(|key| {
-                // Get the environment variable and parse it
-                let var = env::var(key).expect("Failed to get environment variable");
-                let contains = var.split(',').any(|v| v == value);
+    fn roles_permit(id: i32, roles: &[String]) -> bool {
+        T::ROLE
+            .map(|role| {
+                let contains = roles.iter().any(|r| r == role);
 
                 if !contains {
-                    tracing::warn!(
-                        user_id = %value,
-                        environment_variable = %key,
-                        "Failed to find user in the environment variable"
-                    );
+                    tracing::warn!(user_id = %id, %role, "Failed to find the required role for a user");
                 }
 
                 contains
             })
             .unwrap_or(true)
     }
+
+    /// Gets the roles for a user, either from the cache or the database.
+    async fn fetch_roles(request: &Request<'_>, id: i32) -> Option<Vec<String>> {
+        let cache = request.rocket().state::<RoleCache>()?;
+
+        if let Some(roles) = cache.get(id) {
+            return Some(roles);
+        }
+
+        let db = request.guard::<&Db>().await.succeeded()?;
+        let mut conn = db.acquire().await.ok()?;
+        let roles = schema::UserRole::roles_for(id, &mut conn).await.ok()?;
+
+        cache.insert(id, roles.clone());
+
+        Some(roles)
+    }
+
+    /// Gets the user a site administrator is viewing the website as, if they are.
+    ///
+    /// The administrator's roles are checked on every request, so that impersonation stops if
+    /// their role is revoked.
+    async fn impersonated(request: &Request<'_>, admin_id: i32) -> Option<(i32, String)> {
+        let cookies = request.cookies();
+
+        let id = i32::from_str(cookies.get_private("impersonating_id")?.value()).ok()?;
+        let name = String::from(cookies.get_private("impersonating_name")?.value());
+
+        let roles = Self::fetch_roles(request, admin_id).await?;
+
+        User::<SiteAdmin>::roles_permit(admin_id, &roles).then(|| (id, name))
+    }
+
+    /// Finds the owner of an API token, if the token is valid.
+    async fn authenticate_token(
+        request: &Request<'_>,
+        token: &str,
+    ) -> Option<schema::api_token::TokenOwner> {
+        let db = request.guard::<&Db>().await.succeeded()?;
+        let mut conn = db.acquire().await.ok()?;
+
+        schema::ApiToken::authenticate(token, &mut conn)
+            .await
+            .ok()
+            .flatten()
+    }
 }
 
 #[rocket::async_trait]
@@ -96,23 +227,54 @@ impl<T: AccessControl, 'r> FromRequest<'r> for User<T> {
         let unauthorised = Outcome::Failure((Status::Unauthorized, ()));
         let forbidden = Outcome::Failure((Status::Forbidden, ()));
 
-        let id = match request.cookies().get_private("id") {
-            Some(id) => id,
-            None => return unauthorised,
+        // Scripts authenticate with a token instead of cookies, which limits the roles they can use
+        let (id, name, scopes, impersonator) = match bearer_token(request) {
+            Some(token) => match Self::authenticate_token(request, token).await {
+                Some(owner) => {
+                    request.local_cache(|| TokenAuthenticated(true));
+                    (owner.warwick_id, owner.name, Some(owner.scopes), None)
+                }
+                None => return unauthorised,
+            },
+            None => {
+                let id = match request.cookies().get_private("id") {
+                    Some(id) => i32::from_str(id.value()).unwrap(),
+                    None => return unauthorised,
+                };
+
+                let name = match request.cookies().get_private("name") {
+                    Some(name) => String::from(name.value()),
+                    None => return unauthorised,
+                };
+
+                match Self::impersonated(request, id).await {
+                    Some((target, target_name)) => {
+                        request.local_cache(|| Impersonating(true));
+                        (target, target_name, None, Some(id))
+                    }
+                    None => (id, name, None, None),
+                }
+            }
         };
 
-        let name = match request.cookies().get_private("name") {
-            Some(name) => name,
-            None => return unauthorised,
+        let mut roles = match Self::fetch_roles(request, id).await {
+            Some(roles) => roles,
+            None => return Outcome::Failure((Status::InternalServerError, ())),
         };
 
-        if !Self::environment_contains(id.value()) {
+        if let Some(scopes) = scopes {
+            roles.retain(|role| scopes.contains(role));
+        }
+
+        if !Self::roles_permit(id, &roles) {
             return forbidden;
         }
 
         Outcome::Success(Self {
-            id: i32::from_str(id.value()).unwrap(),
-            name: String::from(name.value()),
+            id,
+            name,
+            roles,
+            impersonator,
             level: PhantomData,
         })
     }
@@ -122,28 +284,64 @@ impl<T: AccessControl, 'r> FromRequest<'r> for User<T> {
 mod tests {
     use super::*;
 
+    fn roles(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| String::from(*name)).collect()
+    }
+
     #[test]
     fn site_admins_can_be_checked() {
-        // Place the values in the environment variable
-        env::set_var("SITE_ADMINS", "1702502");
+        assert!(User::<SiteAdmin>::roles_permit(
+            1702502,
+            &roles(&["site_admin"])
+        ));
+    }
+
+    #[test]
+    fn users_can_hold_multiple_roles() {
+        let held = roles(&["member", "election_admin"]);
 
-        assert!(User::<SiteAdmin>::environment_contains("1702502"));
+        assert!(User::<Member>::roles_permit(1820900, &held));
+        assert!(User::<ElectionAdmin>::roles_permit(1820900, &held));
+        assert!(!User::<SiteAdmin>::roles_permit(1820900, &held));
     }
 
     #[test]
-    fn multiple_users_can_be_within_environment_variables() {
-        // Place the values in the environment variable
-        env::set_var("BARBELL_MEMBERS", "1820900,1701229");
+    fn users_without_the_role_are_rejected() {
+        assert!(!User::<ElectionAdmin>::roles_permit(
+            1702502,
+            &roles(&["member"])
+        ));
+    }
 
-        assert!(User::<Member>::environment_contains("1820900"));
-        assert!(User::<Member>::environment_contains("1701229"));
+    #[test]
+    fn generic_users_need_no_roles() {
+        assert!(User::<Generic>::roles_permit(1702502, &[]));
     }
 
     #[test]
-    fn users_can_be_rejected_from_environment_variables() {
-        // Place the values in the environment variable
-        env::set_var("ELECTION_ADMINS", "1707704");
+    fn other_roles_can_be_checked() {
+        let user = User::<Generic> {
+            id: 1702502,
+            name: String::from("Alex Jackson"),
+            roles: roles(&["site_admin"]),
+            impersonator: None,
+            level: PhantomData,
+        };
+
+        assert!(user.is_also::<SiteAdmin>());
+        assert!(!user.is_also::<ElectionAdmin>());
+        assert!(!user.is_also::<Generic>());
+    }
+
+    #[test]
+    fn cached_roles_can_be_invalidated() {
+        let cache = RoleCache::default();
+        cache.insert(1702502, roles(&["member"]));
+
+        assert_eq!(cache.get(1702502), Some(roles(&["member"])));
+
+        cache.invalidate(1702502);
 
-        assert!(!User::<ElectionAdmin>::environment_contains("1702502"));
+        assert_eq!(cache.get(1702502), None);
     }
 }
@@ -16,13 +16,20 @@ use rocket_dyn_templates::Template;
 
 pub mod api;
 pub mod auth;
+pub mod blt;
 pub mod context;
+pub mod counting;
+pub mod csrf;
 pub mod email;
 pub mod forms;
 pub mod frontend;
 pub mod guards;
+pub mod one_rep_max;
 pub mod schema;
+pub mod scoring;
 pub mod session_window;
+pub mod su_export;
+pub mod weight_class;
 
 /// Catches 401 error codes for redirecting.
 #[catch(401)]
@@ -53,6 +60,7 @@ pub fn build_rocket(config: Figment) -> rocket::Rocket<rocket::Build> {
     rocket::custom(config)
         .attach(guards::Db::init())
         .attach(Template::fairing())
+        .manage(guards::RoleCache::default())
         .register("/", catchers![unauthorised, forbidden])
         .mount(
             "/assets",
@@ -70,11 +78,24 @@ pub fn build_rocket(config: Figment) -> rocket::Rocket<rocket::Build> {
                 frontend::session_attendance,
                 frontend::authenticated,
                 frontend::blackboard,
+                frontend::club_records,
                 frontend::personal_bests,
+                frontend::lift_history,
+                frontend::lift_review,
                 frontend::elections,
                 frontend::election_voting,
                 frontend::election_results,
+                frontend::election_archive,
+                frontend::election_count_sheet,
+                frontend::election_ballot_export,
+                frontend::election_receipts,
                 frontend::election_settings,
+                frontend::election_setup,
+                frontend::roles,
+                frontend::lifts,
+                frontend::memberships,
+                frontend::api_tokens,
+                frontend::impersonation,
                 api::sessions_create,
                 api::session_delete,
                 api::register,
@@ -83,9 +104,34 @@ pub fn build_rocket(config: Figment) -> rocket::Rocket<rocket::Build> {
                 api::authenticate,
                 api::authorised,
                 api::personal_bests,
+                api::lift_record_create,
+                api::lift_record_delete,
+                api::lift_review,
                 api::logout,
                 api::election_vote,
                 api::election_settings_toggle,
+                api::election_settings_schedule,
+                api::election_recount,
+                api::election_create,
+                api::election_archive,
+                api::position_create,
+                api::position_edit,
+                api::position_casting_vote,
+                api::position_delete,
+                api::candidate_create,
+                api::candidate_delete,
+                api::nomination_create,
+                api::nomination_delete,
+                api::lift_create,
+                api::lift_update,
+                api::lift_delete,
+                api::role_grant,
+                api::role_revoke,
+                api::memberships_import,
+                api::token_create,
+                api::token_revoke,
+                api::impersonation_start,
+                api::impersonation_stop,
             ],
         )
 }
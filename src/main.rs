@@ -40,12 +40,17 @@ fn config_from_env() -> Figment {
         .merge(("databases", databases))
 }
 
+/// Runs the database migrations and imports any roles from the environment.
 async fn run_migrations(database_url: &str) -> sqlx::Result<()> {
     let pool = PgPool::connect(database_url).await?;
 
     static MIGRATOR: Migrator = sqlx::migrate!();
     MIGRATOR.run(&pool).await?;
 
+    // Bring across any roles still defined in the environment
+    let mut conn = pool.acquire().await?;
+    blackboards::schema::UserRole::import_from_env(&mut conn).await?;
+
     Ok(())
 }
 
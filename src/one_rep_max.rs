@@ -0,0 +1,259 @@
+//! Estimates one rep maxes from sets of multiple reps.
+//!
+//! Users record their heaviest sets of up to 10 reps for each lift, and each formula estimates
+//! the single they could lift from one of these. Estimates are only made from sets of 10 reps or
+//! fewer, since the formulas become unreliable beyond this.
+
+use serde::Serialize;
+
+use crate::schema::custom_types::{Formula, Unit};
+use crate::schema::lift_record::RepMax;
+use crate::schema::{Lift, PersonalBest};
+
+/// The most reps a set can have to be counted as a rep max.
+pub const MAX_REPS: i32 = 10;
+
+/// The percentage of a one rep max that can be lifted, indexed by the number of half RPE steps
+/// away from a single at RPE 10.
+///
+/// Each additional rep is worth a whole RPE, so 3 reps at RPE 8 is 8 half steps away.
+const RPE_PERCENTAGES: [f32; 27] = [
+    100.0, 97.8, 95.5, 93.9, 92.2, 90.7, 89.2, 87.8, 86.3, 85.0, 83.7, 82.4, 81.1, 79.9, 78.6,
+    77.4, 76.2, 75.1, 73.9, 72.3, 70.7, 69.4, 68.0, 66.7, 65.3, 64.0, 62.6,
+];
+
+/// Rounds an estimate to the nearest 0.1kg, as more precision than this is meaningless.
+fn round(weight: f32) -> f32 {
+    (weight * 10.0).round() / 10.0
+}
+
+/// Estimates a one rep max using the Epley formula.
+pub fn epley(weight: f32, reps: i32) -> f32 {
+    if reps == 1 {
+        return weight;
+    }
+
+    weight * (1.0 + reps as f32 / 30.0)
+}
+
+/// Estimates a one rep max using the Brzycki formula.
+pub fn brzycki(weight: f32, reps: i32) -> f32 {
+    weight * 36.0 / (37.0 - reps as f32)
+}
+
+/// Estimates a one rep max using the RPE table, for RPEs between 6 and 10 in steps of 0.5.
+pub fn rpe_table(weight: f32, reps: i32, rpe: f32) -> Option<f32> {
+    if !(6.0..=10.0).contains(&rpe) || (rpe * 2.0).fract() != 0.0 {
+        return None;
+    }
+
+    let steps = 2 * (reps - 1) + ((10.0 - rpe) * 2.0) as i32;
+    let percentage = RPE_PERCENTAGES.get(usize::try_from(steps).ok()?)?;
+
+    Some(weight * 100.0 / percentage)
+}
+
+/// Estimates the one rep max a rep max suggests, if the formula can be applied to it.
+///
+/// Sets without an RPE are assumed to have been taken to failure.
+pub fn estimate(formula: Formula, rep_max: &RepMax) -> Option<f32> {
+    let RepMax { weight, reps, .. } = *rep_max;
+
+    if !(1..=MAX_REPS).contains(&reps) {
+        return None;
+    }
+
+    let estimate = match formula {
+        Formula::Epley => epley(weight, reps),
+        Formula::Brzycki => brzycki(weight, reps),
+        Formula::Rpe => rpe_table(weight, reps, rep_max.rpe.unwrap_or(10.0))?,
+    };
+
+    Some(round(estimate))
+}
+
+/// Gets the highest one rep max estimated from a user's rep maxes for a lift.
+pub fn best_estimate(rep_maxes: &[RepMax], lift: &str, formula: Formula) -> Option<f32> {
+    rep_maxes
+        .iter()
+        .filter(|rep_max| rep_max.lift == lift)
+        .filter_map(|rep_max| estimate(formula, rep_max))
+        .reduce(f32::max)
+}
+
+/// A user's rep maxes for a lift, along with their estimated one rep max.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RepMaxRow {
+    /// The lift the rep maxes are for
+    pub lift: String,
+    /// The display name of the lift
+    pub name: String,
+    /// The heaviest weight lifted for each number of reps, starting from a single
+    pub weights: Vec<Option<f32>>,
+    /// The highest one rep max estimated from these
+    pub estimate: Option<f32>,
+}
+
+impl RepMaxRow {
+    /// Converts the weights from kilograms into the given unit, for displaying them.
+    pub fn in_unit(self, unit: Unit) -> Self {
+        Self {
+            weights: self
+                .weights
+                .into_iter()
+                .map(|weight| weight.map(|kg| unit.from_kg(kg)))
+                .collect(),
+            estimate: self.estimate.map(|kg| unit.from_kg(kg)),
+            ..self
+        }
+    }
+}
+
+/// Tabulates a user's rep maxes for each lift in the catalogue they have recorded any for.
+pub fn table(lifts: &[Lift], rep_maxes: &[RepMax], formula: Formula) -> Vec<RepMaxRow> {
+    lifts
+        .iter()
+        .filter(|lift| rep_maxes.iter().any(|rep_max| rep_max.lift == lift.name))
+        .map(|lift| {
+            let weights = (1..=MAX_REPS)
+                .map(|reps| {
+                    rep_maxes
+                        .iter()
+                        .find(|rep_max| rep_max.lift == lift.name && rep_max.reps == reps)
+                        .map(|rep_max| rep_max.weight)
+                })
+                .collect();
+
+            RepMaxRow {
+                lift: lift.name.clone(),
+                name: lift.display_name.clone(),
+                weights,
+                estimate: best_estimate(rep_maxes, &lift.name, formula),
+            }
+        })
+        .collect()
+}
+
+/// Replaces a lifter's personal bests with their estimated one rep maxes wherever these are
+/// higher, returning the lifts that were replaced.
+pub fn apply_estimates(
+    personal_best: &mut PersonalBest,
+    rep_maxes: &[RepMax],
+    formula: Formula,
+) -> Vec<String> {
+    let mut lifts: Vec<&str> = Vec::new();
+
+    for rep_max in rep_maxes {
+        if !lifts.contains(&rep_max.lift.as_str()) {
+            lifts.push(&rep_max.lift);
+        }
+    }
+
+    let mut estimated = Vec::new();
+
+    for lift in lifts {
+        let estimate = match best_estimate(rep_maxes, lift, formula) {
+            Some(estimate) => estimate,
+            None => continue,
+        };
+
+        let best = personal_best.bests.get(lift).copied();
+
+        if !matches!(best, Some(best) if best >= estimate) {
+            personal_best.bests.insert(String::from(lift), estimate);
+            estimated.push(String::from(lift));
+        }
+    }
+
+    estimated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rep_max(lift: &str, reps: i32, weight: f32, rpe: Option<f32>) -> RepMax {
+        RepMax {
+            warwick_id: 1702502,
+            lift: String::from(lift),
+            reps,
+            weight,
+            rpe,
+        }
+    }
+
+    #[test]
+    fn formulas_match_reference_values() {
+        assert_eq!(round(epley(100.0, 5)), 116.7);
+        assert_eq!(round(brzycki(100.0, 5)), 112.5);
+        assert_eq!(rpe_table(100.0, 1, 10.0), Some(100.0));
+        assert_eq!(rpe_table(100.0, 3, 8.0).map(round), Some(115.9));
+        assert_eq!(rpe_table(100.0, 10, 6.0).map(round), Some(159.7));
+    }
+
+    #[test]
+    fn singles_are_their_own_estimate() {
+        for formula in [Formula::Epley, Formula::Brzycki, Formula::Rpe] {
+            assert_eq!(
+                estimate(formula, &rep_max("squat", 1, 150.0, None)),
+                Some(150.0)
+            );
+        }
+    }
+
+    #[test]
+    fn rpe_table_rejects_unknown_efforts() {
+        assert_eq!(rpe_table(100.0, 3, 5.5), None);
+        assert_eq!(rpe_table(100.0, 3, 8.2), None);
+    }
+
+    #[test]
+    fn sets_of_more_than_ten_reps_are_not_estimated() {
+        assert_eq!(
+            estimate(Formula::Epley, &rep_max("squat", 12, 100.0, None)),
+            None
+        );
+    }
+
+    #[test]
+    fn tables_include_every_rep_max_and_the_best_estimate() {
+        let rep_maxes = vec![
+            rep_max("bench", 1, 100.0, None),
+            rep_max("bench", 5, 90.0, None),
+            rep_max("squat", 3, 140.0, None),
+        ];
+
+        let lifts: Vec<_> = ["squat", "bench", "deadlift"]
+            .into_iter()
+            .map(|name| Lift::new(name, name, name, crate::schema::lift::POWERLIFTING))
+            .collect();
+        let table = table(&lifts, &rep_maxes, Formula::Epley);
+
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[0].lift, "squat");
+        assert_eq!(table[1].weights[0], Some(100.0));
+        assert_eq!(table[1].weights[4], Some(90.0));
+        assert_eq!(table[1].weights[9], None);
+        assert_eq!(table[1].estimate, Some(105.0));
+    }
+
+    #[test]
+    fn estimates_only_replace_lower_personal_bests() {
+        let mut pb = PersonalBest::default();
+        pb.bests.insert(String::from("squat"), 200.0);
+        pb.bests.insert(String::from("bench"), 100.0);
+
+        let rep_maxes = vec![
+            rep_max("squat", 5, 150.0, None),
+            rep_max("bench", 5, 90.0, None),
+            rep_max("deadlift", 3, 180.0, None),
+        ];
+
+        let estimated = apply_estimates(&mut pb, &rep_maxes, Formula::Epley);
+
+        assert_eq!(estimated, vec!["bench", "deadlift"]);
+        assert_eq!(pb.bests["squat"], 200.0);
+        assert_eq!(pb.bests["bench"], 105.0);
+        assert_eq!(pb.bests["deadlift"], 198.0);
+    }
+}
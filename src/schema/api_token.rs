@@ -0,0 +1,175 @@
+//! Allows modifications of the `api_tokens` table in the database.
+
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::schema::{custom_types, Pool};
+
+/// The prefix added to every token, making them easier to recognise if leaked.
+const TOKEN_PREFIX: &str = "bb_";
+
+/// The number of random bytes in each token.
+const TOKEN_LENGTH: usize = 32;
+
+/// Represents a token in the `api_tokens` table, as shown to its owner.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ApiToken {
+    /// The identifier for the token.
+    pub id: i32,
+    /// The name the user gave the token.
+    pub name: String,
+    /// The roles the token is allowed to use.
+    pub scopes: Vec<String>,
+    /// When the token was created.
+    pub created_at: custom_types::DateTime,
+    /// When the token was last used, if it has been.
+    pub last_used: Option<custom_types::DateTime>,
+}
+
+/// The user a token belongs to, found when authenticating with it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TokenOwner {
+    /// The user's Warwick ID.
+    pub warwick_id: i32,
+    /// The user's name.
+    pub name: String,
+    /// The roles the token is allowed to use.
+    pub scopes: Vec<String>,
+}
+
+/// Hashes a token for storing or looking up in the database.
+///
+/// Tokens are long and random, so a single round of SHA-256 is enough to stop them being recovered
+/// from the database.
+fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Generates a new random token.
+fn generate() -> String {
+    let mut bytes = [0u8; TOKEN_LENGTH];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    format!(
+        "{}{}",
+        TOKEN_PREFIX,
+        base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+    )
+}
+
+impl ApiToken {
+    /// Creates a new token for a user, returning it so that it can be shown to them.
+    ///
+    /// Only the hash of the token is stored, so it cannot be shown again afterwards.
+    pub async fn create(
+        warwick_id: i32,
+        owner_name: &str,
+        name: &str,
+        scopes: &[String],
+        pool: &mut Pool,
+    ) -> sqlx::Result<String> {
+        let token = generate();
+        let created_at = chrono::Utc::now().timestamp();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO api_tokens (warwick_id, owner_name, name, token_hash, scopes, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            warwick_id,
+            owner_name,
+            name,
+            hash(&token),
+            scopes,
+            created_at
+        )
+        .execute(pool)
+        .await?;
+
+        tracing::info!(%warwick_id, %name, ?scopes, "Created an API token for a user");
+
+        Ok(token)
+    }
+
+    /// Gets the tokens belonging to a user that have not been revoked, newest first.
+    pub async fn for_user(warwick_id: i32, pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                id,
+                name,
+                scopes,
+                created_at AS "created_at: custom_types::DateTime",
+                last_used AS "last_used: custom_types::DateTime"
+            FROM api_tokens
+            WHERE warwick_id = $1 AND NOT revoked
+            ORDER BY created_at DESC, id DESC
+            "#,
+            warwick_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Revokes one of a user's tokens, returning whether it existed.
+    pub async fn revoke(id: i32, warwick_id: i32, pool: &mut Pool) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            "UPDATE api_tokens SET revoked = TRUE WHERE id = $1 AND warwick_id = $2 AND NOT revoked",
+            id,
+            warwick_id
+        )
+        .execute(pool)
+        .await?;
+
+        let revoked = result.rows_affected() != 0;
+
+        if revoked {
+            tracing::info!(%id, %warwick_id, "Revoked an API token");
+        }
+
+        Ok(revoked)
+    }
+
+    /// Finds the owner of a token if it is valid, recording that it was used.
+    pub async fn authenticate(token: &str, pool: &mut Pool) -> sqlx::Result<Option<TokenOwner>> {
+        let last_used = chrono::Utc::now().timestamp();
+
+        sqlx::query_as!(
+            TokenOwner,
+            r#"
+            UPDATE api_tokens SET last_used = $2
+            WHERE token_hash = $1 AND NOT revoked
+            RETURNING warwick_id, owner_name AS name, scopes
+            "#,
+            hash(token),
+            last_used
+        )
+        .fetch_optional(pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_tokens_are_unique() {
+        let first = generate();
+        let second = generate();
+
+        assert_ne!(first, second);
+        assert!(first.starts_with(TOKEN_PREFIX));
+    }
+
+    #[test]
+    fn tokens_are_hashed_consistently() {
+        let token = generate();
+
+        assert_eq!(hash(&token), hash(&token));
+        assert_ne!(hash(&token), token);
+        assert_eq!(hash(&token).len(), 64);
+    }
+}
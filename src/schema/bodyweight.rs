@@ -0,0 +1,59 @@
+//! Allows modifications of the `bodyweights` table in the database.
+
+use serde::Serialize;
+
+use crate::schema::{custom_types, Pool};
+
+/// Represents a row in the `bodyweights` table.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Bodyweight {
+    /// The user's Warwick ID.
+    pub warwick_id: i32,
+    /// The user's bodyweight.
+    pub weight: f32,
+    /// The day the bodyweight was recorded.
+    pub recorded_on: custom_types::Date,
+}
+
+impl Bodyweight {
+    /// Converts the weight from kilograms into the given unit, for displaying it.
+    pub fn in_unit(self, unit: custom_types::Unit) -> Self {
+        Self {
+            weight: unit.from_kg(self.weight),
+            ..self
+        }
+    }
+
+    /// Inserts the [`Bodyweight`] into the database.
+    pub async fn insert(&self, pool: &mut Pool) -> sqlx::Result<()> {
+        tracing::info!(warwick_id = %self.warwick_id, weight = %self.weight, recorded_on = %self.recorded_on, "Recording a bodyweight for a user");
+
+        sqlx::query!(
+            "INSERT INTO bodyweights (warwick_id, weight, recorded_on) VALUES ($1, $2, $3)",
+            self.warwick_id,
+            self.weight,
+            self.recorded_on.0
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the most recent bodyweight a user has recorded, if they have.
+    pub async fn latest(warwick_id: i32, pool: &mut Pool) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT warwick_id, weight, recorded_on AS "recorded_on: custom_types::Date"
+            FROM bodyweights
+            WHERE warwick_id = $1
+            ORDER BY recorded_on DESC, id DESC
+            LIMIT 1
+            "#,
+            warwick_id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+}
@@ -13,16 +13,19 @@ pub struct Candidate {
     pub name: String,
     /// Whether they have been elected to the exec yet.
     pub elected: bool,
+    /// The election the candidate is standing in.
+    pub election_id: i32,
 }
 
 impl Candidate {
     /// Inserts the [`Candidate`] into the database.
     pub async fn insert(&self, pool: &mut Pool) -> sqlx::Result<()> {
         sqlx::query!(
-            "INSERT INTO candidates (warwick_id, name, elected) VALUES ($1, $2, $3)",
+            "INSERT INTO candidates (warwick_id, name, elected, election_id) VALUES ($1, $2, $3, $4)",
             self.warwick_id,
             self.name,
-            self.elected
+            self.elected,
+            self.election_id
         )
         .execute(pool)
         .await?;
@@ -30,29 +33,73 @@ impl Candidate {
         Ok(())
     }
 
-    /// Gets all [`Candidate`] entries in the database.
-    pub async fn get_results(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
-        sqlx::query_as!(Self, "SELECT * FROM candidates")
-            .fetch_all(pool)
-            .await
+    /// Gets all the [`Candidate`] entries in an election, sorted by name.
+    pub async fn for_election(election_id: i32, pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            "SELECT * FROM candidates WHERE election_id = $1 ORDER BY name, warwick_id",
+            election_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Deletes a candidate from the current election along with their nominations, returning
+    /// whether they were deleted.
+    ///
+    /// Candidates who have received votes are kept, so that no ballots are changed.
+    pub async fn delete(warwick_id: i32, pool: &mut Pool) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM candidates c
+            USING elections e
+            WHERE e.id = c.election_id AND e.status = 'current'
+            AND c.warwick_id = $1
+            AND NOT EXISTS (
+                SELECT 1 FROM votes v
+                INNER JOIN exec_positions p ON p.id = v.position_id
+                WHERE v.candidate_id = $1 AND p.election_id = c.election_id
+            )
+            "#,
+            warwick_id
+        )
+        .execute(pool)
+        .await?;
+
+        let deleted = result.rows_affected() != 0;
+
+        if deleted {
+            tracing::info!(%warwick_id, "Deleted a candidate");
+        }
+
+        Ok(deleted)
     }
 
-    /// Mark the winning candidates as such.
-    pub async fn mark_elected(candidates: &[i32], pool: &mut Pool) -> sqlx::Result<()> {
+    /// Mark the winning candidates of an election as such.
+    pub async fn mark_elected(
+        election_id: i32,
+        candidates: &[i32],
+        pool: &mut Pool,
+    ) -> sqlx::Result<()> {
         tracing::info!(
+            %election_id,
             ?candidates,
             "Marking some candidates as elected to positions"
         );
 
         // Remove all the existing winners
-        sqlx::query!("UPDATE candidates SET elected = FALSE")
-            .execute(&mut *pool)
-            .await?;
+        sqlx::query!(
+            "UPDATE candidates SET elected = FALSE WHERE election_id = $1",
+            election_id
+        )
+        .execute(&mut *pool)
+        .await?;
 
         // Set each candidate to be elected TODO: this could use `IN`
         for candidate in candidates {
             sqlx::query!(
-                "UPDATE candidates SET elected = TRUE WHERE warwick_id = $1",
+                "UPDATE candidates SET elected = TRUE WHERE election_id = $1 AND warwick_id = $2",
+                election_id,
                 candidate
             )
             .execute(&mut *pool)
@@ -0,0 +1,241 @@
+//! Allows the club records to be derived from the `lift_records` table in the database.
+
+use serde::Serialize;
+
+use crate::schema::{custom_types, Lift, Pool};
+use crate::weight_class;
+
+/// A lift that set a club record for its category and weight class when it was performed.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ClubRecord {
+    /// The lift that was performed.
+    pub lift: String,
+    /// The category the lift was performed in.
+    pub sex: custom_types::Sex,
+    /// The weight class the lift was performed in.
+    pub weight_class: String,
+    /// The lifter's Warwick ID.
+    pub warwick_id: i32,
+    /// The lifter's name, if they have appeared on the boards.
+    pub name: Option<String>,
+    /// The weight lifted.
+    pub weight: f32,
+    /// The day the record was set.
+    pub recorded_on: custom_types::Date,
+    /// Whether the lift has been verified by a coach.
+    pub verified: bool,
+}
+
+/// The record for a category and weight class, along with the people who held it before.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ClassRecord {
+    /// The category the record is for
+    pub sex: custom_types::Sex,
+    /// The weight class the record is for
+    pub weight_class: String,
+    /// The lift that currently holds the record
+    pub current: ClubRecord,
+    /// The lifts that held the record before, most recent first
+    pub previous: Vec<ClubRecord>,
+}
+
+/// The records for each category and weight class of a lift.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LiftRecords {
+    /// The lift the records are for
+    pub lift: Lift,
+    /// The records for each category and weight class, lightest first
+    pub records: Vec<ClassRecord>,
+}
+
+impl ClubRecord {
+    /// Converts the weight from kilograms into the given unit, for displaying it.
+    pub fn in_unit(self, unit: custom_types::Unit) -> Self {
+        Self {
+            weight: unit.from_kg(self.weight),
+            ..self
+        }
+    }
+
+    /// Gets every lift that set a club record, oldest first.
+    ///
+    /// A single sets a record if it is heavier than every earlier one in the same category and
+    /// weight class, ignoring rejected lifts. Lifters are included regardless of whether they are
+    /// shown on the boards, so records set by people who have left the club remain.
+    pub async fn get_results(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                history.lift,
+                history.sex AS "sex!: custom_types::Sex",
+                history.weight_class AS "weight_class!",
+                history.warwick_id,
+                pb.name AS "name?",
+                history.weight,
+                history.recorded_on AS "recorded_on: custom_types::Date",
+                history.status = 'verified' AS "verified!"
+            FROM (
+                SELECT
+                    lift,
+                    sex,
+                    weight_class,
+                    warwick_id,
+                    weight,
+                    recorded_on,
+                    status,
+                    id,
+                    MAX(weight) OVER (
+                        PARTITION BY lift, sex, weight_class
+                        ORDER BY recorded_on, id
+                        ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING
+                    ) AS previous_best
+                FROM lift_records
+                WHERE (reps IS NULL OR reps = 1)
+                AND status <> 'rejected'
+                AND sex IS NOT NULL
+                AND weight_class IS NOT NULL
+            ) history
+            LEFT JOIN personal_bests pb ON pb.warwick_id = history.warwick_id
+            WHERE history.previous_best IS NULL OR history.weight > history.previous_best
+            ORDER BY history.recorded_on, history.id
+            "#
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
+
+impl LiftRecords {
+    /// Converts the weights from kilograms into the given unit, for displaying them.
+    pub fn in_unit(mut self, unit: custom_types::Unit) -> Self {
+        for record in &mut self.records {
+            record.current = record.current.clone().in_unit(unit);
+            record.previous = record
+                .previous
+                .drain(..)
+                .map(|previous| previous.in_unit(unit))
+                .collect();
+        }
+
+        self
+    }
+}
+
+/// Groups the lifts that set club records by lift, category and weight class.
+///
+/// Lifts appear in the order of the catalogue, and only those with a record are included. The
+/// records for each lift are sorted by category and then weight class, lightest first.
+pub fn group(lifts: &[Lift], history: Vec<ClubRecord>) -> Vec<LiftRecords> {
+    let mut grouped: Vec<LiftRecords> = lifts
+        .iter()
+        .map(|lift| LiftRecords {
+            lift: lift.clone(),
+            records: Vec::new(),
+        })
+        .collect();
+
+    // The history is oldest first, so each lift replaces the one before it as the record
+    for record in history {
+        let lift = match grouped.iter_mut().find(|l| l.lift.name == record.lift) {
+            Some(lift) => lift,
+            None => continue,
+        };
+
+        let existing = lift
+            .records
+            .iter_mut()
+            .find(|r| r.sex == record.sex && r.weight_class == record.weight_class);
+
+        match existing {
+            Some(class) => {
+                let previous = std::mem::replace(&mut class.current, record);
+                class.previous.insert(0, previous);
+            }
+            None => lift.records.push(ClassRecord {
+                sex: record.sex,
+                weight_class: record.weight_class.clone(),
+                current: record,
+                previous: Vec::new(),
+            }),
+        }
+    }
+
+    for lift in &mut grouped {
+        lift.records.sort_by_key(|record| {
+            let classes = weight_class::classes(record.sex);
+            let position = classes.iter().position(|c| *c == record.weight_class);
+
+            (record.sex, position.unwrap_or(classes.len()))
+        });
+    }
+
+    grouped.retain(|lift| !lift.records.is_empty());
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::schema::custom_types::Sex;
+
+    fn lift(name: &str) -> Lift {
+        Lift::new(name, name, name, crate::schema::lift::POWERLIFTING)
+    }
+
+    fn record(lift: &str, sex: Sex, class: &str, weight: f32, day: u32) -> ClubRecord {
+        ClubRecord {
+            lift: String::from(lift),
+            sex,
+            weight_class: String::from(class),
+            warwick_id: 1702502,
+            name: None,
+            weight,
+            recorded_on: custom_types::Date(NaiveDate::from_ymd_opt(2022, 10, day).unwrap()),
+            verified: false,
+        }
+    }
+
+    #[test]
+    fn later_records_replace_earlier_ones() {
+        let history = vec![
+            record("squat", Sex::Male, "83", 200.0, 1),
+            record("squat", Sex::Male, "83", 210.0, 2),
+            record("squat", Sex::Male, "83", 215.0, 3),
+        ];
+
+        let grouped = group(&[lift("squat")], history);
+        let class = &grouped[0].records[0];
+
+        assert_eq!(class.current.weight, 215.0);
+
+        let previous: Vec<_> = class.previous.iter().map(|r| r.weight).collect();
+        assert_eq!(previous, vec![210.0, 200.0]);
+    }
+
+    #[test]
+    fn records_are_kept_per_category_and_class() {
+        let history = vec![
+            record("bench", Sex::Male, "120+", 180.0, 1),
+            record("bench", Sex::Female, "63", 90.0, 1),
+            record("bench", Sex::Male, "74", 140.0, 2),
+        ];
+
+        let grouped = group(&[lift("squat"), lift("bench")], history);
+
+        assert_eq!(grouped.len(), 1);
+
+        let classes: Vec<_> = grouped[0]
+            .records
+            .iter()
+            .map(|r| (r.sex, r.weight_class.as_str()))
+            .collect();
+
+        assert_eq!(
+            classes,
+            vec![(Sex::Male, "74"), (Sex::Male, "120+"), (Sex::Female, "63")]
+        );
+    }
+}
@@ -3,8 +3,212 @@
 use std::fmt;
 
 use chrono::TimeZone;
+use rocket::form::FromFormField;
+use serde::Serialize;
 use sqlx::Type;
 
+/// Represents the category a lifter competes in, to be stored as TEXT in SQL.
+#[derive(
+    Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Type, FromFormField, Serialize,
+)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Sex {
+    /// Competes in the male category.
+    #[field(value = "male")]
+    Male,
+    /// Competes in the female category.
+    #[field(value = "female")]
+    Female,
+}
+
+/// Represents whether a recorded lift has been checked by a coach, to be stored as TEXT in SQL.
+#[derive(
+    Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Type, FromFormField, Serialize,
+)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum LiftStatus {
+    /// Self-reported and waiting to be reviewed.
+    #[field(value = "pending")]
+    Pending,
+    /// Checked by a coach, such as from a video.
+    #[field(value = "verified")]
+    Verified,
+    /// Rejected by a coach, so it no longer counts.
+    #[field(value = "rejected")]
+    Rejected,
+}
+
+/// Represents whether an election is being run or has finished, to be stored as TEXT in SQL.
+#[derive(
+    Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Type, FromFormField, Serialize,
+)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ElectionStatus {
+    /// Being set up or voted on, of which there is at most one at a time.
+    #[field(value = "current")]
+    Current,
+    /// Finished, so only its results can be viewed.
+    #[field(value = "archived")]
+    Archived,
+}
+
+/// Represents the unit a user enters and views weights in, to be stored as TEXT in SQL.
+///
+/// Weights are always stored in kilograms, and only converted when shown or submitted.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Type,
+    FromFormField,
+    Serialize,
+)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    /// Kilograms.
+    #[default]
+    #[field(value = "kg")]
+    Kg,
+    /// Pounds.
+    #[field(value = "lb")]
+    Lb,
+}
+
+impl Unit {
+    /// The number of kilograms in a pound.
+    const KG_PER_LB: f32 = 0.453_592_37;
+
+    /// Converts a weight in this unit into kilograms.
+    pub fn to_kg(self, weight: f32) -> f32 {
+        match self {
+            Self::Kg => weight,
+            Self::Lb => weight * Self::KG_PER_LB,
+        }
+    }
+
+    /// Converts a weight in kilograms into this unit, rounding pounds to one decimal place.
+    pub fn from_kg(self, weight: f32) -> f32 {
+        match self {
+            Self::Kg => weight,
+            Self::Lb => (weight / Self::KG_PER_LB * 10.0).round() / 10.0,
+        }
+    }
+}
+
+/// Represents the formula used to estimate a one rep max, to be stored as TEXT in SQL.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Type,
+    FromFormField,
+    Serialize,
+)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Formula {
+    /// The Epley formula.
+    #[default]
+    #[field(value = "epley")]
+    Epley,
+    /// The Brzycki formula.
+    #[field(value = "brzycki")]
+    Brzycki,
+    /// The RPE table popularised by Reactive Training Systems.
+    #[field(value = "rpe")]
+    Rpe,
+}
+
+/// Represents the method used to count the ballots for a position, to be stored as TEXT in SQL.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Type,
+    FromFormField,
+    Serialize,
+)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum CountingMethod {
+    /// Instant-runoff voting, ranking candidates by how long they avoid elimination.
+    #[default]
+    #[field(value = "irv")]
+    Irv,
+    /// The single transferable vote using Meek's method.
+    #[field(value = "meek")]
+    Meek,
+    /// The single transferable vote as used in Scottish local elections, with Gregory transfers.
+    #[field(value = "scottish")]
+    Scottish,
+    /// Approval voting, where every candidate on a ballot counts equally.
+    #[field(value = "approval")]
+    Approval,
+    /// First past the post, only counting first preferences.
+    #[field(value = "fptp")]
+    Fptp,
+}
+
+impl CountingMethod {
+    /// Checks whether ballots rank every candidate, rather than only the candidates the voter
+    /// approves of or their first choice.
+    pub fn is_ranked(self) -> bool {
+        !matches!(self, Self::Approval | Self::Fptp)
+    }
+}
+
+/// Represents how ties for the last seats of a position are broken, to be stored as TEXT in SQL.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Type,
+    FromFormField,
+    Serialize,
+)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TieBreak {
+    /// Prefer whoever had more votes at the latest earlier stage where the totals differed.
+    #[default]
+    #[field(value = "backwards")]
+    Backwards,
+    /// Prefer whoever is ranked higher on the casting vote recorded for the position.
+    #[field(value = "casting")]
+    Casting,
+    /// Draw lots using the seed published with the results.
+    #[field(value = "lot")]
+    Lot,
+}
+
 /// Represents a custom datetime, to be stored as BigInt in SQL and formatted otherwise.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Type)]
 #[sqlx(transparent)]
@@ -22,6 +226,11 @@ impl DateTime {
     }
 }
 
+/// Represents a calendar date, to be stored as a DATE in SQL and formatted otherwise.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Type)]
+#[sqlx(transparent)]
+pub struct Date(pub chrono::NaiveDate);
+
 impl serde::Serialize for DateTime {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -39,3 +248,89 @@ impl fmt::Display for DateTime {
         write!(f, "{}", datetime.format("%a %d %h, %H:%M"))
     }
 }
+
+impl serde::Serialize for Date {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let formatted = self.to_string();
+        serializer.serialize_str(&formatted)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 08 Oct 2022
+        write!(f, "{}", self.0.format("%d %b %Y"))
+    }
+}
+
+impl fmt::Display for Sex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Male => write!(f, "Male"),
+            Self::Female => write!(f, "Female"),
+        }
+    }
+}
+
+impl fmt::Display for LiftStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pending => write!(f, "Pending"),
+            Self::Verified => write!(f, "Verified"),
+            Self::Rejected => write!(f, "Rejected"),
+        }
+    }
+}
+
+impl fmt::Display for ElectionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Current => write!(f, "Current"),
+            Self::Archived => write!(f, "Archived"),
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Kg => write!(f, "kg"),
+            Self::Lb => write!(f, "lb"),
+        }
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Epley => write!(f, "Epley"),
+            Self::Brzycki => write!(f, "Brzycki"),
+            Self::Rpe => write!(f, "RPE Table"),
+        }
+    }
+}
+
+impl fmt::Display for TieBreak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Backwards => write!(f, "Backwards Tie-Break"),
+            Self::Casting => write!(f, "Casting Vote"),
+            Self::Lot => write!(f, "Drawing Lots"),
+        }
+    }
+}
+
+impl fmt::Display for CountingMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Irv => write!(f, "Instant-Runoff Voting"),
+            Self::Meek => write!(f, "Meek STV"),
+            Self::Scottish => write!(f, "Scottish STV"),
+            Self::Approval => write!(f, "Approval Voting"),
+            Self::Fptp => write!(f, "First Past the Post"),
+        }
+    }
+}
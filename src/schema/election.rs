@@ -0,0 +1,133 @@
+//! Allows modifications of the `elections` table in the database.
+
+use serde::Serialize;
+use sqlx::Connection;
+
+use crate::schema::{custom_types, Pool};
+
+/// Represents a row in the `elections` table.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Election {
+    /// The identifier for the election.
+    pub id: i32,
+    /// The name of the election, such as `2023 AGM`.
+    pub name: String,
+    /// The day the election is held.
+    pub held_on: custom_types::Date,
+    /// Whether the election is being run or has finished.
+    pub status: custom_types::ElectionStatus,
+}
+
+impl Election {
+    /// Checks whether the election has finished, so it can no longer be changed.
+    pub fn is_archived(&self) -> bool {
+        self.status == custom_types::ElectionStatus::Archived
+    }
+
+    /// Creates a new election to be set up and run, returning its identifier.
+    ///
+    /// This fails if another election is still being run, as it must be archived first.
+    pub async fn create(
+        name: &str,
+        held_on: chrono::NaiveDate,
+        pool: &mut Pool,
+    ) -> sqlx::Result<i32> {
+        tracing::info!(%name, %held_on, "Creating an election");
+
+        sqlx::query!(
+            "INSERT INTO elections (name, held_on) VALUES ($1, $2) RETURNING id",
+            name,
+            held_on
+        )
+        .map(|row| row.id)
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Gets the election being run, if there is one.
+    pub async fn current(pool: &mut Pool) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                id,
+                name,
+                held_on AS "held_on: custom_types::Date",
+                status AS "status: custom_types::ElectionStatus"
+            FROM elections
+            WHERE status = 'current'
+            "#
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Gets an election by its identifier.
+    pub async fn find(id: i32, pool: &mut Pool) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                id,
+                name,
+                held_on AS "held_on: custom_types::Date",
+                status AS "status: custom_types::ElectionStatus"
+            FROM elections
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Gets the elections that have finished, most recent first.
+    pub async fn archived(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                id,
+                name,
+                held_on AS "held_on: custom_types::Date",
+                status AS "status: custom_types::ElectionStatus"
+            FROM elections
+            WHERE status = 'archived'
+            ORDER BY held_on DESC, id DESC
+            "#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Finishes the election being run, closing voting for all its positions and removing their
+    /// schedules so that nothing about it can change. Returns whether there was an election to
+    /// archive.
+    pub async fn archive_current(pool: &mut Pool) -> sqlx::Result<bool> {
+        let mut transaction = pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE exec_positions SET open = FALSE, opens_at = NULL, closes_at = NULL
+            WHERE election_id IN (SELECT id FROM elections WHERE status = 'current')
+            "#
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        let result =
+            sqlx::query!("UPDATE elections SET status = 'archived' WHERE status = 'current'")
+                .execute(&mut transaction)
+                .await?;
+
+        transaction.commit().await?;
+
+        let archived = result.rows_affected() != 0;
+
+        if archived {
+            tracing::info!("Archived the current election");
+        }
+
+        Ok(archived)
+    }
+}
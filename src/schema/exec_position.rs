@@ -1,8 +1,16 @@
 //! Allows modifications of the `exec_positions` table in the database.
 
 use serde::Serialize;
+use sha2::{Digest, Sha256};
 
-use crate::schema::Pool;
+use crate::schema::{custom_types, Pool};
+
+/// The identifier of the built-in candidate for re-opening nominations, which is never a valid
+/// Warwick ID.
+pub const RON_ID: i32 = 0;
+
+/// The name shown for the built-in candidate for re-opening nominations.
+pub const RON_NAME: &str = "Re-open Nominations";
 
 /// Represents a row in the `exec_positions` table.
 #[derive(Clone, Debug, Serialize)]
@@ -13,19 +21,63 @@ pub struct ExecPosition {
     pub title: String,
     /// The number of people who can win in this position
     pub num_winners: i32,
-    /// Whether voting is open for this position or not
+    /// Whether voting is open for this position or not, either by hand or by its schedule
     pub open: bool,
+    /// The election the position is part of
+    pub election_id: i32,
+    /// Whether re-opening nominations is offered on the ballot
+    pub ron: bool,
+    /// When voting opens automatically, as a Unix timestamp
+    pub opens_at: Option<i64>,
+    /// When voting closes automatically, as a Unix timestamp
+    pub closes_at: Option<i64>,
+    /// The method used to count the ballots
+    pub counting_method: custom_types::CountingMethod,
+    /// How ties for the last seats are broken
+    pub tie_break: custom_types::TieBreak,
+    /// The casting vote used to break ties, listing candidates in order of preference
+    pub casting_vote: Vec<i32>,
+    /// The seed used to draw lots, which is chosen when the position is created and never changes.
+    /// Only its hash is shown until voting has closed, when it is published with the results
+    pub lot_seed: String,
 }
 
 impl ExecPosition {
+    /// Checks whether voting has finished, so that the results can be counted.
+    ///
+    /// Positions that are open, or that are scheduled to open or close later on, are not
+    /// finished.
+    pub fn has_closed(&self, now: i64) -> bool {
+        !self.open
+            && !matches!(self.opens_at, Some(opens_at) if opens_at > now)
+            && !matches!(self.closes_at, Some(closes_at) if closes_at > now)
+    }
+
+    /// Gets the SHA-256 hash of the seed for drawing lots, which is shown before voting so that
+    /// anyone can check the seed published with the results is the one chosen beforehand.
+    pub fn lot_seed_hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.lot_seed.as_bytes()))
+    }
+
     /// Inserts the [`ExecPosition`] into the database.
     pub async fn insert(&self, pool: &mut Pool) -> sqlx::Result<()> {
         sqlx::query!(
-            "INSERT INTO exec_positions (id, title, num_winners, open) VALUES ($1, $2, $3, $4)",
+            r#"
+            INSERT INTO exec_positions (id, title, num_winners, open, election_id, ron, opens_at, closes_at, counting_method, tie_break, casting_vote, lot_seed)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            "#,
             self.id,
             self.title,
             self.num_winners,
-            self.open
+            self.open,
+            self.election_id,
+            self.ron,
+            self.opens_at,
+            self.closes_at,
+            self.counting_method as _,
+            self.tie_break as _,
+            &self.casting_vote,
+            self.lot_seed
         )
         .execute(pool)
         .await?;
@@ -33,11 +85,192 @@ impl ExecPosition {
         Ok(())
     }
 
-    /// Gets all [`ExecPosition`] entries in the database.
-    pub async fn get_results(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
-        sqlx::query_as!(Self, "SELECT * FROM exec_positions ORDER BY id")
-            .fetch_all(pool)
-            .await
+    /// Creates a new position in an election, closed for voting, returning its identifier.
+    pub async fn create(
+        election_id: i32,
+        title: &str,
+        num_winners: i32,
+        ron: bool,
+        counting_method: custom_types::CountingMethod,
+        tie_break: custom_types::TieBreak,
+        pool: &mut Pool,
+    ) -> sqlx::Result<i32> {
+        tracing::info!(%election_id, %title, %num_winners, %ron, %counting_method, %tie_break, "Creating an exec position");
+
+        sqlx::query!(
+            "INSERT INTO exec_positions (title, num_winners, open, election_id, ron, counting_method, tie_break) VALUES ($1, $2, FALSE, $3, $4, $5, $6) RETURNING id",
+            title,
+            num_winners,
+            election_id,
+            ron,
+            counting_method as _,
+            tie_break as _
+        )
+        .map(|row| row.id)
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Changes the title, number of winners, whether re-opening nominations is offered, the
+    /// counting method and how ties are broken for a position in the current election.
+    ///
+    /// Re-opening nominations, the number of winners, the counting method and how ties are broken
+    /// cannot be changed once the position has been voted for, as they would change the ballots
+    /// and the results they were cast for.
+    pub async fn update(
+        position_id: i32,
+        title: &str,
+        num_winners: i32,
+        ron: bool,
+        counting_method: custom_types::CountingMethod,
+        tie_break: custom_types::TieBreak,
+        pool: &mut Pool,
+    ) -> sqlx::Result<bool> {
+        tracing::info!(%position_id, %title, %num_winners, %ron, %counting_method, %tie_break, "Updating an exec position");
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE exec_positions SET title = $1, num_winners = $2, ron = $3, counting_method = $4, tie_break = $5
+            WHERE id = $6
+            AND election_id IN (SELECT id FROM elections WHERE status = 'current')
+            AND (
+                (ron = $3 AND num_winners = $2 AND counting_method = $4 AND tie_break = $5)
+                OR NOT EXISTS (SELECT 1 FROM votes WHERE position_id = $6)
+            )
+            "#,
+            title,
+            num_winners,
+            ron,
+            counting_method as _,
+            tie_break as _,
+            position_id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() != 0)
+    }
+
+    /// Deletes a position in the current election along with its nominations, returning whether
+    /// it was deleted.
+    ///
+    /// Positions that have been voted for are kept, so that no ballots are lost.
+    pub async fn delete(position_id: i32, pool: &mut Pool) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM exec_positions
+            WHERE id = $1
+            AND election_id IN (SELECT id FROM elections WHERE status = 'current')
+            AND NOT EXISTS (SELECT 1 FROM votes WHERE position_id = $1)
+            "#,
+            position_id
+        )
+        .execute(pool)
+        .await?;
+
+        let deleted = result.rows_affected() != 0;
+
+        if deleted {
+            tracing::info!(%position_id, "Deleted an exec position");
+        }
+
+        Ok(deleted)
+    }
+
+    /// Gets all the [`ExecPosition`] entries in an election, with whether they are open taking
+    /// their schedules into account.
+    pub async fn for_election(election_id: i32, pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        let now = chrono::Utc::now().timestamp();
+
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                id,
+                title,
+                num_winners,
+                (open OR COALESCE(opens_at <= $2, FALSE))
+                    AND NOT COALESCE(closes_at <= $2, FALSE) AS "open!",
+                election_id,
+                ron,
+                opens_at,
+                closes_at,
+                counting_method AS "counting_method: custom_types::CountingMethod",
+                tie_break AS "tie_break: custom_types::TieBreak",
+                casting_vote,
+                lot_seed
+            FROM exec_positions
+            WHERE election_id = $1
+            ORDER BY id
+            "#,
+            election_id,
+            now
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Gets a single [`ExecPosition`], with whether it is open taking its schedule into account.
+    pub async fn find(position_id: i32, pool: &mut Pool) -> sqlx::Result<Option<Self>> {
+        let now = chrono::Utc::now().timestamp();
+
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                id,
+                title,
+                num_winners,
+                (open OR COALESCE(opens_at <= $2, FALSE))
+                    AND NOT COALESCE(closes_at <= $2, FALSE) AS "open!",
+                election_id,
+                ron,
+                opens_at,
+                closes_at,
+                counting_method AS "counting_method: custom_types::CountingMethod",
+                tie_break AS "tie_break: custom_types::TieBreak",
+                casting_vote,
+                lot_seed
+            FROM exec_positions
+            WHERE id = $1
+            "#,
+            position_id,
+            now
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Records the casting vote for a position in the current election, returning whether it was
+    /// recorded.
+    ///
+    /// The casting vote cannot be changed once the position has been voted for, so that it can't
+    /// be chosen to suit the ballots.
+    pub async fn set_casting_vote(
+        position_id: i32,
+        casting_vote: &[i32],
+        pool: &mut Pool,
+    ) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE exec_positions SET casting_vote = $2
+            WHERE id = $1
+            AND election_id IN (SELECT id FROM elections WHERE status = 'current')
+            AND NOT EXISTS (SELECT 1 FROM votes WHERE position_id = $1)
+            "#,
+            position_id,
+            casting_vote
+        )
+        .execute(pool)
+        .await?;
+
+        let recorded = result.rows_affected() != 0;
+
+        if recorded {
+            tracing::info!(%position_id, ?casting_vote, "Recorded the casting vote for a position");
+        }
+
+        Ok(recorded)
     }
 
     /// Gets the title of a given position.
@@ -51,39 +284,146 @@ impl ExecPosition {
         .await
     }
 
-    /// Gets the identifiers of all closed positions.
-    pub async fn closed_identifiers(pool: &mut Pool) -> sqlx::Result<Vec<i32>> {
-        sqlx::query!("SELECT id FROM exec_positions WHERE open IS NOT TRUE")
-            .map(|row| row.id)
-            .fetch_all(pool)
-            .await
+    /// Gets the identifiers of the positions in an election that have received votes.
+    pub async fn voted_identifiers(election_id: i32, pool: &mut Pool) -> sqlx::Result<Vec<i32>> {
+        sqlx::query!(
+            r#"
+            SELECT DISTINCT v.position_id
+            FROM votes v
+            INNER JOIN exec_positions p ON p.id = v.position_id
+            WHERE p.election_id = $1
+            "#,
+            election_id
+        )
+        .map(|row| row.position_id)
+        .fetch_all(pool)
+        .await
     }
 
-    /// Checks whether voting is open for a given identifier.
+    /// Checks whether voting is open for a given identifier, which is only possible in the
+    /// current election.
+    ///
+    /// Voting is open once it has been opened by hand or its scheduled opening has passed, until
+    /// its scheduled closing passes.
     pub async fn voting_is_open(position_id: i32, pool: &mut Pool) -> bool {
-        sqlx::query_as!(
-            Self,
-            "SELECT * FROM exec_positions WHERE id = $1",
-            position_id
+        let now = chrono::Utc::now().timestamp();
+
+        sqlx::query!(
+            r#"
+            SELECT
+                (p.open OR COALESCE(p.opens_at <= $2, FALSE))
+                AND NOT COALESCE(p.closes_at <= $2, FALSE)
+                AND e.status = 'current' AS "open!"
+            FROM exec_positions p
+            INNER JOIN elections e ON e.id = p.election_id
+            WHERE p.id = $1
+            "#,
+            position_id,
+            now
         )
+        .map(|row| row.open)
         .fetch_one(pool)
         .await
-        .ok()
-        .map(|row| row.open)
         .unwrap_or_default()
     }
 
-    /// Toggles the state of the position, either opening or closing voting.
-    pub async fn toggle_state(position_id: i32, pool: &mut Pool) -> sqlx::Result<()> {
+    /// Toggles the state of a position in the current election, either opening or closing
+    /// voting.
+    ///
+    /// This takes over from any schedule the position had, which is removed.
+    pub async fn toggle_state(position_id: i32, pool: &mut Pool) -> sqlx::Result<bool> {
         tracing::info!(%position_id, "Toggling the state of an exec position");
 
-        sqlx::query!(
-            "UPDATE exec_positions SET open = NOT open WHERE id = $1",
+        let now = chrono::Utc::now().timestamp();
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE exec_positions
+            SET
+                open = NOT ((open OR COALESCE(opens_at <= $2, FALSE)) AND NOT COALESCE(closes_at <= $2, FALSE)),
+                opens_at = NULL,
+                closes_at = NULL
+            WHERE id = $1
+            AND election_id IN (SELECT id FROM elections WHERE status = 'current')
+            "#,
+            position_id,
+            now
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() != 0)
+    }
+
+    /// Sets when voting for a position in the current election opens and closes automatically,
+    /// returning whether the position was found.
+    ///
+    /// Either can be left out, such as to open voting by hand and close it automatically.
+    pub async fn schedule(
+        position_id: i32,
+        opens_at: Option<i64>,
+        closes_at: Option<i64>,
+        pool: &mut Pool,
+    ) -> sqlx::Result<bool> {
+        tracing::info!(%position_id, ?opens_at, ?closes_at, "Scheduling voting for an exec position");
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE exec_positions
+            SET opens_at = $1, closes_at = $2
+            WHERE id = $3
+            AND election_id IN (SELECT id FROM elections WHERE status = 'current')
+            "#,
+            opens_at,
+            closes_at,
             position_id
         )
         .execute(pool)
         .await?;
 
-        Ok(())
+        Ok(result.rows_affected() != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(open: bool, opens_at: Option<i64>, closes_at: Option<i64>) -> ExecPosition {
+        ExecPosition {
+            id: 1,
+            title: String::from("President"),
+            num_winners: 1,
+            open,
+            election_id: 1,
+            ron: true,
+            opens_at,
+            closes_at,
+            counting_method: custom_types::CountingMethod::Irv,
+            tie_break: custom_types::TieBreak::Backwards,
+            casting_vote: vec![],
+            lot_seed: String::from("seed"),
+        }
+    }
+
+    #[test]
+    fn open_positions_have_not_closed() {
+        assert!(!position(true, None, None).has_closed(100));
+        assert!(position(false, None, None).has_closed(100));
+    }
+
+    #[test]
+    fn scheduled_positions_close_once_both_times_have_passed() {
+        assert!(!position(false, Some(200), Some(300)).has_closed(100));
+        assert!(!position(false, None, Some(300)).has_closed(200));
+        assert!(position(false, Some(200), Some(300)).has_closed(300));
+    }
+
+    #[test]
+    fn lot_seeds_are_published_as_their_hash() {
+        assert_eq!(
+            position(true, None, None).lot_seed_hash(),
+            "19b25856e1c150ca834cffc8b59b23adbd0ec0389e58eb22b3b64768098d002b"
+        );
     }
 }
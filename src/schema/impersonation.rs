@@ -0,0 +1,85 @@
+//! Allows modifications of the `impersonation_log` table in the database.
+
+use serde::Serialize;
+
+use crate::schema::{custom_types, Pool};
+
+/// Represents a row in the `impersonation_log` table.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ImpersonationEntry {
+    /// The Warwick ID of the site administrator.
+    pub admin_id: i32,
+    /// The Warwick ID of the user they viewed the website as.
+    pub warwick_id: i32,
+    /// Either "start" or "stop".
+    pub action: String,
+    /// When it happened.
+    pub timestamp: custom_types::DateTime,
+}
+
+impl ImpersonationEntry {
+    /// Inserts a new entry into the log, timestamped with the current time.
+    pub async fn insert(
+        admin_id: i32,
+        warwick_id: i32,
+        action: &str,
+        pool: &mut Pool,
+    ) -> sqlx::Result<()> {
+        let timestamp = chrono::Utc::now().timestamp();
+
+        tracing::info!(%admin_id, %warwick_id, %action, "Recording a change in impersonation");
+
+        sqlx::query!(
+            "INSERT INTO impersonation_log (admin_id, warwick_id, action, timestamp) VALUES ($1, $2, $3, $4)",
+            admin_id,
+            warwick_id,
+            action,
+            timestamp
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets all [`ImpersonationEntry`] entries in the database, most recent first.
+    pub async fn get_results(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                admin_id,
+                warwick_id,
+                action,
+                timestamp AS "timestamp: custom_types::DateTime"
+            FROM impersonation_log
+            ORDER BY timestamp DESC, id DESC
+            "#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Finds the name of a user from their registrations or personal bests, if they have any.
+    ///
+    /// Personal bests are preferred, then the registration for their most recent session.
+    pub async fn find_name(warwick_id: i32, pool: &mut Pool) -> sqlx::Result<Option<String>> {
+        sqlx::query!(
+            r#"
+            SELECT name AS "name!", 0 AS "priority!", 0::BIGINT AS "start_time!"
+            FROM personal_bests WHERE warwick_id = $1
+            UNION ALL
+            SELECT r.name, 1, s.start_time
+            FROM registrations r
+            INNER JOIN sessions s ON s.id = r.session_id
+            WHERE r.warwick_id = $1
+            ORDER BY 2, 3 DESC, 1
+            LIMIT 1
+            "#,
+            warwick_id
+        )
+        .map(|row| row.name)
+        .fetch_optional(pool)
+        .await
+    }
+}
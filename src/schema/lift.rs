@@ -0,0 +1,385 @@
+//! Allows modifications of the `lifts` table in the database.
+
+use serde::Serialize;
+
+use crate::forms;
+use crate::schema::{custom_types, Pool};
+
+/// The board showing powerlifting totals and scores.
+pub const POWERLIFTING: &str = "Powerlifting";
+
+/// The board showing weightlifting totals and Sinclair scores.
+pub const WEIGHTLIFTING: &str = "Weightlifting";
+
+/// Represents a row in the `lifts` table.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Lift {
+    /// The name the lift is recorded under.
+    pub name: String,
+    /// The name to display for the lift.
+    pub display_name: String,
+    /// The abbreviation used in the column headers of the boards.
+    pub abbreviation: String,
+    /// The board the lift is shown on.
+    pub board: String,
+    /// Where the lift appears relative to the others.
+    pub position: i32,
+}
+
+/// A lifter's heaviest single for a lift shown on one of the additional boards.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BoardBest {
+    /// The lifter's Warwick ID.
+    pub warwick_id: i32,
+    /// The lifter's name.
+    pub name: String,
+    /// The lift that was performed.
+    pub lift: String,
+    /// The heaviest single the lifter has recorded.
+    pub weight: f32,
+}
+
+/// A lifter on one of the additional boards.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BoardLifter {
+    /// The lifter's position on the board, if they have recorded the board's first lift
+    pub rank: Option<usize>,
+    /// The lifter's Warwick ID
+    pub warwick_id: i32,
+    /// The lifter's name
+    pub name: String,
+    /// The lifter's best for each of the board's lifts, in order
+    pub bests: Vec<Option<f32>>,
+}
+
+/// A board made up of lifts from the catalogue, other than powerlifting and weightlifting.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Board {
+    /// The name of the board
+    pub name: String,
+    /// The lifts shown on the board, in order
+    pub lifts: Vec<Lift>,
+    /// The lifters who have recorded any of the lifts, ranked by the first
+    pub lifters: Vec<BoardLifter>,
+}
+
+impl Lift {
+    /// Creates a new [`Lift`] for the given board, shown before any others with the same
+    /// position.
+    pub fn new(name: &str, display_name: &str, abbreviation: &str, board: &str) -> Self {
+        Self {
+            name: String::from(name),
+            display_name: String::from(display_name),
+            abbreviation: String::from(abbreviation),
+            board: String::from(board),
+            position: 0,
+        }
+    }
+
+    /// Checks whether the lift is shown on one of the boards with scores, whose totals depend on
+    /// their lifts.
+    pub fn is_scored(&self) -> bool {
+        self.board == POWERLIFTING || self.board == WEIGHTLIFTING
+    }
+
+    /// Gets all [`Lift`] entries in the database, in the order they are displayed.
+    pub async fn get_results(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(Self, "SELECT * FROM lifts ORDER BY position, name")
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Finds a lift in the catalogue by its name.
+    pub async fn find(name: &str, pool: &mut Pool) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(Self, "SELECT * FROM lifts WHERE name = $1", name)
+            .fetch_optional(pool)
+            .await
+    }
+
+    /// Inserts the [`Lift`] into the catalogue.
+    pub async fn insert(&self, pool: &mut Pool) -> sqlx::Result<()> {
+        tracing::info!(name = %self.name, board = %self.board, "Adding a lift to the catalogue");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO lifts (name, display_name, abbreviation, board, position)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            self.name,
+            self.display_name,
+            self.abbreviation,
+            self.board,
+            self.position
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Updates how a lift in the catalogue is displayed, returning whether it was changed.
+    ///
+    /// Lifts are never moved onto or off the boards with scores, since that would change the
+    /// totals of anyone who has recorded them.
+    pub async fn update(&self, pool: &mut Pool) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE lifts
+            SET display_name = $2, abbreviation = $3, board = $4, position = $5
+            WHERE name = $1
+            AND (board = $4 OR (board NOT IN ($6, $7) AND $4 NOT IN ($6, $7)))
+            "#,
+            self.name,
+            self.display_name,
+            self.abbreviation,
+            self.board,
+            self.position,
+            POWERLIFTING,
+            WEIGHTLIFTING
+        )
+        .execute(pool)
+        .await?;
+
+        let updated = result.rows_affected() != 0;
+
+        if updated {
+            tracing::info!(name = %self.name, board = %self.board, "Updated a lift in the catalogue");
+        }
+
+        Ok(updated)
+    }
+
+    /// Removes a lift from the catalogue, returning whether it was removed.
+    ///
+    /// Lifts that have been recorded by anyone, or that are shown on the boards with scores, are
+    /// kept.
+    pub async fn delete(name: &str, pool: &mut Pool) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM lifts
+            WHERE name = $1
+            AND board NOT IN ($2, $3)
+            AND NOT EXISTS (SELECT 1 FROM lift_records WHERE lift = $1)
+            "#,
+            name,
+            POWERLIFTING,
+            WEIGHTLIFTING
+        )
+        .execute(pool)
+        .await?;
+
+        let deleted = result.rows_affected() != 0;
+
+        if deleted {
+            tracing::info!(%name, "Removed a lift from the catalogue");
+        }
+
+        Ok(deleted)
+    }
+}
+
+impl BoardBest {
+    /// Gets the heaviest single each lifter has recorded for the lifts on the additional boards,
+    /// ignoring rejected lifts.
+    ///
+    /// Only lifters who have chosen to be shown on the additional boards are included, along with
+    /// the given category and weight class if either are given.
+    pub async fn get_results(
+        query: &forms::BoardQuery,
+        pool: &mut Pool,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT pb.warwick_id, pb.name, lr.lift, MAX(lr.weight) AS "weight!"
+            FROM lift_records lr
+            INNER JOIN lifts l ON l.name = lr.lift
+            INNER JOIN personal_bests pb ON pb.warwick_id = lr.warwick_id
+            WHERE l.board NOT IN ($1, $2)
+            AND pb.show_other
+            AND (lr.reps IS NULL OR lr.reps = 1)
+            AND lr.status <> 'rejected'
+            AND ($3::TEXT IS NULL OR pb.sex = $3)
+            AND ($4::TEXT IS NULL OR pb.weight_class = $4)
+            GROUP BY pb.warwick_id, pb.name, lr.lift
+            "#,
+            POWERLIFTING,
+            WEIGHTLIFTING,
+            query.sex as _,
+            query.class()
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
+
+impl Board {
+    /// Converts the lifters' bests from kilograms into the given unit, for displaying them.
+    pub fn in_unit(mut self, unit: custom_types::Unit) -> Self {
+        for lifter in &mut self.lifters {
+            for best in &mut lifter.bests {
+                *best = best.map(|kg| unit.from_kg(kg));
+            }
+        }
+
+        self
+    }
+}
+
+/// Gets the lifts shown on the given board, in the order they are displayed.
+pub fn on_board(lifts: &[Lift], board: &str) -> Vec<Lift> {
+    lifts
+        .iter()
+        .filter(|lift| lift.board == board)
+        .cloned()
+        .collect()
+}
+
+/// Builds the additional boards from the catalogue and the lifters' bests.
+///
+/// Each board other than powerlifting and weightlifting appears in the order of its first lift.
+/// Lifters are ranked by the board's first lift and then by each of the others, with lifters who
+/// have not recorded the first lift placed last without a rank.
+pub fn boards(lifts: &[Lift], bests: &[BoardBest]) -> Vec<Board> {
+    let mut boards: Vec<Board> = Vec::new();
+
+    for lift in lifts.iter().filter(|lift| !lift.is_scored()) {
+        match boards.iter_mut().find(|board| board.name == lift.board) {
+            Some(board) => board.lifts.push(lift.clone()),
+            None => boards.push(Board {
+                name: lift.board.clone(),
+                lifts: vec![lift.clone()],
+                lifters: Vec::new(),
+            }),
+        }
+    }
+
+    for board in &mut boards {
+        for best in bests {
+            let index = match board.lifts.iter().position(|lift| lift.name == best.lift) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let position = board
+                .lifters
+                .iter()
+                .position(|lifter| lifter.warwick_id == best.warwick_id);
+
+            let lifter = match position {
+                Some(position) => &mut board.lifters[position],
+                None => {
+                    board.lifters.push(BoardLifter {
+                        rank: None,
+                        warwick_id: best.warwick_id,
+                        name: best.name.clone(),
+                        bests: vec![None; board.lifts.len()],
+                    });
+
+                    board.lifters.last_mut().unwrap()
+                }
+            };
+
+            lifter.bests[index] = Some(best.weight);
+        }
+
+        // Missing bests sort below any recorded ones, and ties keep a consistent order
+        board.lifters.sort_by(|a, b| {
+            b.bests
+                .partial_cmp(&a.bests)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(a.warwick_id.cmp(&b.warwick_id))
+        });
+
+        let mut previous: Option<(f32, usize)> = None;
+
+        for (index, lifter) in board.lifters.iter_mut().enumerate() {
+            lifter.rank = lifter.bests[0].map(|value| match previous {
+                Some((last, rank)) if last == value => rank,
+                _ => {
+                    previous = Some((value, index + 1));
+                    index + 1
+                }
+            });
+        }
+    }
+
+    boards
+}
+
+/// The lifts the catalogue starts with, for the tests of anything built from it.
+#[cfg(test)]
+pub(crate) fn original_lifts() -> Vec<Lift> {
+    vec![
+        Lift::new("squat", "Squat", "SQ", POWERLIFTING),
+        Lift::new("bench", "Bench", "BP", POWERLIFTING),
+        Lift::new("deadlift", "Deadlift", "DL", POWERLIFTING),
+        Lift::new("snatch", "Snatch", "SN", WEIGHTLIFTING),
+        Lift::new("clean_and_jerk", "Clean and Jerk", "CJ", WEIGHTLIFTING),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lift(name: &str, board: &str) -> Lift {
+        Lift::new(name, name, name, board)
+    }
+
+    fn best(warwick_id: i32, lift: &str, weight: f32) -> BoardBest {
+        BoardBest {
+            warwick_id,
+            name: format!("Lifter {}", warwick_id),
+            lift: String::from(lift),
+            weight,
+        }
+    }
+
+    #[test]
+    fn scored_boards_are_not_built() {
+        let lifts = vec![lift("squat", POWERLIFTING), lift("snatch", WEIGHTLIFTING)];
+
+        assert!(boards(&lifts, &[]).is_empty());
+    }
+
+    #[test]
+    fn lifts_are_grouped_by_board_in_order() {
+        let lifts = vec![
+            lift("log_press", "Strongman"),
+            lift("pull_up", "Accessories"),
+            lift("yoke", "Strongman"),
+        ];
+
+        let boards = boards(&lifts, &[]);
+        let names: Vec<_> = boards.iter().map(|board| board.name.as_str()).collect();
+
+        assert_eq!(names, vec!["Strongman", "Accessories"]);
+        assert_eq!(boards[0].lifts.len(), 2);
+    }
+
+    #[test]
+    fn lifters_are_ranked_by_the_first_lift() {
+        let lifts = vec![lift("log_press", "Strongman"), lift("yoke", "Strongman")];
+        let bests = vec![
+            best(1, "log_press", 100.0),
+            best(2, "log_press", 120.0),
+            best(2, "yoke", 250.0),
+            best(3, "yoke", 300.0),
+            best(4, "log_press", 100.0),
+        ];
+
+        let board = &boards(&lifts, &bests)[0];
+        let lifters: Vec<_> = board
+            .lifters
+            .iter()
+            .map(|lifter| (lifter.warwick_id, lifter.rank))
+            .collect();
+
+        assert_eq!(
+            lifters,
+            vec![(2, Some(1)), (1, Some(2)), (4, Some(2)), (3, None)]
+        );
+        assert_eq!(board.lifters[0].bests, vec![Some(120.0), Some(250.0)]);
+    }
+}
@@ -0,0 +1,398 @@
+//! Allows modifications of the `lift_records` table in the database.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::schema::{custom_types, Pool};
+
+/// Represents a row in the `lift_records` table.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LiftRecord {
+    /// The identifier for the record.
+    pub id: i32,
+    /// The user's Warwick ID.
+    pub warwick_id: i32,
+    /// The lift that was performed.
+    pub lift: String,
+    /// The weight lifted.
+    pub weight: f32,
+    /// The number of repetitions, if more than a single.
+    pub reps: Option<i32>,
+    /// How hard the set was on the RPE scale, if the user recorded it.
+    pub rpe: Option<f32>,
+    /// The day the lift was performed.
+    pub recorded_on: custom_types::Date,
+    /// Any note the user added.
+    pub note: Option<String>,
+    /// A link to a video of the lift, if the user provided one.
+    pub video_url: Option<String>,
+    /// Whether the lift has been reviewed by a coach.
+    pub status: custom_types::LiftStatus,
+}
+
+/// A lift waiting to be reviewed by a coach, along with the name of the user who recorded it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PendingLift {
+    /// The identifier for the record.
+    pub id: i32,
+    /// The user's Warwick ID.
+    pub warwick_id: i32,
+    /// The user's name, if they have appeared on the boards.
+    pub name: Option<String>,
+    /// The lift that was performed.
+    pub lift: String,
+    /// The weight lifted.
+    pub weight: f32,
+    /// The number of repetitions, if more than a single.
+    pub reps: Option<i32>,
+    /// The day the lift was performed.
+    pub recorded_on: custom_types::Date,
+    /// Any note the user added.
+    pub note: Option<String>,
+    /// A link to a video of the lift, if the user provided one.
+    pub video_url: Option<String>,
+}
+
+impl PendingLift {
+    /// Converts the weight from kilograms into the given unit, for displaying it.
+    pub fn in_unit(self, unit: custom_types::Unit) -> Self {
+        Self {
+            weight: unit.from_kg(self.weight),
+            ..self
+        }
+    }
+}
+
+/// The heaviest set a user has recorded of a lift for a number of reps.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RepMax {
+    /// The user's Warwick ID.
+    pub warwick_id: i32,
+    /// The lift that was performed.
+    pub lift: String,
+    /// The number of repetitions.
+    pub reps: i32,
+    /// The weight lifted.
+    pub weight: f32,
+    /// How hard the set was on the RPE scale, if the user recorded it.
+    pub rpe: Option<f32>,
+}
+
+/// A point on the progression chart for a lift.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ProgressionPoint {
+    /// The day, formatted as `YYYY-MM-DD`.
+    pub x: String,
+    /// The user's best single for the lift at that point.
+    pub y: f32,
+}
+
+impl LiftRecord {
+    /// Creates a new pending [`LiftRecord`] for a single, without a note or video.
+    pub fn single(
+        warwick_id: i32,
+        lift: &str,
+        weight: f32,
+        recorded_on: custom_types::Date,
+    ) -> Self {
+        Self {
+            id: 0,
+            warwick_id,
+            lift: String::from(lift),
+            weight,
+            reps: None,
+            rpe: None,
+            recorded_on,
+            note: None,
+            video_url: None,
+            status: custom_types::LiftStatus::Pending,
+        }
+    }
+
+    /// Converts the weight from kilograms into the given unit, for displaying it.
+    pub fn in_unit(self, unit: custom_types::Unit) -> Self {
+        Self {
+            weight: unit.from_kg(self.weight),
+            ..self
+        }
+    }
+
+    /// Checks whether the record counts towards the user's personal best for the lift.
+    pub fn is_single(&self) -> bool {
+        matches!(self.reps, None | Some(1))
+    }
+
+    /// Inserts the [`LiftRecord`] into the database, ignoring the identifier.
+    ///
+    /// Lifts are always recorded as pending, regardless of their status, until a coach reviews
+    /// them. The category and weight class the user was in on the day the lift was performed are
+    /// kept with it, for the club records.
+    pub async fn insert(&self, pool: &mut Pool) -> sqlx::Result<()> {
+        tracing::info!(warwick_id = %self.warwick_id, lift = %self.lift, weight = %self.weight, "Recording a lift for a user");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO lift_records
+            (warwick_id, lift, weight, reps, rpe, recorded_on, note, video_url, sex, weight_class)
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8,
+                (
+                    SELECT sex FROM category_changes
+                    WHERE warwick_id = $1 AND changed_on <= $6
+                    ORDER BY changed_on DESC, id DESC
+                    LIMIT 1
+                ),
+                (
+                    SELECT weight_class FROM category_changes
+                    WHERE warwick_id = $1 AND changed_on <= $6
+                    ORDER BY changed_on DESC, id DESC
+                    LIMIT 1
+                )
+            )
+            "#,
+            self.warwick_id,
+            self.lift,
+            self.weight,
+            self.reps,
+            self.rpe,
+            self.recorded_on.0,
+            self.note,
+            self.video_url
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets all the lifts a user has recorded, oldest first.
+    pub async fn for_user(warwick_id: i32, pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                id,
+                warwick_id,
+                lift,
+                weight,
+                reps,
+                rpe,
+                recorded_on AS "recorded_on: custom_types::Date",
+                note,
+                video_url,
+                status AS "status: custom_types::LiftStatus"
+            FROM lift_records
+            WHERE warwick_id = $1
+            ORDER BY recorded_on, id
+            "#,
+            warwick_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Gets the heaviest set of each lift for every number of reps up to 10, ignoring rejected
+    /// lifts.
+    ///
+    /// These are fetched for a single user if one is given, and for every user otherwise. Sets at
+    /// the same weight are broken by the lowest RPE, as these suggest the highest one rep max.
+    pub async fn rep_maxes(warwick_id: Option<i32>, pool: &mut Pool) -> sqlx::Result<Vec<RepMax>> {
+        sqlx::query_as!(
+            RepMax,
+            r#"
+            SELECT DISTINCT ON (warwick_id, lift, COALESCE(reps, 1))
+                warwick_id,
+                lift,
+                COALESCE(reps, 1) AS "reps!",
+                weight,
+                rpe
+            FROM lift_records
+            WHERE status <> 'rejected'
+            AND COALESCE(reps, 1) <= 10
+            AND ($1::INT IS NULL OR warwick_id = $1)
+            ORDER BY warwick_id, lift, COALESCE(reps, 1), weight DESC, COALESCE(rpe, 10)
+            "#,
+            warwick_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Deletes one of a user's records, such as if they made a mistake.
+    pub async fn delete(id: i32, warwick_id: i32, pool: &mut Pool) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM lift_records WHERE id = $1 AND warwick_id = $2",
+            id,
+            warwick_id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() != 0)
+    }
+
+    /// Gets the lifts waiting to be reviewed, oldest first.
+    pub async fn pending(pool: &mut Pool) -> sqlx::Result<Vec<PendingLift>> {
+        sqlx::query_as!(
+            PendingLift,
+            r#"
+            SELECT
+                lr.id,
+                lr.warwick_id,
+                pb.name AS "name?",
+                lr.lift,
+                lr.weight,
+                lr.reps,
+                lr.recorded_on AS "recorded_on: custom_types::Date",
+                lr.note,
+                lr.video_url
+            FROM lift_records lr
+            LEFT JOIN personal_bests pb ON pb.warwick_id = lr.warwick_id
+            WHERE lr.status = 'pending'
+            ORDER BY lr.recorded_on, lr.id
+            "#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Marks a pending lift as verified or rejected, returning whether it was pending.
+    ///
+    /// Coaches cannot review their own lifts.
+    pub async fn review(
+        id: i32,
+        reviewer_id: i32,
+        status: custom_types::LiftStatus,
+        pool: &mut Pool,
+    ) -> sqlx::Result<bool> {
+        let reviewed_at = chrono::Utc::now().timestamp();
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE lift_records
+            SET status = $1, reviewed_by = $2, reviewed_at = $3
+            WHERE id = $4 AND status = 'pending' AND warwick_id <> $2
+            "#,
+            status as _,
+            reviewer_id,
+            reviewed_at,
+            id
+        )
+        .execute(pool)
+        .await?;
+
+        let reviewed = result.rows_affected() != 0;
+
+        if reviewed {
+            tracing::info!(%id, %reviewer_id, %status, "Reviewed a recorded lift");
+        }
+
+        Ok(reviewed)
+    }
+}
+
+/// Calculates how a user's best single for each lift has progressed over time.
+///
+/// Each lift has a point for every day its best single improved, ignoring rejected lifts. The
+/// records are expected to be sorted by the day they were performed, as returned by
+/// [`LiftRecord::for_user`].
+pub fn progression(records: &[LiftRecord]) -> BTreeMap<String, Vec<ProgressionPoint>> {
+    let mut progression: BTreeMap<String, Vec<ProgressionPoint>> = BTreeMap::new();
+
+    let counted = records
+        .iter()
+        .filter(|record| record.is_single() && record.status != custom_types::LiftStatus::Rejected);
+
+    for record in counted {
+        let points = progression.entry(record.lift.clone()).or_default();
+        let x = record.recorded_on.0.format("%Y-%m-%d").to_string();
+
+        match points.last_mut() {
+            Some(last) if last.y >= record.weight => continue,
+            Some(last) if last.x == x => last.y = record.weight,
+            _ => points.push(ProgressionPoint {
+                x,
+                y: record.weight,
+            }),
+        }
+    }
+
+    progression
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn record(lift: &str, weight: f32, reps: Option<i32>, day: u32) -> LiftRecord {
+        let day = custom_types::Date(NaiveDate::from_ymd_opt(2022, 10, day).unwrap());
+
+        LiftRecord {
+            reps,
+            ..LiftRecord::single(1702502, lift, weight, day)
+        }
+    }
+
+    fn point(day: &str, y: f32) -> ProgressionPoint {
+        ProgressionPoint {
+            x: format!("2022-10-{}", day),
+            y,
+        }
+    }
+
+    #[test]
+    fn progression_only_includes_improvements() {
+        let records = vec![
+            record("squat", 100.0, None, 1),
+            record("squat", 95.0, None, 2),
+            record("squat", 105.0, Some(1), 3),
+        ];
+
+        let progression = progression(&records);
+
+        assert_eq!(
+            progression["squat"],
+            vec![point("01", 100.0), point("03", 105.0)]
+        );
+    }
+
+    #[test]
+    fn progression_ignores_sets_of_multiple_reps() {
+        let records = vec![
+            record("bench", 80.0, None, 1),
+            record("bench", 90.0, Some(5), 2),
+        ];
+
+        let progression = progression(&records);
+
+        assert_eq!(progression["bench"], vec![point("01", 80.0)]);
+    }
+
+    #[test]
+    fn progression_keeps_the_best_single_on_each_day() {
+        let records = vec![
+            record("deadlift", 150.0, None, 1),
+            record("deadlift", 160.0, None, 1),
+            record("snatch", 60.0, None, 2),
+        ];
+
+        let progression = progression(&records);
+
+        assert_eq!(progression["deadlift"], vec![point("01", 160.0)]);
+        assert_eq!(progression["snatch"], vec![point("02", 60.0)]);
+    }
+
+    #[test]
+    fn progression_ignores_rejected_lifts() {
+        let mut rejected = record("squat", 200.0, None, 2);
+        rejected.status = custom_types::LiftStatus::Rejected;
+
+        let records = vec![record("squat", 100.0, None, 1), rejected];
+        let progression = progression(&records);
+
+        assert_eq!(progression["squat"], vec![point("01", 100.0)]);
+    }
+}
@@ -0,0 +1,218 @@
+//! Allows modifications of the `memberships` table in the database.
+
+use std::collections::HashSet;
+
+use chrono::{Datelike, NaiveDate};
+use serde::Serialize;
+use sqlx::PgConnection;
+
+use crate::schema::{custom_types, Pool};
+use crate::su_export::ExportedMember;
+
+/// Represents a row in the `memberships` table.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Membership {
+    /// The user's Warwick ID.
+    pub warwick_id: i32,
+    /// The type of membership they hold.
+    pub membership_type: String,
+    /// The first day the membership is valid.
+    pub valid_from: custom_types::Date,
+    /// The last day the membership is valid.
+    pub valid_to: custom_types::Date,
+    /// Where the membership came from, such as the students' union export.
+    pub source: String,
+}
+
+/// Gets the last day of the academic year a date falls in, which ends on the 31st of July.
+pub fn academic_year_end(date: NaiveDate) -> NaiveDate {
+    let end = NaiveDate::from_ymd_opt(date.year(), 7, 31).unwrap();
+
+    if date <= end {
+        end
+    } else {
+        NaiveDate::from_ymd_opt(date.year() + 1, 7, 31).unwrap()
+    }
+}
+
+/// The differences between the current members and those in an import.
+#[derive(Debug, Default, Eq, PartialEq, Serialize)]
+pub struct MembershipDiff {
+    /// Members in the import who are not currently members.
+    pub joining: Vec<i32>,
+    /// Current members who are also in the import.
+    pub continuing: Vec<i32>,
+    /// Current members who are not in the import, so will lapse when their membership expires.
+    pub lapsing: Vec<i32>,
+}
+
+impl MembershipDiff {
+    /// Compares the current memberships against the members in an import.
+    pub fn between(current: &[Membership], imported: &[ExportedMember]) -> Self {
+        let current: HashSet<i32> = current.iter().map(|m| m.warwick_id).collect();
+        let imported: HashSet<i32> = imported.iter().map(|m| m.warwick_id).collect();
+
+        let sorted = |ids: HashSet<&i32>| {
+            let mut ids: Vec<i32> = ids.into_iter().copied().collect();
+            ids.sort_unstable();
+            ids
+        };
+
+        Self {
+            joining: sorted(imported.difference(&current).collect()),
+            continuing: sorted(imported.intersection(&current).collect()),
+            lapsing: sorted(current.difference(&imported).collect()),
+        }
+    }
+}
+
+impl Membership {
+    /// Inserts the [`Membership`] into the database, updating it if it already exists.
+    pub async fn insert(&self, conn: &mut PgConnection) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO memberships (warwick_id, membership_type, valid_from, valid_to, source)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (warwick_id, membership_type, valid_from)
+            DO UPDATE SET valid_to = EXCLUDED.valid_to, source = EXCLUDED.source
+            "#,
+            self.warwick_id,
+            self.membership_type,
+            self.valid_from.0,
+            self.valid_to.0,
+            self.source
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets all [`Membership`] entries in the database, those expiring last first.
+    pub async fn get_results(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                warwick_id,
+                membership_type,
+                valid_from AS "valid_from: custom_types::Date",
+                valid_to AS "valid_to: custom_types::Date",
+                source
+            FROM memberships
+            ORDER BY valid_to DESC, warwick_id
+            "#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Gets the memberships that are valid today.
+    pub async fn current(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                warwick_id,
+                membership_type,
+                valid_from AS "valid_from: custom_types::Date",
+                valid_to AS "valid_to: custom_types::Date",
+                source
+            FROM memberships
+            WHERE valid_from <= CURRENT_DATE AND CURRENT_DATE <= valid_to
+            ORDER BY warwick_id
+            "#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Records a membership for everyone in an import, all valid for the same period.
+    ///
+    /// Either every membership is recorded or none are, so a failure can't leave a partial import.
+    pub async fn import(
+        members: &[ExportedMember],
+        valid_from: custom_types::Date,
+        valid_to: custom_types::Date,
+        source: &str,
+        pool: &mut Pool,
+    ) -> sqlx::Result<()> {
+        tracing::info!(count = %members.len(), %valid_from, %valid_to, %source, "Importing memberships");
+
+        let mut transaction = pool.begin().await?;
+
+        // `sqlx` doesn't support multiple entries, so iterate instead
+        for member in members {
+            let membership = Self {
+                warwick_id: member.warwick_id,
+                membership_type: member.membership_type.clone(),
+                valid_from,
+                valid_to,
+                source: String::from(source),
+            };
+
+            membership.insert(&mut *transaction).await?;
+        }
+
+        transaction.commit().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn membership(warwick_id: i32) -> Membership {
+        let date = custom_types::Date(NaiveDate::from_ymd_opt(2022, 10, 1).unwrap());
+
+        Membership {
+            warwick_id,
+            membership_type: String::from("Standard"),
+            valid_from: date,
+            valid_to: date,
+            source: String::from("test"),
+        }
+    }
+
+    fn exported(warwick_id: i32) -> ExportedMember {
+        ExportedMember {
+            warwick_id,
+            membership_type: String::from("Standard"),
+        }
+    }
+
+    #[test]
+    fn academic_years_end_in_july() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        assert_eq!(academic_year_end(date(2022, 10, 1)), date(2023, 7, 31));
+        assert_eq!(academic_year_end(date(2023, 3, 14)), date(2023, 7, 31));
+        assert_eq!(academic_year_end(date(2023, 7, 31)), date(2023, 7, 31));
+        assert_eq!(academic_year_end(date(2023, 8, 1)), date(2024, 7, 31));
+    }
+
+    #[test]
+    fn imports_are_compared_against_current_members() {
+        let current = vec![membership(1), membership(2), membership(3)];
+        let imported = vec![exported(4), exported(2), exported(1)];
+
+        let diff = MembershipDiff::between(&current, &imported);
+        let expected = MembershipDiff {
+            joining: vec![4],
+            continuing: vec![1, 2],
+            lapsing: vec![3],
+        };
+
+        assert_eq!(diff, expected);
+    }
+
+    #[test]
+    fn imports_with_no_current_members_are_all_joining() {
+        let imported = vec![exported(2), exported(1)];
+        let diff = MembershipDiff::between(&[], &imported);
+
+        assert_eq!(diff.joining, vec![1, 2]);
+        assert!(diff.continuing.is_empty());
+        assert!(diff.lapsing.is_empty());
+    }
+}
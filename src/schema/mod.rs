@@ -2,24 +2,44 @@
 
 use sqlx::{pool::PoolConnection, Postgres};
 
+pub mod api_token;
 pub mod attendance;
 pub mod auth_pair;
+pub mod bodyweight;
 pub mod candidate;
+pub mod club_record;
 pub mod custom_types;
+pub mod election;
 pub mod exec_position;
+pub mod impersonation;
+pub mod lift;
+pub mod lift_record;
+pub mod membership;
 pub mod nomination;
 pub mod personal_best;
+pub mod preference;
 pub mod registration;
+pub mod role;
 pub mod session;
 pub mod vote;
 
+pub use api_token::ApiToken;
 pub use attendance::Attendance;
 pub use auth_pair::AuthPair;
+pub use bodyweight::Bodyweight;
 pub use candidate::Candidate;
+pub use club_record::ClubRecord;
+pub use election::Election;
 pub use exec_position::ExecPosition;
-pub use nomination::Nomination;
+pub use impersonation::ImpersonationEntry;
+pub use lift::Lift;
+pub use lift_record::LiftRecord;
+pub use membership::Membership;
+pub use nomination::{NamedNomination, Nomination};
 pub use personal_best::PersonalBest;
+pub use preference::Preferences;
 pub use registration::Registration;
+pub use role::{Role, RoleAuditEntry, UserRole};
 pub use session::Session;
 pub use vote::Vote;
 
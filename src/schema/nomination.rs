@@ -2,6 +2,7 @@
 
 use serde::Serialize;
 
+use crate::schema::exec_position::{RON_ID, RON_NAME};
 use crate::schema::Pool;
 
 /// Represents a row in the `nominations` table.
@@ -22,28 +23,102 @@ pub struct NamedNominationForPosition {
     pub name: String,
 }
 
+/// A nomination along with the name of the candidate.
+#[derive(Clone, Debug, Serialize)]
+pub struct NamedNomination {
+    /// The identifier of the exec position.
+    pub position_id: i32,
+    /// The identifier of the candidate.
+    pub warwick_id: i32,
+    /// The name of the candidate.
+    pub name: String,
+}
+
 impl Nomination {
-    /// Inserts the [`Nomination`] into the database.
-    pub async fn insert(&self, pool: &mut Pool) -> sqlx::Result<()> {
-        sqlx::query!(
-            "INSERT INTO nominations (position_id, warwick_id) VALUES ($1, $2)",
+    /// Inserts the [`Nomination`] into the election of its position, returning whether it was
+    /// added.
+    ///
+    /// Nominations are only added to positions in the current election that are not open for
+    /// voting and have not been voted for, so that every ballot is cast for the same candidates.
+    pub async fn insert(&self, pool: &mut Pool) -> sqlx::Result<bool> {
+        let now = chrono::Utc::now().timestamp();
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO nominations (position_id, warwick_id, election_id)
+            SELECT p.id, $2, p.election_id
+            FROM exec_positions p
+            INNER JOIN elections e ON e.id = p.election_id
+            WHERE p.id = $1 AND e.status = 'current'
+            AND NOT ((p.open OR COALESCE(p.opens_at <= $3, FALSE)) AND NOT COALESCE(p.closes_at <= $3, FALSE))
+            AND NOT EXISTS (SELECT 1 FROM votes WHERE position_id = $1)
+            "#,
             self.position_id,
-            self.warwick_id
+            self.warwick_id,
+            now
         )
         .execute(pool)
         .await?;
 
-        Ok(())
+        Ok(result.rows_affected() != 0)
     }
 
     /// Gets all [`Nomination`] entries in the database.
     pub async fn get_results(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
-        sqlx::query_as!(Self, "SELECT * FROM nominations")
+        sqlx::query_as!(Self, "SELECT position_id, warwick_id FROM nominations")
             .fetch_all(pool)
             .await
     }
 
-    /// Gets all the [`Nomination`] entries for a position identifier.
+    /// Gets all the [`Nomination`] entries in an election along with the names of the
+    /// candidates.
+    pub async fn for_election_with_names(
+        election_id: i32,
+        pool: &mut Pool,
+    ) -> sqlx::Result<Vec<NamedNomination>> {
+        sqlx::query_as!(
+            NamedNomination,
+            r#"
+            SELECT n.position_id, n.warwick_id, c.name
+            FROM nominations n
+            INNER JOIN candidates c ON n.election_id = c.election_id AND n.warwick_id = c.warwick_id
+            WHERE n.election_id = $1
+            ORDER BY n.position_id, c.name
+            "#,
+            election_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Withdraws a nomination in the current election, returning whether it was withdrawn.
+    ///
+    /// Nominations that have received votes are kept, so that no ballots are changed.
+    pub async fn delete(&self, pool: &mut Pool) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM nominations
+            WHERE position_id = $1 AND warwick_id = $2
+            AND election_id IN (SELECT id FROM elections WHERE status = 'current')
+            AND NOT EXISTS (SELECT 1 FROM votes WHERE position_id = $1 AND candidate_id = $2)
+            "#,
+            self.position_id,
+            self.warwick_id
+        )
+        .execute(pool)
+        .await?;
+
+        let deleted = result.rows_affected() != 0;
+
+        if deleted {
+            tracing::info!(position_id = %self.position_id, warwick_id = %self.warwick_id, "Withdrew a nomination");
+        }
+
+        Ok(deleted)
+    }
+
+    /// Gets all the [`Nomination`] entries for a position identifier, along with the built-in
+    /// candidate for re-opening nominations if the position offers it.
     pub async fn for_position_with_names(
         position_id: i32,
         pool: &mut Pool,
@@ -51,12 +126,16 @@ impl Nomination {
         sqlx::query_as!(
             NamedNominationForPosition,
             r#"
-                SELECT n.warwick_id AS warwick_id, name
+                SELECT n.warwick_id AS "warwick_id!", name AS "name!"
                 FROM nominations n
-                INNER JOIN candidates c ON n.warwick_id = c.warwick_id
+                INNER JOIN candidates c ON n.election_id = c.election_id AND n.warwick_id = c.warwick_id
                 WHERE c.elected IS false AND n.position_id = $1
+                UNION ALL
+                SELECT $2, $3 FROM exec_positions WHERE id = $1 AND ron
             "#,
-            position_id
+            position_id,
+            RON_ID,
+            RON_NAME
         )
         .fetch_all(pool)
         .await
@@ -1,31 +1,70 @@
 //! Allows modifications of the `personal_bests` table in the database.
+//!
+//! The table only stores how each user appears on the boards. The `personal_best_board` view adds
+//! their latest bodyweight and verified lifts, and their personal bests are the heaviest single
+//! they have recorded for each lift in the catalogue.
+
+use std::collections::{BTreeMap, HashMap};
 
 use serde::Serialize;
 
 use crate::forms;
-use crate::schema::Pool;
+use crate::schema::lift::{POWERLIFTING, WEIGHTLIFTING};
+use crate::schema::{custom_types, Bodyweight, Lift, LiftRecord, Pool};
 
-/// Represents a row in the `personal_bests` table.
+/// Represents a row in the `personal_best_board` view, along with the user's best lifts.
 #[derive(Clone, Debug, Default, PartialEq, Serialize)]
 pub struct PersonalBest {
     /// The user's Warwick ID
     pub warwick_id: i32,
     /// The user's name
     pub name: String,
-    /// The user's best squat.
-    pub squat: Option<f32>,
-    /// The user's best bench.
-    pub bench: Option<f32>,
-    /// The user's best deadlift.
-    pub deadlift: Option<f32>,
-    /// The user's best snatch.
-    pub snatch: Option<f32>,
-    /// The user's best clean and jerk.
-    pub clean_and_jerk: Option<f32>,
+    /// The user's best single for each lift they have recorded, by the name of the lift.
+    pub bests: BTreeMap<String, f32>,
     /// Whether to show the user for the PL board.
     pub show_pl: bool,
     /// Whether to show the user for the WL board.
     pub show_wl: bool,
+    /// Whether to show the user on the boards for the other lifts in the catalogue.
+    pub show_other: bool,
+    /// The user's most recently recorded bodyweight.
+    pub bodyweight: Option<f32>,
+    /// The category the user competes in.
+    pub sex: Option<custom_types::Sex>,
+    /// The weight class the user competes in.
+    pub weight_class: Option<String>,
+    /// The lifts whose best has been verified by a coach.
+    pub verified: Vec<String>,
+}
+
+/// Represents a row in the `personal_best_board` view, before the user's bests are added.
+struct BoardRow {
+    warwick_id: i32,
+    name: String,
+    show_pl: bool,
+    show_wl: bool,
+    show_other: bool,
+    bodyweight: Option<f32>,
+    sex: Option<custom_types::Sex>,
+    weight_class: Option<String>,
+    verified: Vec<String>,
+}
+
+impl From<BoardRow> for PersonalBest {
+    fn from(row: BoardRow) -> Self {
+        Self {
+            warwick_id: row.warwick_id,
+            name: row.name,
+            bests: BTreeMap::new(),
+            show_pl: row.show_pl,
+            show_wl: row.show_wl,
+            show_other: row.show_other,
+            bodyweight: row.bodyweight,
+            sex: row.sex,
+            weight_class: row.weight_class,
+            verified: row.verified,
+        }
+    }
 }
 
 impl PersonalBest {
@@ -38,44 +77,203 @@ impl PersonalBest {
         }
     }
 
-    /// Inserts the [`PersonalBest`] into the database.
+    /// Converts the weights from kilograms into the given unit, for displaying them.
+    pub fn in_unit(mut self, unit: custom_types::Unit) -> Self {
+        for weight in self.bests.values_mut() {
+            *weight = unit.from_kg(*weight);
+        }
+
+        self.bodyweight = self.bodyweight.map(|kg| unit.from_kg(kg));
+
+        self
+    }
+
+    /// Checks whether the user has chosen to be shown on a board.
+    pub fn is_shown_on(&self, board: &str) -> bool {
+        match board {
+            POWERLIFTING => self.show_pl,
+            WEIGHTLIFTING => self.show_wl,
+            _ => self.show_other,
+        }
+    }
+
+    /// Checks whether the user has recorded any of the lifts shown on a board.
+    pub fn has_lift_on(&self, board: &str, lifts: &[Lift]) -> bool {
+        lifts
+            .iter()
+            .any(|lift| lift.board == board && self.bests.contains_key(&lift.name))
+    }
+
+    /// Inserts the [`PersonalBest`] into the database, recording each lift and the bodyweight as
+    /// performed today.
     pub async fn insert(&self, pool: &mut Pool) -> sqlx::Result<()> {
-        sqlx::query!("INSERT INTO personal_bests (warwick_id, name, squat, bench, deadlift, snatch, clean_and_jerk, show_pl, show_wl) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)", self.warwick_id, self.name, self.squat, self.bench, self.deadlift, self.snatch, self.clean_and_jerk, self.show_pl, self.show_wl).execute(pool).await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO personal_bests
+            (warwick_id, name, show_pl, show_wl, show_other, sex, weight_class)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            self.warwick_id,
+            self.name,
+            self.show_pl,
+            self.show_wl,
+            self.show_other,
+            self.sex as _,
+            self.weight_class
+        )
+        .execute(&mut *pool)
+        .await?;
 
-        Ok(())
+        // The first category chosen applies to any lifts recorded for earlier days
+        sqlx::query!(
+            r#"
+            INSERT INTO category_changes (warwick_id, sex, weight_class, changed_on)
+            VALUES ($1, $2, $3, '-infinity')
+            "#,
+            self.warwick_id,
+            self.sex as _,
+            self.weight_class
+        )
+        .execute(&mut *pool)
+        .await?;
+
+        let today = custom_types::Date(chrono::Local::now().date_naive());
+
+        if let Some(weight) = self.bodyweight {
+            let bodyweight = Bodyweight {
+                warwick_id: self.warwick_id,
+                weight,
+                recorded_on: today,
+            };
+
+            bodyweight.insert(&mut *pool).await?;
+        }
+
+        self.record_lifts(today, &mut *pool).await
     }
 
-    /// Gets all personal bests currently in the database.
-    pub async fn get_results(pool: &mut Pool) -> sqlx::Result<(Vec<Self>, Vec<Self>)> {
-        let pl = Self::get_pl(pool).await?;
-        let wl = Self::get_wl(pool).await?;
+    /// Records each of the user's bests as a single performed on the given day.
+    async fn record_lifts(&self, today: custom_types::Date, pool: &mut Pool) -> sqlx::Result<()> {
+        for (lift, weight) in &self.bests {
+            let record = LiftRecord::single(self.warwick_id, lift, *weight, today);
+            record.insert(&mut *pool).await?;
+        }
 
-        Ok((pl, wl))
+        Ok(())
     }
 
-    /// Gets all personal bests currently in the database.
-    pub async fn get_pl(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
-        sqlx::query_as!(Self, "SELECT * FROM personal_bests WHERE show_pl AND (squat IS NOT NULL OR bench IS NOT NULL OR deadlift IS NOT NULL) ORDER BY warwick_id").fetch_all(pool).await
+    /// Fills in the best single each user has recorded for every lift, ignoring rejected lifts.
+    async fn with_bests(mut lifters: Vec<Self>, pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        let ids: Vec<i32> = lifters.iter().map(|lifter| lifter.warwick_id).collect();
+
+        let bests = sqlx::query!(
+            r#"
+            SELECT warwick_id, lift, MAX(weight) AS "weight!"
+            FROM lift_records
+            WHERE warwick_id = ANY($1)
+            AND (reps IS NULL OR reps = 1)
+            AND status <> 'rejected'
+            GROUP BY warwick_id, lift
+            "#,
+            &ids
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let positions: HashMap<i32, usize> = ids
+            .into_iter()
+            .enumerate()
+            .map(|(index, warwick_id)| (warwick_id, index))
+            .collect();
+
+        for best in bests {
+            if let Some(&index) = positions.get(&best.warwick_id) {
+                lifters[index].bests.insert(best.lift, best.weight);
+            }
+        }
+
+        Ok(lifters)
     }
 
-    /// Gets all personal bests currently in the database.
-    pub async fn get_wl(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
-        sqlx::query_as!(Self, "SELECT * FROM personal_bests WHERE show_wl AND (snatch IS NOT NULL OR clean_and_jerk IS NOT NULL) ORDER BY warwick_id").fetch_all(pool).await
+    /// Gets the personal bests to show on the PL and WL boards, along with the lifts shown on
+    /// each.
+    ///
+    /// Lifters are included if they have chosen to be shown on a board and have recorded any of
+    /// its lifts. They are returned in order of their Warwick ID, and ranked afterwards by
+    /// [`crate::scoring::rank`].
+    pub async fn get_results(
+        query: &forms::BoardQuery,
+        lifts: &[Lift],
+        pool: &mut Pool,
+    ) -> sqlx::Result<(Vec<Self>, Vec<Self>)> {
+        let rows = sqlx::query_as!(
+            BoardRow,
+            r#"
+            SELECT
+                warwick_id AS "warwick_id!",
+                name AS "name!",
+                show_pl AS "show_pl!",
+                show_wl AS "show_wl!",
+                show_other AS "show_other!",
+                bodyweight,
+                sex AS "sex: custom_types::Sex",
+                weight_class,
+                verified AS "verified!"
+            FROM personal_best_board
+            WHERE (show_pl OR show_wl)
+                AND ($1::TEXT IS NULL OR sex = $1)
+                AND ($2::TEXT IS NULL OR weight_class = $2)
+            ORDER BY warwick_id
+            "#,
+            query.sex as _,
+            query.class()
+        )
+        .fetch_all(&mut *pool)
+        .await?;
+
+        let lifters = rows.into_iter().map(Self::from).collect();
+
+        let lifters = Self::with_bests(lifters, pool).await?;
+
+        let on_board = |board: &str| -> Vec<Self> {
+            lifters
+                .iter()
+                .filter(|lifter| lifter.is_shown_on(board) && lifter.has_lift_on(board, lifts))
+                .cloned()
+                .collect()
+        };
+
+        Ok((on_board(POWERLIFTING), on_board(WEIGHTLIFTING)))
     }
 
     /// Finds a user's personal bests in the database given their Warwick ID.
     pub async fn find(warwick_id: i32, name: &str, pool: &mut Pool) -> sqlx::Result<Self> {
         // See if we can find some personal bests first
         let potential = sqlx::query_as!(
-            Self,
-            "SELECT * FROM personal_bests WHERE warwick_id = $1",
+            BoardRow,
+            r#"
+            SELECT
+                warwick_id AS "warwick_id!",
+                name AS "name!",
+                show_pl AS "show_pl!",
+                show_wl AS "show_wl!",
+                show_other AS "show_other!",
+                bodyweight,
+                sex AS "sex: custom_types::Sex",
+                weight_class,
+                verified AS "verified!"
+            FROM personal_best_board
+            WHERE warwick_id = $1
+            "#,
             warwick_id
         )
         .fetch_optional(&mut *pool)
         .await?;
 
-        if let Some(pbs) = potential {
-            return Ok(pbs);
+        if let Some(row) = potential {
+            let mut pbs = Self::with_bests(vec![Self::from(row)], pool).await?;
+
+            return Ok(pbs.remove(0));
         }
 
         tracing::info!(%name, %warwick_id, "Inserting default personal bests for a user");
@@ -88,6 +286,9 @@ impl PersonalBest {
     }
 
     /// Updates a user's personal bests based on their form submission.
+    ///
+    /// Any lifts submitted are recorded as performed today, keeping their previous records. The
+    /// form is expected to have been validated already.
     pub async fn update(
         user_id: i32,
         name: String,
@@ -96,46 +297,82 @@ impl PersonalBest {
     ) -> sqlx::Result<()> {
         tracing::info!(%name, %user_id, ?data, "Updating personal bests for a user with new information");
 
+        let today = chrono::Local::now().date_naive();
+
         sqlx::query!(
             r#"
             UPDATE personal_bests
-            SET
-                squat = COALESCE($1, squat),
-                bench = COALESCE($2, bench),
-                deadlift = COALESCE($3, deadlift),
-                snatch = COALESCE($4, snatch),
-                clean_and_jerk = COALESCE($5, clean_and_jerk),
-                show_pl = $6,
-                show_wl = $7
-            WHERE warwick_id = $8"#,
-            data.squat,
-            data.bench,
-            data.deadlift,
-            data.snatch,
-            data.clean_and_jerk,
+            SET show_pl = $1, show_wl = $2, show_other = $3, sex = $4, weight_class = $5
+            WHERE warwick_id = $6
+            "#,
             data.show_pl,
             data.show_wl,
+            data.show_other,
+            data.sex as _,
+            data.weight_class(),
             user_id
         )
-        .execute(pool)
+        .execute(&mut *pool)
         .await?;
 
-        Ok(())
-    }
+        // Changing category only applies from today, so earlier lifts keep the previous one
+        sqlx::query!(
+            r#"
+            INSERT INTO category_changes (warwick_id, sex, weight_class, changed_on)
+            SELECT $1, $2::TEXT, $3::TEXT, $4
+            WHERE NOT EXISTS (
+                SELECT 1 FROM (
+                    SELECT sex, weight_class FROM category_changes
+                    WHERE warwick_id = $1
+                    ORDER BY changed_on DESC, id DESC
+                    LIMIT 1
+                ) latest
+                WHERE latest.sex IS NOT DISTINCT FROM $2
+                AND latest.weight_class IS NOT DISTINCT FROM $3
+            )
+            "#,
+            user_id,
+            data.sex as _,
+            data.weight_class(),
+            today
+        )
+        .execute(&mut *pool)
+        .await?;
 
-    /// Checks whether the personal bests warrant a warning message.
-    pub fn check_for_show_without_values(&self) -> Option<String> {
-        if self.show_pl
-            && !(self.squat.is_some() || self.bench.is_some() || self.deadlift.is_some())
-        {
-            return Some(String::from("You have checked to be shown for powerlifting but have no personal bests, so you have been hidden from this board"));
-        }
+        if let Some(weight) = data.bodyweight {
+            let bodyweight = Bodyweight {
+                warwick_id: user_id,
+                weight,
+                recorded_on: custom_types::Date(data.bodyweight_recorded_on().unwrap_or(today)),
+            };
 
-        if self.show_wl && !(self.snatch.is_some() || self.clean_and_jerk.is_some()) {
-            return Some(String::from("You have checked to be shown for weightlifting but have no personal bests, so you have been hidden from this board"));
+            bodyweight.insert(&mut *pool).await?;
         }
 
-        None
+        let submitted = Self {
+            warwick_id: user_id,
+            name,
+            bests: data.lifts(),
+            ..Self::default()
+        };
+
+        submitted
+            .record_lifts(custom_types::Date(today), pool)
+            .await
+    }
+
+    /// Checks whether the personal bests warrant a warning message, given the lifts in the
+    /// catalogue.
+    pub fn check_for_show_without_values(&self, lifts: &[Lift]) -> Option<String> {
+        [POWERLIFTING, WEIGHTLIFTING]
+            .into_iter()
+            .find(|board| self.is_shown_on(board) && !self.has_lift_on(board, lifts))
+            .map(|board| {
+                format!(
+                    "You have checked to be shown for {} but have no personal bests, so you have been hidden from this board",
+                    board.to_lowercase()
+                )
+            })
     }
 }
 
@@ -143,37 +380,65 @@ impl PersonalBest {
 mod tests {
     use super::*;
 
+    fn catalogue() -> Vec<Lift> {
+        let mut lifts = crate::schema::lift::original_lifts();
+        lifts.push(Lift::new("log_press", "Log Press", "LOG", "Strongman"));
+        lifts
+    }
+
     #[test]
     fn show_pl_with_no_pl_lifts_shows_a_warning() {
         let mut personal_bests = PersonalBest::new(1702502, String::from("Alex Jackson"));
         personal_bests.show_pl = true;
+        personal_bests.bests.insert(String::from("snatch"), 50.0);
 
-        assert!(personal_bests.check_for_show_without_values().is_some());
+        assert!(personal_bests
+            .check_for_show_without_values(&catalogue())
+            .is_some());
     }
 
     #[test]
     fn show_wl_with_no_wl_lifts_shows_a_warning() {
         let mut personal_bests = PersonalBest::new(1702502, String::from("Alex Jackson"));
         personal_bests.show_wl = true;
+        personal_bests.bests.insert(String::from("log_press"), 80.0);
 
-        assert!(personal_bests.check_for_show_without_values().is_some());
+        assert!(personal_bests
+            .check_for_show_without_values(&catalogue())
+            .is_some());
     }
 
     #[test]
     fn show_pl_with_some_pl_lifts_shows_no_warning() {
         let mut personal_bests = PersonalBest::new(1702502, String::from("Alex Jackson"));
         personal_bests.show_pl = true;
-        personal_bests.squat = Some(100.0);
+        personal_bests.bests.insert(String::from("squat"), 100.0);
 
-        assert!(personal_bests.check_for_show_without_values().is_none());
+        assert!(personal_bests
+            .check_for_show_without_values(&catalogue())
+            .is_none());
     }
 
     #[test]
     fn show_wl_with_some_wl_lifts_shows_no_warning() {
         let mut personal_bests = PersonalBest::new(1702502, String::from("Alex Jackson"));
         personal_bests.show_wl = true;
-        personal_bests.snatch = Some(50.0);
+        personal_bests.bests.insert(String::from("snatch"), 50.0);
+
+        assert!(personal_bests
+            .check_for_show_without_values(&catalogue())
+            .is_none());
+    }
+
+    #[test]
+    fn other_boards_have_their_own_choice() {
+        let mut personal_bests = PersonalBest::new(1702502, String::from("Alex Jackson"));
+        personal_bests.show_pl = true;
+        personal_bests.show_wl = true;
+
+        assert!(!personal_bests.is_shown_on("Strongman"));
 
-        assert!(personal_bests.check_for_show_without_values().is_none());
+        personal_bests.show_other = true;
+        assert!(personal_bests.is_shown_on("Strongman"));
     }
 }
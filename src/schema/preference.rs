@@ -0,0 +1,58 @@
+//! Allows modifications of the `preferences` table in the database.
+
+use serde::Serialize;
+
+use crate::schema::{custom_types, Pool};
+
+/// Represents a row in the `preferences` table.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct Preferences {
+    /// The user's Warwick ID.
+    pub warwick_id: i32,
+    /// The unit the user enters and views weights in.
+    pub unit: custom_types::Unit,
+    /// The formula used to estimate the user's one rep maxes.
+    pub formula: custom_types::Formula,
+}
+
+impl Preferences {
+    /// Finds a user's preferences, using the defaults if they have not set any.
+    pub async fn find(warwick_id: i32, pool: &mut Pool) -> sqlx::Result<Self> {
+        let preferences = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                warwick_id,
+                unit AS "unit: custom_types::Unit",
+                formula AS "formula: custom_types::Formula"
+            FROM preferences
+            WHERE warwick_id = $1
+            "#,
+            warwick_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(preferences.unwrap_or(Self {
+            warwick_id,
+            ..Self::default()
+        }))
+    }
+
+    /// Saves a user's preferences, replacing any they had already.
+    pub async fn save(&self, pool: &mut Pool) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO preferences (warwick_id, unit, formula) VALUES ($1, $2, $3)
+            ON CONFLICT (warwick_id) DO UPDATE SET unit = EXCLUDED.unit, formula = EXCLUDED.formula
+            "#,
+            self.warwick_id,
+            self.unit as _,
+            self.formula as _
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+//! Allows modifications of the `roles`, `user_roles` and `role_audit_log` tables in the database.
+
+use std::env;
+
+use serde::Serialize;
+use sqlx::PgConnection;
+
+use crate::schema::membership::{self, Membership};
+use crate::schema::{custom_types, Pool};
+
+/// The environment variables that used to define each role, used for importing them.
+const LEGACY_VARIABLES: [(&str, &str); 2] = [
+    ("ELECTION_ADMINS", "election_admin"),
+    ("SITE_ADMINS", "site_admin"),
+];
+
+/// The environment variable that used to list the members, who are imported as memberships.
+const LEGACY_MEMBERS: &str = "BARBELL_MEMBERS";
+
+/// Represents a row in the `roles` table.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Role {
+    /// The name of the role.
+    pub name: String,
+    /// A human readable description of the role.
+    pub description: String,
+}
+
+/// Represents a row in the `user_roles` table.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct UserRole {
+    /// The user's Warwick ID.
+    pub warwick_id: i32,
+    /// The name of the role they hold.
+    pub role: String,
+}
+
+/// Represents a row in the `role_audit_log` table.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct RoleAuditEntry {
+    /// The Warwick ID of the user who made the change, if it was not the system.
+    pub actor_id: Option<i32>,
+    /// The Warwick ID of the user whose roles changed.
+    pub warwick_id: i32,
+    /// The name of the role that changed.
+    pub role: String,
+    /// Either "grant" or "revoke".
+    pub action: String,
+    /// When the change was made.
+    pub timestamp: custom_types::DateTime,
+}
+
+impl Role {
+    /// Gets all [`Role`] entries in the database.
+    pub async fn get_results(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(Self, "SELECT * FROM roles ORDER BY name")
+            .fetch_all(pool)
+            .await
+    }
+}
+
+impl UserRole {
+    /// Gets all [`UserRole`] entries in the database.
+    pub async fn get_results(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(Self, "SELECT * FROM user_roles ORDER BY role, warwick_id")
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Gets the names of all the roles a user holds.
+    ///
+    /// Anyone with a membership that is valid today also holds the member role, so that it lapses
+    /// automatically when their membership expires.
+    pub async fn roles_for(warwick_id: i32, pool: &mut Pool) -> sqlx::Result<Vec<String>> {
+        sqlx::query!(
+            r#"
+            SELECT role AS "role!" FROM user_roles WHERE warwick_id = $1
+            UNION
+            SELECT 'member' FROM memberships
+            WHERE warwick_id = $1 AND valid_from <= CURRENT_DATE AND CURRENT_DATE <= valid_to
+            "#,
+            warwick_id
+        )
+        .map(|row| row.role)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Grants a role to a user, recording the change in the audit log in the same transaction.
+    ///
+    /// Granting a role the user already holds does nothing and is not recorded.
+    pub async fn grant(
+        actor_id: Option<i32>,
+        warwick_id: i32,
+        role: &str,
+        pool: &mut Pool,
+    ) -> sqlx::Result<()> {
+        let mut transaction = pool.begin().await?;
+
+        let result = sqlx::query!(
+            "INSERT INTO user_roles (warwick_id, role) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            warwick_id,
+            role
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(());
+        }
+
+        RoleAuditEntry::insert(actor_id, warwick_id, role, "grant", &mut *transaction).await?;
+        transaction.commit().await?;
+
+        tracing::info!(?actor_id, %warwick_id, %role, "Granted a role to a user");
+
+        Ok(())
+    }
+
+    /// Revokes a role from a user, recording the change in the audit log in the same transaction.
+    ///
+    /// Revoking a role the user does not hold does nothing and is not recorded.
+    pub async fn revoke(
+        actor_id: Option<i32>,
+        warwick_id: i32,
+        role: &str,
+        pool: &mut Pool,
+    ) -> sqlx::Result<()> {
+        let mut transaction = pool.begin().await?;
+
+        let result = sqlx::query!(
+            "DELETE FROM user_roles WHERE warwick_id = $1 AND role = $2",
+            warwick_id,
+            role
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(());
+        }
+
+        RoleAuditEntry::insert(actor_id, warwick_id, role, "revoke", &mut *transaction).await?;
+        transaction.commit().await?;
+
+        tracing::info!(?actor_id, %warwick_id, %role, "Revoked a role from a user");
+
+        Ok(())
+    }
+
+    /// Imports the roles that used to be defined by environment variables.
+    ///
+    /// This only happens if nobody holds any roles or memberships yet, so that it runs once when
+    /// upgrading and the environment variables can then be removed. Members are given a
+    /// membership lasting until the end of the academic year, so that they lapse like everyone
+    /// else.
+    pub async fn import_from_env(pool: &mut Pool) -> sqlx::Result<()> {
+        let existing = sqlx::query!(
+            r#"SELECT (SELECT COUNT(*) FROM user_roles) + (SELECT COUNT(*) FROM memberships) AS "count!""#
+        )
+        .map(|row| row.count)
+        .fetch_one(&mut *pool)
+        .await?;
+
+        if existing != 0 {
+            return Ok(());
+        }
+
+        for (key, role) in LEGACY_VARIABLES {
+            let value = match env::var(key) {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+
+            tracing::info!(environment_variable = %key, %role, "Importing roles from the environment");
+
+            for id in value.split(',').filter_map(|id| id.trim().parse().ok()) {
+                Self::grant(None, id, role, &mut *pool).await?;
+            }
+        }
+
+        if let Ok(value) = env::var(LEGACY_MEMBERS) {
+            tracing::info!(environment_variable = %LEGACY_MEMBERS, "Importing members from the environment");
+
+            let today = chrono::Local::today().naive_local();
+            let valid_to = membership::academic_year_end(today);
+
+            for warwick_id in value.split(',').filter_map(|id| id.trim().parse().ok()) {
+                let membership = Membership {
+                    warwick_id,
+                    membership_type: String::from("Legacy"),
+                    valid_from: custom_types::Date(today),
+                    valid_to: custom_types::Date(valid_to),
+                    source: String::from("environment"),
+                };
+
+                membership.insert(&mut *pool).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl RoleAuditEntry {
+    /// Inserts a new entry into the audit log, timestamped with the current time.
+    async fn insert(
+        actor_id: Option<i32>,
+        warwick_id: i32,
+        role: &str,
+        action: &str,
+        conn: &mut PgConnection,
+    ) -> sqlx::Result<()> {
+        let timestamp = chrono::Utc::now().timestamp();
+
+        sqlx::query!(
+            "INSERT INTO role_audit_log (actor_id, warwick_id, role, action, timestamp) VALUES ($1, $2, $3, $4, $5)",
+            actor_id,
+            warwick_id,
+            role,
+            action,
+            timestamp
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets all [`RoleAuditEntry`] entries in the database, most recent first.
+    pub async fn get_results(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                actor_id,
+                warwick_id,
+                role,
+                action,
+                timestamp AS "timestamp: custom_types::DateTime"
+            FROM role_audit_log
+            ORDER BY timestamp DESC, id DESC
+            "#
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
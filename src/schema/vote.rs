@@ -1,16 +1,38 @@
-//! Allows modifications of the `votes` table in the database.
+//! Allows modifications of the `votes` and `ballots` tables in the database.
+//!
+//! Ballots are only identified by a random ID, so nothing links a ballot to the voter who cast it.
+//! Instead, voters are given a secret when they vote, and holding it lets them replace their ballot
+//! while voting is open. They are also given a receipt code committing to their votes, which they
+//! can find among the published ballots once voting closes.
 
 use std::collections::HashMap;
 
+use itertools::Itertools;
+use rand::seq::SliceRandom;
+use rand::RngCore;
 use serde::Serialize;
+use sha2::{Digest, Sha256};
 
+use crate::schema::exec_position::RON_NAME;
 use crate::schema::Pool;
 
+/// The number of random bytes in each ballot identifier.
+const BALLOT_ID_LENGTH: usize = 16;
+
+/// The number of random bytes in each secret.
+const SECRET_LENGTH: usize = 32;
+
+/// The number of random bytes mixed into each receipt code, so that codes can't be guessed.
+const NONCE_LENGTH: usize = 16;
+
+/// The start of the name of the cookies holding voters' secrets.
+pub const SECRET_COOKIE_PREFIX: &str = "ballot_";
+
 /// Represents a row in the `votes` table.
-#[derive(Copy, Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Vote {
-    /// The Warwick identifier of the user voting.
-    pub warwick_id: i32,
+    /// The random identifier of the ballot the vote is on.
+    pub ballot_id: String,
     /// The position identifier they voted for.
     pub position_id: i32,
     /// The identifier of the candidate they voted for.
@@ -19,94 +41,396 @@ pub struct Vote {
     pub ranking: i32,
 }
 
-impl Vote {
-    /// Inserts the [`Vote`] into the database.
-    pub async fn insert(&self, pool: &mut Pool) -> sqlx::Result<()> {
-        tracing::debug!(?self, "Inserting an entry to the `vote` table");
+/// What a voter is given when their ballot is recorded.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Receipt {
+    /// The secret kept in the voter's session, which lets them replace their ballot.
+    pub secret: String,
+    /// The code committing to the votes on the ballot.
+    pub code: String,
+    /// What the code is the hash of, so that the voter can calculate it themselves.
+    pub commitment: String,
+}
 
-        sqlx::query!("INSERT INTO votes (warwick_id, position_id, candidate_id, ranking) VALUES ($1, $2, $3, $4)", self.warwick_id, self.position_id, self.candidate_id, self.ranking).execute(pool).await?;
+/// A ballot as published once voting closes, without anything linking it to its voter.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct PublishedBallot {
+    /// The receipt code committing to the ballot.
+    pub receipt: String,
+    /// The names of the candidates on the ballot, in order of preference.
+    pub preferences: Vec<String>,
+}
 
-        Ok(())
-    }
+/// Hashes a secret for storing or looking up in the database.
+fn hash(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
 
-    /// Inserts a group of [`Vote`]s into the database.
-    pub async fn insert_all(
+/// Generates a random string from the given number of bytes.
+fn generate(length: usize) -> String {
+    let mut bytes = vec![0u8; length];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// Gets what the receipt code for a ballot commits to, which is its nonce, position and candidates
+/// in order of preference, such as `nonce:12:1702502,0`.
+///
+/// The receipt code is the SHA-256 hash of this, so a ballot can't be changed without its code
+/// changing too. Only the voter is given the nonce, so nobody else can work out the code of a
+/// published ballot.
+fn commitment(nonce: &str, position_id: i32, candidates: &[i32]) -> String {
+    format!("{}:{}:{}", nonce, position_id, candidates.iter().join(","))
+}
+
+/// Gets the name of the cookie holding a voter's secret for a position.
+///
+/// The name includes the voter, so that nobody else signing in on the same browser can use it.
+pub fn secret_cookie(warwick_id: i32, position_id: i32) -> String {
+    format!("{}{}_{}", SECRET_COOKIE_PREFIX, position_id, warwick_id)
+}
+
+impl Vote {
+    /// Records a voter's ballot, returning the secret that lets them replace it along with its
+    /// receipt code.
+    ///
+    /// If the secret for their existing ballot is given, its votes are replaced instead and it gets
+    /// a new receipt code. Voters are added to the voter roll when they first vote, and nothing is
+    /// recorded if they are already on it without a secret, as their existing ballot can't be
+    /// found.
+    ///
+    /// The voter roll and the ballot are written in separate transactions, after which every
+    /// ballot for the position is rewritten in a random order. Neither the transaction that wrote
+    /// a ballot nor where it is stored shows which ballot was cast last, so ballots can't be
+    /// matched up with the voter roll. Ballots for the same position are written one at a time,
+    /// as each rewrite touches all of them.
+    pub async fn cast(
         warwick_id: i32,
         position_id: i32,
+        secret: Option<&str>,
         map: &HashMap<i32, i32>,
         pool: &mut Pool,
-    ) -> sqlx::Result<()> {
-        // Delete all previous votes to avoid clashes
-        sqlx::query!(
-            "DELETE FROM votes WHERE warwick_id = $1 AND position_id = $2",
-            warwick_id,
-            position_id
+    ) -> sqlx::Result<Option<Receipt>> {
+        let candidates: Vec<_> = map.iter().sorted().map(|(_, id)| *id).collect();
+        let nonce = generate(NONCE_LENGTH);
+        let commitment = commitment(&nonce, position_id, &candidates);
+        let code = hash(&commitment);
+
+        let existing = match secret {
+            Some(secret) => {
+                sqlx::query_scalar!(
+                    "SELECT id FROM ballots WHERE position_id = $1 AND secret_hash = $2",
+                    position_id,
+                    hash(secret)
+                )
+                .fetch_optional(&mut *pool)
+                .await?
+            }
+            None => None,
+        };
+
+        let (ballot_id, secret, replacing) = match (existing, secret) {
+            (Some(ballot_id), Some(secret)) => (ballot_id, String::from(secret), true),
+            _ => {
+                let voted_at = chrono::Utc::now().timestamp();
+
+                let added = sqlx::query!(
+                    r#"
+                    INSERT INTO voters (position_id, warwick_id, voted_at) VALUES ($1, $2, $3)
+                    ON CONFLICT DO NOTHING
+                    "#,
+                    position_id,
+                    warwick_id,
+                    voted_at
+                )
+                .execute(&mut *pool)
+                .await?;
+
+                if added.rows_affected() == 0 {
+                    return Ok(None);
+                }
+
+                tracing::info!(%warwick_id, %position_id, "Added a voter to the voter roll");
+
+                (generate(BALLOT_ID_LENGTH), generate(SECRET_LENGTH), false)
+            }
+        };
+
+        let recorded: sqlx::Result<()> = async {
+            let mut transaction = pool.begin().await?;
+
+            // Wait for any other ballot for the position to be written first, so that concurrent
+            // rewrites can't deadlock on each other's rows
+            sqlx::query_scalar!(
+                "SELECT id FROM exec_positions WHERE id = $1 FOR UPDATE",
+                position_id
+            )
+            .fetch_optional(&mut transaction)
+            .await?;
+
+            if replacing {
+                // Delete all previous votes to avoid clashes
+                sqlx::query!("DELETE FROM votes WHERE ballot_id = $1", ballot_id)
+                    .execute(&mut transaction)
+                    .await?;
+
+                sqlx::query!(
+                    "UPDATE ballots SET nonce = $2, receipt = $3 WHERE id = $1",
+                    ballot_id,
+                    nonce,
+                    code
+                )
+                .execute(&mut transaction)
+                .await?;
+
+                tracing::info!(%position_id, "Deleted the votes on a ballot being replaced");
+            } else {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO ballots (id, position_id, secret_hash, nonce, receipt)
+                    VALUES ($1, $2, $3, $4, $5)
+                    "#,
+                    ballot_id,
+                    position_id,
+                    hash(&secret),
+                    nonce,
+                    code
+                )
+                .execute(&mut transaction)
+                .await?;
+            }
+
+            // `sqlx` doesn't support multiple entries, so iterate instead
+            for (ranking, candidate_id) in map {
+                sqlx::query!(
+                    "INSERT INTO votes (ballot_id, position_id, candidate_id, ranking) VALUES ($1, $2, $3, $4)",
+                    ballot_id,
+                    position_id,
+                    candidate_id,
+                    ranking
+                )
+                .execute(&mut transaction)
+                .await?;
+            }
+
+            // Rewrite every ballot in a random order, so they are all stored anew by this
+            // transaction and the new one can't be told apart by when or where it was written
+            let mut ballot_ids = sqlx::query_scalar!(
+                "SELECT id FROM ballots WHERE position_id = $1",
+                position_id
+            )
+            .fetch_all(&mut transaction)
+            .await?;
+
+            ballot_ids.shuffle(&mut rand::thread_rng());
+
+            for id in ballot_ids {
+                sqlx::query!("UPDATE ballots SET nonce = nonce WHERE id = $1", id)
+                    .execute(&mut transaction)
+                    .await?;
+
+                sqlx::query!("UPDATE votes SET ranking = ranking WHERE ballot_id = $1", id)
+                    .execute(&mut transaction)
+                    .await?;
+            }
+
+            transaction.commit().await
+        }
+        .await;
+
+        if let Err(e) = recorded {
+            // Take them off the voter roll again, so that they can vote once the problem is fixed
+            if !replacing {
+                sqlx::query!(
+                    "DELETE FROM voters WHERE position_id = $1 AND warwick_id = $2",
+                    position_id,
+                    warwick_id
+                )
+                .execute(&mut *pool)
+                .await?;
+            }
+
+            return Err(e);
+        }
+
+        tracing::info!(%position_id, "Inserted a ballot into the database");
+
+        Ok(Some(Receipt {
+            secret,
+            code,
+            commitment,
+        }))
+    }
+
+    /// Gets all the [`Vote`] entries for the positions in an election.
+    pub async fn for_election(election_id: i32, pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT v.ballot_id, v.position_id, v.candidate_id, v.ranking
+            FROM votes v
+            INNER JOIN exec_positions p ON p.id = v.position_id
+            WHERE p.election_id = $1
+            "#,
+            election_id
         )
-        .execute(&mut *pool)
-        .await?;
+        .fetch_all(pool)
+        .await
+    }
 
-        tracing::info!(%warwick_id, %position_id, "Deleted all the votes in the database");
+    /// Gets the ballots for a position with their receipt codes, ordered by code so that they can't
+    /// be matched up with the voter roll.
+    ///
+    /// Voters can find their code and check the preferences next to it are the ones they cast,
+    /// which are the same preferences that are counted.
+    pub async fn published(
+        position_id: i32,
+        pool: &mut Pool,
+    ) -> sqlx::Result<Vec<PublishedBallot>> {
+        let votes = sqlx::query!(
+            r#"
+            SELECT b.receipt, COALESCE(c.name, $2) AS "name!"
+            FROM ballots b
+            INNER JOIN votes v ON v.ballot_id = b.id
+            INNER JOIN exec_positions p ON p.id = b.position_id
+            LEFT JOIN candidates c ON c.election_id = p.election_id AND c.warwick_id = v.candidate_id
+            WHERE b.position_id = $1
+            ORDER BY b.receipt, v.ranking
+            "#,
+            position_id,
+            RON_NAME
+        )
+        .fetch_all(pool)
+        .await?;
 
-        let votes: Vec<Self> = map
-            .iter()
-            .map(|(ranking, candidate_id)| Vote {
-                warwick_id,
-                position_id,
-                candidate_id: *candidate_id,
-                ranking: *ranking,
+        let ballots = votes
+            .into_iter()
+            .group_by(|vote| vote.receipt.clone())
+            .into_iter()
+            .map(|(receipt, votes)| PublishedBallot {
+                receipt,
+                preferences: votes.map(|vote| vote.name).collect(),
             })
             .collect();
 
-        // `sqlx` doesn't support multiple entries, so iterate instead
-        for vote in votes {
-            vote.insert(&mut *pool).await?;
-        }
+        Ok(ballots)
+    }
 
-        tracing::info!(%warwick_id, %position_id, ?map, "Inserted a ballot into the database");
+    /// Gets the receipt for the ballot a secret is for, if there is one.
+    pub async fn get_receipt(
+        secret: &str,
+        position_id: i32,
+        pool: &mut Pool,
+    ) -> sqlx::Result<Option<Receipt>> {
+        let votes = sqlx::query!(
+            r#"
+            SELECT b.nonce, b.receipt, v.candidate_id
+            FROM ballots b
+            INNER JOIN votes v ON v.ballot_id = b.id
+            WHERE b.secret_hash = $1 AND b.position_id = $2
+            ORDER BY v.ranking
+            "#,
+            hash(secret),
+            position_id
+        )
+        .fetch_all(pool)
+        .await?;
 
-        Ok(())
-    }
+        let receipt = votes.first().map(|first| {
+            let candidates: Vec<_> = votes.iter().map(|vote| vote.candidate_id).collect();
+
+            Receipt {
+                secret: String::from(secret),
+                code: first.receipt.clone(),
+                commitment: commitment(&first.nonce, position_id, &candidates),
+            }
+        });
 
-    /// Gets all [`Vote`] entries in the database.
-    pub async fn get_results(pool: &mut Pool) -> sqlx::Result<Vec<Self>> {
-        sqlx::query_as!(Self, "SELECT * FROM votes")
-            .fetch_all(pool)
-            .await
+        Ok(receipt)
     }
 
-    /// Gets a user's current ballot state, if they have voted.
+    /// Gets the current state of the ballot a secret is for, if there is one.
     pub async fn get_current_ballot(
-        user_id: i32,
+        secret: &str,
         position_id: i32,
         pool: &mut Pool,
     ) -> sqlx::Result<Option<Vec<String>>> {
-        tracing::debug!(%user_id, %position_id, "Fetching the current ballot");
+        tracing::debug!(%position_id, "Fetching the current ballot");
 
-        // Get their votes for this position
+        // Get the votes on the ballot for this position
         sqlx::query!(
         r#"
-            SELECT c.name AS name
+            SELECT COALESCE(c.name, $3) AS "name!"
             FROM votes v
-            INNER JOIN nominations n ON n.warwick_id = v.candidate_id AND n.position_id = v.position_id
-            INNER JOIN candidates c ON c.warwick_id = n.warwick_id
-            WHERE v.warwick_id = $1 AND v.position_id = $2 ORDER BY v.ranking
+            INNER JOIN ballots b ON b.id = v.ballot_id
+            INNER JOIN exec_positions p ON p.id = v.position_id
+            LEFT JOIN candidates c ON c.election_id = p.election_id AND c.warwick_id = v.candidate_id
+            WHERE b.secret_hash = $1 AND v.position_id = $2 ORDER BY v.ranking
         "#,
-        user_id,
-        position_id
+        hash(secret),
+        position_id,
+        RON_NAME
         )
         .fetch_all(pool)
         .await
         .map(|v| if v.is_empty() { None } else { Some(v.into_iter().map(|e| e.name).collect()) })
     }
+
+    /// Checks whether a user is on the voter roll for a position.
+    pub async fn has_voted(
+        warwick_id: i32,
+        position_id: i32,
+        pool: &mut Pool,
+    ) -> sqlx::Result<bool> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM voters WHERE position_id = $1 AND warwick_id = $2) AS "voted!"
+            "#,
+            position_id,
+            warwick_id
+        )
+        .fetch_one(pool)
+        .await
+    }
 }
 
 impl From<(i32, i32, i32, i32)> for Vote {
-    fn from((position_id, warwick_id, candidate_id, ranking): (i32, i32, i32, i32)) -> Self {
+    fn from((position_id, ballot, candidate_id, ranking): (i32, i32, i32, i32)) -> Self {
         Self {
-            warwick_id,
+            ballot_id: ballot.to_string(),
             position_id,
             candidate_id,
             ranking,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ballot_ids_and_secrets_are_random() {
+        assert_ne!(generate(BALLOT_ID_LENGTH), generate(BALLOT_ID_LENGTH));
+        assert_eq!(generate(SECRET_LENGTH).len(), 43);
+    }
+
+    #[test]
+    fn receipt_codes_commit_to_the_ballot() {
+        let committed = commitment("nonce", 12, &[1702502, 0]);
+
+        assert_eq!(committed, "nonce:12:1702502,0");
+        assert_ne!(committed, commitment("nonce", 12, &[0, 1702502]));
+        assert_ne!(committed, commitment("other", 12, &[1702502, 0]));
+        assert_ne!(committed, commitment("nonce", 13, &[1702502, 0]));
+    }
+
+    #[test]
+    fn secrets_are_hashed_consistently() {
+        let secret = generate(SECRET_LENGTH);
+
+        assert_eq!(hash(&secret), hash(&secret));
+        assert_ne!(hash(&secret), secret);
+    }
+}
@@ -0,0 +1,597 @@
+//! Calculates totals and bodyweight-adjusted scores for the boards.
+//!
+//! Each formula compares lifters of different sizes by dividing their total by a function of their
+//! bodyweight. Bodyweights outside the range each formula was fitted to are clamped to it, as in
+//! the published tables.
+
+use std::cmp::Ordering;
+use std::env;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::forms::BoardSort;
+use crate::schema::custom_types::{Sex, Unit};
+use crate::schema::lift::{self, POWERLIFTING, WEIGHTLIFTING};
+use crate::schema::{Lift, PersonalBest};
+
+/// The coefficients of the DOTS polynomial, from the constant term upwards.
+const DOTS_MALE: [f64; 5] = [
+    -307.75076,
+    24.0900756,
+    -0.1918759221,
+    0.0007391293,
+    -0.000001093,
+];
+
+/// The coefficients of the DOTS polynomial, from the constant term upwards.
+const DOTS_FEMALE: [f64; 5] = [
+    -57.96288,
+    13.6175032,
+    -0.1126655495,
+    0.0005158568,
+    -0.0000010706,
+];
+
+/// The coefficients of the original Wilks polynomial, from the constant term upwards.
+const WILKS_MALE: [f64; 6] = [
+    -216.0475144,
+    16.2606339,
+    -0.002388645,
+    -0.00113732,
+    7.01863e-06,
+    -1.291e-08,
+];
+
+/// The coefficients of the original Wilks polynomial, from the constant term upwards.
+const WILKS_FEMALE: [f64; 6] = [
+    594.31747775582,
+    -27.23842536447,
+    0.82112226871,
+    -0.00930733913,
+    4.731582e-05,
+    -9.054e-08,
+];
+
+/// The coefficients of the 2020 Wilks polynomial, from the constant term upwards.
+const WILKS_2020_MALE: [f64; 6] = [
+    47.46178854,
+    8.472061379,
+    0.07369410346,
+    -0.001395833811,
+    7.07665973070743e-06,
+    -1.20804336482315e-08,
+];
+
+/// The coefficients of the 2020 Wilks polynomial, from the constant term upwards.
+const WILKS_2020_FEMALE: [f64; 6] = [
+    -125.4255398,
+    13.71219419,
+    -0.03307250631,
+    -0.001050400051,
+    9.38773881462799e-06,
+    -2.3334613884954e-08,
+];
+
+/// The `A`, `B` and `C` parameters of the IPF GL formula for classic powerlifting.
+const IPF_GL_MALE: (f64, f64, f64) = (1199.72839, 1025.18162, 0.00921);
+
+/// The `A`, `B` and `C` parameters of the IPF GL formula for classic powerlifting.
+const IPF_GL_FEMALE: (f64, f64, f64) = (610.32796, 1045.59282, 0.03048);
+
+/// The Olympic cycles the IWF has published Sinclair coefficients for.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SinclairPeriod {
+    /// The coefficients used from 2017 to 2020.
+    Cycle2017To2020,
+    /// The coefficients used from 2021 to 2024.
+    #[default]
+    Cycle2021To2024,
+}
+
+impl SinclairPeriod {
+    /// Gets the period to use from the `SINCLAIR_PERIOD` environment variable, such as
+    /// `2017-2020`, defaulting to the most recent one.
+    pub fn from_env() -> Self {
+        env::var("SINCLAIR_PERIOD")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Gets the `A` and `b` parameters of the Sinclair formula for a category.
+    fn parameters(self, sex: Sex) -> (f64, f64) {
+        match (self, sex) {
+            (Self::Cycle2017To2020, Sex::Male) => (0.751945030, 175.508),
+            (Self::Cycle2017To2020, Sex::Female) => (0.783497476, 153.655),
+            (Self::Cycle2021To2024, Sex::Male) => (0.722762521, 193.609),
+            (Self::Cycle2021To2024, Sex::Female) => (0.787004341, 153.757),
+        }
+    }
+}
+
+impl FromStr for SinclairPeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "2017-2020" => Ok(Self::Cycle2017To2020),
+            "2021-2024" => Ok(Self::Cycle2021To2024),
+            _ => Err(format!("{} is not a known Sinclair coefficient period", s)),
+        }
+    }
+}
+
+/// Evaluates a polynomial with the given coefficients, from the constant term upwards.
+fn polynomial(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c)
+}
+
+/// Calculates the DOTS score for a total.
+pub fn dots(sex: Sex, bodyweight: f32, total: f32) -> f32 {
+    let (coefficients, max) = match sex {
+        Sex::Male => (&DOTS_MALE, 210.0),
+        Sex::Female => (&DOTS_FEMALE, 150.0),
+    };
+
+    let bodyweight = f64::from(bodyweight).clamp(40.0, max);
+
+    (f64::from(total) * 500.0 / polynomial(coefficients, bodyweight)) as f32
+}
+
+/// Calculates the IPF GL points for a classic total.
+pub fn ipf_gl(sex: Sex, bodyweight: f32, total: f32) -> f32 {
+    let (a, b, c) = match sex {
+        Sex::Male => IPF_GL_MALE,
+        Sex::Female => IPF_GL_FEMALE,
+    };
+
+    // The formula is undefined for very light lifters, where the IPF awards no points
+    if bodyweight < 35.0 {
+        return 0.0;
+    }
+
+    let denominator = a - b * (-c * f64::from(bodyweight)).exp();
+
+    (f64::from(total) * 100.0 / denominator) as f32
+}
+
+/// Calculates the score for a total using the original Wilks formula.
+pub fn wilks(sex: Sex, bodyweight: f32, total: f32) -> f32 {
+    let (coefficients, min, max) = match sex {
+        Sex::Male => (&WILKS_MALE, 40.0, 201.9),
+        Sex::Female => (&WILKS_FEMALE, 26.51, 154.53),
+    };
+
+    let bodyweight = f64::from(bodyweight).clamp(min, max);
+
+    (f64::from(total) * 500.0 / polynomial(coefficients, bodyweight)) as f32
+}
+
+/// Calculates the score for a total using the 2020 revision of the Wilks formula.
+pub fn wilks_2020(sex: Sex, bodyweight: f32, total: f32) -> f32 {
+    let (coefficients, max) = match sex {
+        Sex::Male => (&WILKS_2020_MALE, 200.95),
+        Sex::Female => (&WILKS_2020_FEMALE, 150.95),
+    };
+
+    let bodyweight = f64::from(bodyweight).clamp(40.0, max);
+
+    (f64::from(total) * 600.0 / polynomial(coefficients, bodyweight)) as f32
+}
+
+/// Calculates the Sinclair coefficient for a bodyweight.
+///
+/// Lifters heavier than the world record holder of the heaviest class have a coefficient of 1.
+pub fn sinclair_coefficient(period: SinclairPeriod, sex: Sex, bodyweight: f32) -> f32 {
+    let (a, b) = period.parameters(sex);
+    let bodyweight = f64::from(bodyweight);
+
+    if bodyweight >= b {
+        return 1.0;
+    }
+
+    10f64.powf(a * (bodyweight / b).log10().powi(2)) as f32
+}
+
+/// Calculates the Sinclair score for a weightlifting total.
+pub fn sinclair(period: SinclairPeriod, sex: Sex, bodyweight: f32, total: f32) -> f32 {
+    total * sinclair_coefficient(period, sex, bodyweight)
+}
+
+/// Gets the names of the lifts on a board from the catalogue.
+fn board_lifts(catalogue: &[Lift], board: &str) -> Vec<String> {
+    lift::on_board(catalogue, board)
+        .into_iter()
+        .map(|lift| lift.name)
+        .collect()
+}
+
+/// Sums the lifter's bests for the lifts on a board, if every one of them has been recorded.
+fn total(pb: &PersonalBest, lifts: &[String]) -> Option<f32> {
+    if lifts.is_empty() {
+        return None;
+    }
+
+    lifts.iter().map(|lift| pb.bests.get(lift)).sum()
+}
+
+/// The total and scores for a lifter on one of the boards.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Scores {
+    /// The sum of the lifter's best lifts for the board
+    pub total: Option<f32>,
+    /// The lifter's DOTS score
+    pub dots: Option<f32>,
+    /// The lifter's IPF GL points
+    pub ipf_gl: Option<f32>,
+    /// The lifter's score using the original Wilks formula
+    pub wilks: Option<f32>,
+    /// The lifter's score using the 2020 Wilks formula
+    pub wilks_2020: Option<f32>,
+    /// The lifter's Sinclair score
+    pub sinclair: Option<f32>,
+}
+
+impl Scores {
+    /// Calculates the scores for a lifter's personal bests, given the lifts on the powerlifting
+    /// board.
+    ///
+    /// The total is only calculated if all of the lifts have been recorded, and the scores also
+    /// require the lifter's bodyweight and category.
+    pub fn powerlifting(pb: &PersonalBest, lifts: &[String]) -> Self {
+        let total = total(pb, lifts);
+
+        let (total, sex, bodyweight) = match (total, pb.sex, pb.bodyweight) {
+            (Some(total), Some(sex), Some(bodyweight)) => (total, sex, bodyweight),
+            _ => {
+                return Self {
+                    total,
+                    ..Self::default()
+                }
+            }
+        };
+
+        Self {
+            total: Some(total),
+            dots: Some(dots(sex, bodyweight, total)),
+            ipf_gl: Some(ipf_gl(sex, bodyweight, total)),
+            wilks: Some(wilks(sex, bodyweight, total)),
+            wilks_2020: Some(wilks_2020(sex, bodyweight, total)),
+            sinclair: None,
+        }
+    }
+
+    /// Calculates the scores for a lifter's weightlifting personal bests, given the lifts on the
+    /// weightlifting board.
+    ///
+    /// The total is only calculated if all of the lifts have been recorded, and the Sinclair
+    /// score also requires the lifter's bodyweight and category.
+    pub fn weightlifting(pb: &PersonalBest, lifts: &[String], period: SinclairPeriod) -> Self {
+        let total = total(pb, lifts);
+
+        let sinclair = match (total, pb.sex, pb.bodyweight) {
+            (Some(total), Some(sex), Some(bodyweight)) => {
+                Some(sinclair(period, sex, bodyweight, total))
+            }
+            _ => None,
+        };
+
+        Self {
+            total,
+            sinclair,
+            ..Self::default()
+        }
+    }
+}
+
+/// A lifter on one of the boards, along with their scores.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ScoredLifter {
+    /// The lifter's position on the board, if they have a value for the column it is sorted by
+    pub rank: Option<usize>,
+    /// The lifter's personal bests
+    #[serde(flatten)]
+    pub personal_best: PersonalBest,
+    /// The lifter's total and scores
+    #[serde(flatten)]
+    pub scores: Scores,
+    /// The lifts shown as one rep maxes estimated from sets of multiple reps
+    pub estimated: Vec<String>,
+    /// The lifts on the lifter's board, which make up their total
+    #[serde(skip)]
+    lifts: Vec<String>,
+}
+
+impl ScoredLifter {
+    /// Scores a lifter for the powerlifting board, given the lifts in the catalogue.
+    pub fn powerlifting(personal_best: PersonalBest, catalogue: &[Lift]) -> Self {
+        let lifts = board_lifts(catalogue, POWERLIFTING);
+        let scores = Scores::powerlifting(&personal_best, &lifts);
+
+        Self {
+            rank: None,
+            personal_best,
+            scores,
+            estimated: Vec::new(),
+            lifts,
+        }
+    }
+
+    /// Scores a lifter for the weightlifting board, given the lifts in the catalogue.
+    pub fn weightlifting(
+        personal_best: PersonalBest,
+        catalogue: &[Lift],
+        period: SinclairPeriod,
+    ) -> Self {
+        let lifts = board_lifts(catalogue, WEIGHTLIFTING);
+        let scores = Scores::weightlifting(&personal_best, &lifts, period);
+
+        Self {
+            rank: None,
+            personal_best,
+            scores,
+            estimated: Vec::new(),
+            lifts,
+        }
+    }
+
+    /// Marks the lifts that are shown as estimated one rep maxes.
+    pub fn with_estimated(self, estimated: Vec<String>) -> Self {
+        Self { estimated, ..self }
+    }
+
+    /// Converts the lifter's weights and total from kilograms into the given unit, for displaying
+    /// them. The scores are left as they are.
+    ///
+    /// The total is summed from the converted lifts rather than converted itself, so that it
+    /// always matches the lifts shown beside it after rounding.
+    pub fn in_unit(mut self, unit: Unit) -> Self {
+        self.personal_best = self.personal_best.in_unit(unit);
+        self.scores.total = total(&self.personal_best, &self.lifts);
+
+        self
+    }
+
+    /// Gets the value the lifter is ranked by when a board is sorted a certain way.
+    pub fn value(&self, by: &BoardSort) -> Option<f32> {
+        match by {
+            BoardSort::Lift(lift) => self.personal_best.bests.get(lift).copied(),
+            BoardSort::Total => self.scores.total,
+            BoardSort::Dots => self.scores.dots,
+            BoardSort::IpfGl => self.scores.ipf_gl,
+            BoardSort::Wilks => self.scores.wilks,
+            BoardSort::Wilks2020 => self.scores.wilks_2020,
+            BoardSort::Sinclair => self.scores.sinclair,
+        }
+    }
+}
+
+/// Ranks the lifters on a board from best to worst.
+///
+/// Scores and estimated one rep maxes are calculated after querying the database, so lifters are
+/// sorted again here, keeping the database's order for ties. Lifters with equal values share a
+/// rank, and lifters with no value are placed last without one.
+pub fn rank(lifters: &mut [ScoredLifter], by: &BoardSort) {
+    lifters.sort_by(|a, b| match (a.value(by), b.value(by)) {
+        (Some(a), Some(b)) => b.partial_cmp(&a).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+
+    let mut previous: Option<(f32, usize)> = None;
+
+    for (index, lifter) in lifters.iter_mut().enumerate() {
+        lifter.rank = lifter.value(by).map(|value| match previous {
+            Some((last, rank)) if last == value => rank,
+            _ => {
+                previous = Some((value, index + 1));
+                index + 1
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::lift::original_lifts;
+
+    fn personal_best(bests: &[(&str, f32)]) -> PersonalBest {
+        PersonalBest {
+            bests: bests
+                .iter()
+                .map(|&(lift, weight)| (String::from(lift), weight))
+                .collect(),
+            ..PersonalBest::default()
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 0.01,
+            "expected {} but got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn dots_matches_reference_values() {
+        assert_close(dots(Sex::Male, 100.0, 700.0), 430.86);
+        assert_close(dots(Sex::Male, 82.5, 600.0), 406.44);
+        assert_close(dots(Sex::Female, 60.0, 400.0), 443.42);
+        assert_close(dots(Sex::Female, 52.0, 300.0), 365.67);
+    }
+
+    #[test]
+    fn ipf_gl_matches_reference_values() {
+        assert_close(ipf_gl(Sex::Male, 100.0, 700.0), 88.43);
+        assert_close(ipf_gl(Sex::Male, 82.5, 600.0), 83.31);
+        assert_close(ipf_gl(Sex::Female, 60.0, 400.0), 90.42);
+        assert_close(ipf_gl(Sex::Female, 52.0, 300.0), 75.75);
+        assert_close(ipf_gl(Sex::Female, 30.0, 300.0), 0.0);
+    }
+
+    #[test]
+    fn wilks_matches_reference_values() {
+        // The published coefficient for a 100kg man is 0.6086
+        assert_close(wilks(Sex::Male, 100.0, 1.0) * 1000.0, 608.59);
+        assert_close(wilks(Sex::Male, 100.0, 700.0), 426.01);
+        assert_close(wilks(Sex::Male, 82.5, 600.0), 401.94);
+        assert_close(wilks(Sex::Female, 60.0, 400.0), 445.95);
+        assert_close(wilks(Sex::Female, 52.0, 300.0), 373.99);
+    }
+
+    #[test]
+    fn wilks_2020_matches_reference_values() {
+        assert_close(wilks_2020(Sex::Male, 100.0, 700.0), 510.55);
+        assert_close(wilks_2020(Sex::Male, 82.5, 600.0), 482.66);
+        assert_close(wilks_2020(Sex::Female, 60.0, 400.0), 527.61);
+        assert_close(wilks_2020(Sex::Female, 52.0, 300.0), 438.75);
+    }
+
+    #[test]
+    fn bodyweights_are_clamped_to_the_fitted_range() {
+        assert_close(dots(Sex::Male, 250.0, 900.0), dots(Sex::Male, 210.0, 900.0));
+        assert_close(wilks(Sex::Male, 250.0, 900.0), 478.35);
+        assert_close(
+            wilks_2020(Sex::Female, 30.0, 200.0),
+            wilks_2020(Sex::Female, 40.0, 200.0),
+        );
+    }
+
+    #[test]
+    fn scores_require_every_lift_and_bodyweight() {
+        let lifts = board_lifts(&original_lifts(), POWERLIFTING);
+        let mut pb = personal_best(&[("squat", 250.0), ("bench", 150.0), ("deadlift", 300.0)]);
+
+        let scores = Scores::powerlifting(&pb, &lifts);
+        assert_eq!(scores.total, Some(700.0));
+        assert_eq!(scores.dots, None);
+
+        pb.sex = Some(Sex::Male);
+        pb.bodyweight = Some(100.0);
+
+        let scores = Scores::powerlifting(&pb, &lifts);
+        assert_close(scores.dots.unwrap(), 430.86);
+        assert_close(scores.ipf_gl.unwrap(), 88.43);
+
+        pb.bests.remove("bench");
+        assert_eq!(Scores::powerlifting(&pb, &lifts), Scores::default());
+    }
+
+    #[test]
+    fn totals_only_include_the_lifts_on_the_board() {
+        let pb = personal_best(&[
+            ("squat", 250.0),
+            ("snatch", 100.0),
+            ("clean_and_jerk", 130.0),
+        ]);
+        let lifter = ScoredLifter::weightlifting(pb, &original_lifts(), SinclairPeriod::default());
+
+        assert_eq!(lifter.scores.total, Some(230.0));
+    }
+
+    #[test]
+    fn sinclair_matches_reference_values() {
+        let period = SinclairPeriod::Cycle2021To2024;
+
+        assert_close(sinclair_coefficient(period, Sex::Male, 81.0), 1.2691);
+        assert_close(sinclair(period, Sex::Male, 81.0, 350.0), 444.20);
+        assert_close(sinclair(period, Sex::Female, 64.0, 220.0), 286.06);
+        assert_close(sinclair(period, Sex::Male, 200.0, 400.0), 400.0);
+    }
+
+    #[test]
+    fn sinclair_depends_on_the_period() {
+        let period = SinclairPeriod::Cycle2017To2020;
+
+        assert_close(sinclair(period, Sex::Male, 81.0, 350.0), 425.47);
+        assert_close(sinclair(period, Sex::Female, 64.0, 220.0), 285.61);
+    }
+
+    #[test]
+    fn sinclair_periods_can_be_parsed() {
+        assert_eq!(
+            "2017-2020".parse::<SinclairPeriod>(),
+            Ok(SinclairPeriod::Cycle2017To2020)
+        );
+        assert!("2030-2033".parse::<SinclairPeriod>().is_err());
+    }
+
+    fn weightlifter(bodyweight: f32, snatch: f32, clean_and_jerk: Option<f32>) -> ScoredLifter {
+        let mut pb = personal_best(&[("snatch", snatch)]);
+        pb.sex = Some(Sex::Male);
+        pb.bodyweight = Some(bodyweight);
+
+        if let Some(clean_and_jerk) = clean_and_jerk {
+            pb.bests
+                .insert(String::from("clean_and_jerk"), clean_and_jerk);
+        }
+
+        ScoredLifter::weightlifting(pb, &original_lifts(), SinclairPeriod::default())
+    }
+
+    #[test]
+    fn weightlifters_can_be_ranked_by_sinclair() {
+        let mut lifters = vec![
+            weightlifter(110.0, 100.0, Some(130.0)),
+            weightlifter(90.0, 120.0, None),
+            weightlifter(60.0, 80.0, Some(110.0)),
+        ];
+
+        // The lighter lifter has a much larger coefficient
+        rank(&mut lifters, &BoardSort::Sinclair);
+        let totals: Vec<_> = lifters.iter().map(|l| l.scores.total).collect();
+        let ranks: Vec<_> = lifters.iter().map(|l| l.rank).collect();
+
+        assert_eq!(totals, vec![Some(190.0), Some(230.0), None]);
+        assert_eq!(ranks, vec![Some(1), Some(2), None]);
+    }
+
+    #[test]
+    fn lifts_are_ranked_in_the_order_given() {
+        let mut lifters = vec![
+            weightlifter(110.0, 100.0, Some(130.0)),
+            weightlifter(60.0, 80.0, Some(110.0)),
+        ];
+
+        rank(
+            &mut lifters,
+            &BoardSort::Lift(String::from("clean_and_jerk")),
+        );
+        let snatches: Vec<_> = lifters
+            .iter()
+            .map(|l| l.personal_best.bests["snatch"])
+            .collect();
+
+        assert_eq!(snatches, vec![100.0, 80.0]);
+        assert_eq!(lifters[1].rank, Some(2));
+    }
+
+    #[test]
+    fn converted_totals_match_the_converted_lifts() {
+        let pb = personal_best(&[("squat", 100.03), ("bench", 60.03), ("deadlift", 120.03)]);
+
+        let lifter = ScoredLifter::powerlifting(pb, &original_lifts()).in_unit(Unit::Lb);
+        let lifts = lifter.personal_best.bests.values().sum();
+
+        assert_eq!(lifter.scores.total, Some(lifts));
+    }
+
+    #[test]
+    fn equal_values_share_a_rank() {
+        let mut lifters = vec![
+            weightlifter(80.0, 100.0, Some(120.0)),
+            weightlifter(70.0, 100.0, Some(120.0)),
+            weightlifter(90.0, 90.0, Some(120.0)),
+        ];
+
+        rank(&mut lifters, &BoardSort::Lift(String::from("snatch")));
+        let ranks: Vec<_> = lifters.iter().map(|l| l.rank).collect();
+
+        assert_eq!(ranks, vec![Some(1), Some(1), Some(3)]);
+    }
+}
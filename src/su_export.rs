@@ -0,0 +1,147 @@
+//! Parses the membership export provided by the students' union.
+//!
+//! The export is a CSV file with a header row. Only the columns containing the member's University
+//! ID and their membership type are used, and these are found by name so that the column order
+//! and any additional columns do not matter.
+
+use csv::StringRecord;
+
+/// The accepted names for the column containing a member's University ID.
+const ID_HEADERS: [&str; 4] = ["university id", "student id", "warwick id", "card number"];
+
+/// The accepted names for the column containing the type of membership.
+const TYPE_HEADERS: [&str; 4] = ["membership type", "type", "product name", "membership"];
+
+/// A single member listed in the export.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExportedMember {
+    /// The member's Warwick ID.
+    pub warwick_id: i32,
+    /// The type of membership they purchased.
+    pub membership_type: String,
+}
+
+/// The result of parsing an export.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Export {
+    /// The members that were parsed successfully.
+    pub members: Vec<ExportedMember>,
+    /// The number of rows that did not contain a valid Warwick ID.
+    pub skipped: usize,
+}
+
+fn find_column(headers: &StringRecord, names: &[&str]) -> Option<usize> {
+    headers
+        .iter()
+        .position(|header| names.contains(&header.trim().to_lowercase().as_str()))
+}
+
+/// Parses a Warwick ID, allowing for the `u` prefix used in usernames.
+fn parse_warwick_id(value: &str) -> Option<i32> {
+    let value = value.trim().trim_start_matches(['u', 'U']);
+
+    if !(value.chars().all(|c| c.is_ascii_digit()) && value.len() == 7) {
+        return None;
+    }
+
+    value.parse().ok()
+}
+
+/// Parses the contents of an export, returning an error message if it is malformed.
+pub fn parse(contents: &str) -> Result<Export, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(contents.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Failed to read the header row: {}", e))?
+        .clone();
+
+    let id_column = find_column(&headers, &ID_HEADERS)
+        .ok_or_else(|| String::from("Failed to find a University ID column in the export"))?;
+
+    let type_column = find_column(&headers, &TYPE_HEADERS);
+
+    let mut export = Export::default();
+
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("Failed to read a row: {}", e))?;
+
+        let warwick_id = match record.get(id_column).and_then(parse_warwick_id) {
+            Some(warwick_id) => warwick_id,
+            None => {
+                export.skipped += 1;
+                continue;
+            }
+        };
+
+        let membership_type = type_column
+            .and_then(|column| record.get(column))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .unwrap_or("Standard")
+            .to_string();
+
+        export.members.push(ExportedMember {
+            warwick_id,
+            membership_type,
+        });
+    }
+
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(warwick_id: i32, membership_type: &str) -> ExportedMember {
+        ExportedMember {
+            warwick_id,
+            membership_type: String::from(membership_type),
+        }
+    }
+
+    #[test]
+    fn exports_can_be_parsed() {
+        let contents = "First Name,Last Name,Card Number,Type\n\
+                        Alex,Jackson,1702502,Standard\n\
+                        Dan,Smith,u1820900,Competitive\n";
+
+        let expected = Export {
+            members: vec![member(1702502, "Standard"), member(1820900, "Competitive")],
+            skipped: 0,
+        };
+
+        assert_eq!(parse(contents), Ok(expected));
+    }
+
+    #[test]
+    fn rows_without_valid_identifiers_are_skipped() {
+        let contents = "University ID,Membership Type\n\
+                        1702502,Standard\n\
+                        170250,Standard\n\
+                        ,Standard\n";
+
+        let export = parse(contents).unwrap();
+
+        assert_eq!(export.members, vec![member(1702502, "Standard")]);
+        assert_eq!(export.skipped, 2);
+    }
+
+    #[test]
+    fn missing_membership_types_use_a_default() {
+        let contents = "Student ID\n1702502\n";
+        let export = parse(contents).unwrap();
+
+        assert_eq!(export.members, vec![member(1702502, "Standard")]);
+    }
+
+    #[test]
+    fn exports_without_identifiers_are_rejected() {
+        let contents = "First Name,Last Name\nAlex,Jackson\n";
+
+        assert!(parse(contents).is_err());
+    }
+}
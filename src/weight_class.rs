@@ -0,0 +1,73 @@
+//! Defines the weight classes lifters can compete in.
+//!
+//! These follow the classes used by the IPF, which British Powerlifting and the students' union
+//! competitions also use.
+
+use crate::schema::custom_types::Sex;
+
+/// The weight classes for the male category, as upper limits in kilograms.
+const MALE_LIMITS: [f32; 7] = [59.0, 66.0, 74.0, 83.0, 93.0, 105.0, 120.0];
+
+/// The weight classes for the female category, as upper limits in kilograms.
+const FEMALE_LIMITS: [f32; 7] = [47.0, 52.0, 57.0, 63.0, 69.0, 76.0, 84.0];
+
+fn limits(sex: Sex) -> &'static [f32] {
+    match sex {
+        Sex::Male => &MALE_LIMITS,
+        Sex::Female => &FEMALE_LIMITS,
+    }
+}
+
+/// Gets the names of the weight classes for a category, from lightest to heaviest.
+pub fn classes(sex: Sex) -> Vec<String> {
+    let limits = limits(sex);
+    let heaviest = limits[limits.len() - 1];
+
+    limits
+        .iter()
+        .map(|limit| format!("{}", limit))
+        .chain(std::iter::once(format!("{}+", heaviest)))
+        .collect()
+}
+
+/// Checks whether a weight class exists for a category.
+pub fn is_valid(sex: Sex, class: &str) -> bool {
+    classes(sex).iter().any(|c| c == class)
+}
+
+/// Gets the weight class a lifter of a given bodyweight would compete in.
+pub fn for_bodyweight(sex: Sex, bodyweight: f32) -> String {
+    let limits = limits(sex);
+
+    match limits.iter().find(|limit| bodyweight <= **limit) {
+        Some(limit) => format!("{}", limit),
+        None => format!("{}+", limits[limits.len() - 1]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classes_are_listed_in_order() {
+        let expected = vec!["47", "52", "57", "63", "69", "76", "84", "84+"];
+
+        assert_eq!(classes(Sex::Female), expected);
+    }
+
+    #[test]
+    fn classes_depend_on_the_category() {
+        assert!(is_valid(Sex::Male, "120+"));
+        assert!(!is_valid(Sex::Female, "120+"));
+        assert!(!is_valid(Sex::Male, "heavy"));
+    }
+
+    #[test]
+    fn bodyweights_are_placed_in_the_correct_class() {
+        assert_eq!(for_bodyweight(Sex::Male, 83.0), "83");
+        assert_eq!(for_bodyweight(Sex::Male, 83.1), "93");
+        assert_eq!(for_bodyweight(Sex::Male, 130.0), "120+");
+        assert_eq!(for_bodyweight(Sex::Female, 45.0), "47");
+    }
+}
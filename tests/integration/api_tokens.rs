@@ -0,0 +1,52 @@
+use crate::{cleanup_database, create_database};
+
+use blackboards::schema::ApiToken;
+
+#[tokio::test]
+async fn tokens_can_be_used_to_authenticate() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let scopes = vec![String::from("member")];
+    let token = ApiToken::create(1702502, "Alex", "script", &scopes, &mut conn).await?;
+
+    let owner = ApiToken::authenticate(&token, &mut conn).await?.unwrap();
+
+    assert_eq!(owner.warwick_id, 1702502);
+    assert_eq!(owner.name, "Alex");
+    assert_eq!(owner.scopes, scopes);
+
+    // Using the token should be recorded
+    let tokens = ApiToken::for_user(1702502, &mut conn).await?;
+    assert_eq!(tokens.len(), 1);
+    assert!(tokens[0].last_used.is_some());
+
+    // Unknown tokens should not authenticate
+    assert!(ApiToken::authenticate("bb_unknown", &mut conn)
+        .await?
+        .is_none());
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn revoked_tokens_cannot_be_used() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let token = ApiToken::create(1702502, "Alex", "script", &[], &mut conn).await?;
+    let id = ApiToken::for_user(1702502, &mut conn).await?[0].id;
+
+    // Other users should not be able to revoke the token
+    assert!(!ApiToken::revoke(id, 1820900, &mut conn).await?);
+    assert!(ApiToken::revoke(id, 1702502, &mut conn).await?);
+
+    assert!(ApiToken::authenticate(&token, &mut conn).await?.is_none());
+    assert!(ApiToken::for_user(1702502, &mut conn).await?.is_empty());
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
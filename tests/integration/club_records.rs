@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use crate::{cleanup_database, create_database};
+
+use blackboards::forms::PersonalBests;
+use blackboards::schema::{club_record, custom_types, ClubRecord, Lift, LiftRecord, PersonalBest};
+
+fn squat(weight: f32, weight_class: &str) -> PersonalBests {
+    PersonalBests {
+        lifts: BTreeMap::from([(String::from("squat"), Some(weight))]),
+        show_pl: true,
+        show_wl: false,
+        show_other: false,
+        bodyweight: None,
+        bodyweight_recorded_on: None,
+        sex: Some(custom_types::Sex::Male),
+        weight_class: Some(String::from(weight_class)),
+        unit: None,
+        formula: None,
+    }
+}
+
+#[tokio::test]
+async fn records_are_kept_in_the_class_they_were_set_in() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    PersonalBest::update(1, String::from("Dan"), squat(200.0, "83"), &mut conn).await?;
+    PersonalBest::update(2, String::from("James"), squat(190.0, "83"), &mut conn).await?;
+
+    // Moving up a class and leaving the boards keeps the old record
+    let mut data = squat(210.0, "93");
+    data.show_pl = false;
+    PersonalBest::update(1, String::from("Dan"), data, &mut conn).await?;
+
+    let lifts = Lift::get_results(&mut conn).await?;
+    let history = ClubRecord::get_results(&mut conn).await?;
+    let grouped = club_record::group(&lifts, history);
+
+    assert_eq!(grouped.len(), 1);
+    assert_eq!(grouped[0].lift.name, "squat");
+
+    let records: Vec<_> = grouped[0]
+        .records
+        .iter()
+        .map(|r| {
+            (
+                r.weight_class.as_str(),
+                r.current.warwick_id,
+                r.current.weight,
+            )
+        })
+        .collect();
+
+    assert_eq!(records, vec![("83", 1, 200.0), ("93", 1, 210.0)]);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn beating_a_record_keeps_the_previous_holder() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    PersonalBest::update(1, String::from("Dan"), squat(200.0, "83"), &mut conn).await?;
+    PersonalBest::update(2, String::from("James"), squat(205.0, "83"), &mut conn).await?;
+    PersonalBest::update(1, String::from("Dan"), squat(205.0, "83"), &mut conn).await?;
+
+    let lifts = Lift::get_results(&mut conn).await?;
+    let history = ClubRecord::get_results(&mut conn).await?;
+    let record = &club_record::group(&lifts, history)[0].records[0];
+
+    // Equalling the record does not take it
+    assert_eq!(record.current.name.as_deref(), Some("James"));
+    assert_eq!(record.previous.len(), 1);
+    assert_eq!(record.previous[0].name.as_deref(), Some("Dan"));
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn backdated_lifts_use_the_class_on_the_day_they_were_performed() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    PersonalBest::update(1, String::from("Dan"), squat(200.0, "83"), &mut conn).await?;
+
+    // Dan had not chosen a class before today, so the older squat is not a record
+    let day = chrono::NaiveDate::from_ymd_opt(2022, 10, 1).unwrap();
+    LiftRecord::single(1, "squat", 230.0, custom_types::Date(day))
+        .insert(&mut conn)
+        .await?;
+
+    let lifts = Lift::get_results(&mut conn).await?;
+    let history = ClubRecord::get_results(&mut conn).await?;
+    let grouped = club_record::group(&lifts, history);
+    let records: Vec<_> = grouped[0]
+        .records
+        .iter()
+        .map(|r| (r.weight_class.as_str(), r.current.weight))
+        .collect();
+
+    assert_eq!(records, vec![("83", 200.0)]);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use chrono::NaiveDate;
+use rocket::http::{ContentType, Cookie, Header, Status};
+use rocket::local::asynchronous::{Client, LocalResponse};
+
+use crate::{cleanup_database, create_client, create_database};
+
+use blackboards::schema::custom_types::{CountingMethod, TieBreak};
+use blackboards::schema::{ApiToken, Election, ExecPosition, PersonalBest, UserRole};
+
+/// The token stored in the signed in user's cookie.
+const CSRF_TOKEN: &str = "expected-token";
+
+/// Submits a form as Dan, who has the CSRF token cookie, along with the given token if any.
+async fn submit<'c>(
+    client: &'c Client,
+    uri: &'static str,
+    fields: &str,
+    token: Option<&str>,
+) -> LocalResponse<'c> {
+    let body = match token {
+        Some(token) => format!("csrf_token={}&{}", token, fields),
+        None => String::from(fields),
+    };
+
+    client
+        .post(uri)
+        .header(ContentType::Form)
+        .private_cookie(Cookie::new("id", "1"))
+        .private_cookie(Cookie::new("name", "Dan"))
+        .private_cookie(Cookie::new("csrf_token", CSRF_TOKEN))
+        .body(body)
+        .dispatch()
+        .await
+}
+
+/// Starts an election with a single position, returning the position's identifier.
+async fn start_election(conn: &mut blackboards::schema::Pool) -> sqlx::Result<i32> {
+    let held_on = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+    let election_id = Election::create("2023 AGM", held_on, conn).await?;
+
+    ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        true,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        conn,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn votes_need_a_matching_token() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    UserRole::grant(None, 1, "member", &mut conn).await?;
+
+    let client = create_client(uuid).await;
+    let uri = "/election/vote/999";
+
+    let response = submit(&client, uri, "1=2", None).await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let response = submit(&client, uri, "1=2", Some("wrong-token")).await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    // The vote reaches the route, which turns it away as voting isn't open
+    let response = submit(&client, uri, "1=2", Some(CSRF_TOKEN)).await;
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/elections"));
+
+    drop(client);
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn toggling_voting_needs_a_matching_token() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    UserRole::grant(None, 1, "election_admin", &mut conn).await?;
+    let position_id = start_election(&mut conn).await?;
+
+    let client = create_client(uuid).await;
+    let uri = "/elections/settings/toggle";
+    let fields = format!("position_id={}", position_id);
+
+    let response = submit(&client, uri, &fields, None).await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let response = submit(&client, uri, &fields, Some("wrong-token")).await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let position = ExecPosition::find(position_id, &mut conn).await?.unwrap();
+    assert!(!position.open);
+
+    let response = submit(&client, uri, &fields, Some(CSRF_TOKEN)).await;
+    assert_eq!(response.status(), Status::SeeOther);
+
+    let position = ExecPosition::find(position_id, &mut conn).await?.unwrap();
+    assert!(position.open);
+
+    drop(client);
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn personal_bests_need_a_matching_token() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    UserRole::grant(None, 1, "member", &mut conn).await?;
+
+    let client = create_client(uuid).await;
+    let uri = "/pbs";
+    let fields = "lifts[squat]=200&show_pl=true&show_wl=true&show_other=true";
+
+    let response = submit(&client, uri, fields, None).await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let response = submit(&client, uri, fields, Some("wrong-token")).await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let bests = PersonalBest::find(1, "Dan", &mut conn).await?;
+    assert_eq!(bests.bests.get("squat"), Some(&180.0));
+
+    let response = submit(&client, uri, fields, Some(CSRF_TOKEN)).await;
+    assert_eq!(response.status(), Status::SeeOther);
+
+    let bests = PersonalBest::find(1, "Dan", &mut conn).await?;
+    assert_eq!(bests.bests.get("squat"), Some(&200.0));
+
+    drop(client);
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_accepted_api_tokens_skip_the_check() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    UserRole::grant(None, 1, "election_admin", &mut conn).await?;
+    let position_id = start_election(&mut conn).await?;
+
+    let scopes = vec![String::from("election_admin")];
+    let token = ApiToken::create(1, "Dan", "script", &scopes, &mut conn).await?;
+
+    let client = create_client(uuid).await;
+    let toggle = |authorization: String| {
+        client
+            .post("/elections/settings/toggle")
+            .header(ContentType::Form)
+            .header(Header::new("Authorization", authorization))
+            .body(format!("position_id={}", position_id))
+            .dispatch()
+    };
+
+    // Unknown tokens are sent to sign in without the form being handled
+    let response = toggle(String::from("Bearer bb_unknown")).await;
+    assert_eq!(response.status(), Status::SeeOther);
+    assert!(response
+        .headers()
+        .get_one("Location")
+        .unwrap()
+        .starts_with("/authenticate"));
+
+    let position = ExecPosition::find(position_id, &mut conn).await?.unwrap();
+    assert!(!position.open);
+
+    // Accepted tokens don't need a CSRF token, as browsers never send them by themselves
+    let response = toggle(format!("Bearer {}", token)).await;
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(
+        response.headers().get_one("Location"),
+        Some("/elections/settings")
+    );
+
+    let position = ExecPosition::find(position_id, &mut conn).await?.unwrap();
+    assert!(position.open);
+
+    drop(client);
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
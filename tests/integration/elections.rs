@@ -0,0 +1,967 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use itertools::Itertools;
+use rocket::http::{ContentType, Cookie, Status};
+use sha2::{Digest, Sha256};
+
+use crate::{cleanup_database, create_client, create_database};
+
+use blackboards::schema::custom_types::{CountingMethod, TieBreak};
+use blackboards::schema::exec_position::{RON_ID, RON_NAME};
+use blackboards::schema::{Candidate, Election, ExecPosition, Nomination, UserRole, Vote};
+
+fn candidate(election_id: i32, warwick_id: i32, name: &str) -> Candidate {
+    Candidate {
+        warwick_id,
+        name: String::from(name),
+        elected: false,
+        election_id,
+    }
+}
+
+async fn start_election(conn: &mut blackboards::schema::Pool) -> sqlx::Result<i32> {
+    let held_on = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+
+    Election::create("2023 AGM", held_on, conn).await
+}
+
+#[tokio::test]
+async fn the_previous_election_is_archived() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    assert!(Election::current(&mut conn).await?.is_none());
+
+    let archived = Election::archived(&mut conn).await?;
+    let positions = ExecPosition::for_election(archived[0].id, &mut conn).await?;
+
+    assert_eq!(archived.len(), 1);
+    assert_eq!(archived[0].name, "2022 AGM");
+    assert_eq!(positions.len(), 8);
+    assert!(positions.iter().all(|position| !position.open));
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn elections_can_be_set_up_from_scratch() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let election_id = start_election(&mut conn).await?;
+    assert!(ExecPosition::for_election(election_id, &mut conn)
+        .await?
+        .is_empty());
+
+    let position_id = ExecPosition::create(
+        election_id,
+        "Treasurer",
+        1,
+        false,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+    assert!(
+        ExecPosition::update(
+            position_id,
+            "Treasurers",
+            2,
+            false,
+            CountingMethod::Meek,
+            TieBreak::Backwards,
+            &mut conn
+        )
+        .await?
+    );
+
+    candidate(election_id, 1702502, "Alex")
+        .insert(&mut conn)
+        .await?;
+    candidate(election_id, 1700000, "Bea")
+        .insert(&mut conn)
+        .await?;
+
+    for warwick_id in [1702502, 1700000] {
+        let nomination = Nomination {
+            position_id,
+            warwick_id,
+        };
+
+        assert!(nomination.insert(&mut conn).await?);
+    }
+
+    let positions = ExecPosition::for_election(election_id, &mut conn).await?;
+    assert_eq!(positions.len(), 1);
+    assert_eq!(positions[0].title, "Treasurers");
+    assert_eq!(positions[0].num_winners, 2);
+    assert_eq!(positions[0].counting_method, CountingMethod::Meek);
+    assert!(!positions[0].open);
+
+    let names: Vec<_> = Nomination::for_election_with_names(election_id, &mut conn)
+        .await?
+        .into_iter()
+        .map(|nomination| nomination.name)
+        .collect();
+
+    assert_eq!(names, vec!["Alex", "Bea"]);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_one_election_can_be_run_at_a_time() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    start_election(&mut conn).await?;
+    assert!(start_election(&mut conn).await.is_err());
+
+    assert!(Election::archive_current(&mut conn).await?);
+    assert!(!Election::archive_current(&mut conn).await?);
+    assert!(start_election(&mut conn).await.is_ok());
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn candidates_can_stand_in_later_elections() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let first = start_election(&mut conn).await?;
+    candidate(first, 1702502, "Alex").insert(&mut conn).await?;
+    Election::archive_current(&mut conn).await?;
+
+    let second = start_election(&mut conn).await?;
+    candidate(second, 1702502, "Alex").insert(&mut conn).await?;
+
+    assert_eq!(Candidate::for_election(first, &mut conn).await?.len(), 1);
+    assert_eq!(Candidate::for_election(second, &mut conn).await?.len(), 1);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archived_elections_cannot_be_changed() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let election_id = start_election(&mut conn).await?;
+    let position_id = ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        false,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+
+    candidate(election_id, 1702502, "Alex")
+        .insert(&mut conn)
+        .await?;
+    candidate(election_id, 1700000, "Bea")
+        .insert(&mut conn)
+        .await?;
+
+    let nomination = Nomination {
+        position_id,
+        warwick_id: 1702502,
+    };
+
+    nomination.insert(&mut conn).await?;
+    assert!(ExecPosition::toggle_state(position_id, &mut conn).await?);
+    assert!(ExecPosition::voting_is_open(position_id, &mut conn).await);
+
+    Election::archive_current(&mut conn).await?;
+
+    let late = Nomination {
+        position_id,
+        warwick_id: 1700000,
+    };
+
+    assert!(!ExecPosition::voting_is_open(position_id, &mut conn).await);
+    assert!(!ExecPosition::toggle_state(position_id, &mut conn).await?);
+    assert!(
+        !ExecPosition::update(
+            position_id,
+            "Chair",
+            1,
+            false,
+            CountingMethod::Irv,
+            TieBreak::Backwards,
+            &mut conn
+        )
+        .await?
+    );
+    assert!(!late.insert(&mut conn).await?);
+    assert!(!nomination.delete(&mut conn).await?);
+    assert!(!Candidate::delete(1700000, &mut conn).await?);
+    assert!(!ExecPosition::delete(position_id, &mut conn).await?);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn removing_a_position_or_candidate_removes_their_nominations() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let election_id = start_election(&mut conn).await?;
+
+    let first = ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        false,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+    let second = ExecPosition::create(
+        election_id,
+        "Secretary",
+        1,
+        false,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+
+    candidate(election_id, 1702502, "Alex")
+        .insert(&mut conn)
+        .await?;
+    candidate(election_id, 1700000, "Bea")
+        .insert(&mut conn)
+        .await?;
+
+    for (position_id, warwick_id) in [(first, 1702502), (second, 1702502), (second, 1700000)] {
+        Nomination {
+            position_id,
+            warwick_id,
+        }
+        .insert(&mut conn)
+        .await?;
+    }
+
+    assert!(ExecPosition::delete(first, &mut conn).await?);
+    assert!(Candidate::delete(1700000, &mut conn).await?);
+
+    let nominations: Vec<_> = Nomination::for_election_with_names(election_id, &mut conn)
+        .await?
+        .into_iter()
+        .map(|nomination| (nomination.position_id, nomination.warwick_id))
+        .collect();
+
+    assert_eq!(nominations, vec![(second, 1702502)]);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn anything_that_has_been_voted_for_is_kept() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let election_id = start_election(&mut conn).await?;
+    let position_id = ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        false,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+
+    candidate(election_id, 1702502, "Alex")
+        .insert(&mut conn)
+        .await?;
+
+    let nomination = Nomination {
+        position_id,
+        warwick_id: 1702502,
+    };
+
+    nomination.insert(&mut conn).await?;
+
+    let ballot = HashMap::from([(1, 1702502)]);
+    Vote::cast(1, position_id, None, &ballot, &mut conn).await?;
+
+    assert!(!nomination.delete(&mut conn).await?);
+    assert!(!Candidate::delete(1702502, &mut conn).await?);
+    assert!(!ExecPosition::delete(position_id, &mut conn).await?);
+    assert_eq!(
+        ExecPosition::voted_identifiers(election_id, &mut conn).await?,
+        vec![position_id]
+    );
+    assert_eq!(Vote::for_election(election_id, &mut conn).await?.len(), 1);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ron_is_added_to_the_ballot_when_offered() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let election_id = start_election(&mut conn).await?;
+    let with_ron = ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        true,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+    let without_ron = ExecPosition::create(
+        election_id,
+        "Secretary",
+        1,
+        false,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+
+    candidate(election_id, 1702502, "Alex")
+        .insert(&mut conn)
+        .await?;
+
+    for position_id in [with_ron, without_ron] {
+        Nomination {
+            position_id,
+            warwick_id: 1702502,
+        }
+        .insert(&mut conn)
+        .await?;
+    }
+
+    let ballot: Vec<_> = Nomination::for_position_with_names(with_ron, &mut conn)
+        .await?
+        .into_iter()
+        .map(|nomination| (nomination.warwick_id, nomination.name))
+        .collect();
+
+    assert_eq!(
+        ballot,
+        vec![
+            (1702502, String::from("Alex")),
+            (RON_ID, String::from(RON_NAME))
+        ]
+    );
+    assert_eq!(
+        Nomination::for_position_with_names(without_ron, &mut conn)
+            .await?
+            .len(),
+        1
+    );
+
+    // Votes for RON are shown in the voter's current ballot
+    let votes = HashMap::from([(1, RON_ID), (2, 1702502)]);
+    ExecPosition::toggle_state(with_ron, &mut conn).await?;
+    let receipt = Vote::cast(1, with_ron, None, &votes, &mut conn)
+        .await?
+        .unwrap();
+
+    assert_eq!(
+        Vote::get_current_ballot(&receipt.secret, with_ron, &mut conn).await?,
+        Some(vec![String::from(RON_NAME), String::from("Alex")])
+    );
+
+    // Offering RON cannot change once the position has been voted for
+    assert!(
+        !ExecPosition::update(
+            with_ron,
+            "President",
+            1,
+            false,
+            CountingMethod::Irv,
+            TieBreak::Backwards,
+            &mut conn
+        )
+        .await?
+    );
+    assert!(
+        ExecPosition::update(
+            with_ron,
+            "Chair",
+            1,
+            true,
+            CountingMethod::Irv,
+            TieBreak::Backwards,
+            &mut conn
+        )
+        .await?
+    );
+
+    // Neither can the number of winners, the counting method or the candidates
+    assert!(
+        !ExecPosition::update(
+            with_ron,
+            "Chair",
+            2,
+            true,
+            CountingMethod::Irv,
+            TieBreak::Backwards,
+            &mut conn
+        )
+        .await?
+    );
+    assert!(
+        !ExecPosition::update(
+            with_ron,
+            "Chair",
+            1,
+            true,
+            CountingMethod::Approval,
+            TieBreak::Backwards,
+            &mut conn
+        )
+        .await?
+    );
+
+    candidate(election_id, 1700000, "Bea")
+        .insert(&mut conn)
+        .await?;
+
+    let late = |position_id| Nomination {
+        position_id,
+        warwick_id: 1700000,
+    };
+
+    assert!(!late(with_ron).insert(&mut conn).await?);
+
+    // Candidates can only be nominated while voting is closed
+    ExecPosition::toggle_state(without_ron, &mut conn).await?;
+    assert!(!late(without_ron).insert(&mut conn).await?);
+
+    ExecPosition::toggle_state(without_ron, &mut conn).await?;
+    assert!(late(without_ron).insert(&mut conn).await?);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ron_candidates_from_previous_elections_are_replaced() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let archived = Election::archived(&mut conn).await?;
+    let candidates = Candidate::for_election(archived[0].id, &mut conn).await?;
+    let positions = ExecPosition::for_election(archived[0].id, &mut conn).await?;
+
+    assert!(candidates.iter().all(|candidate| candidate.name != "RON"));
+    assert!(positions.iter().all(|position| position.ron));
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn voting_follows_the_schedule() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let election_id = start_election(&mut conn).await?;
+    let position_id = ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        true,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+    let now = chrono::Utc::now().timestamp();
+
+    // Scheduled to open later on
+    ExecPosition::schedule(position_id, Some(now + 3600), Some(now + 7200), &mut conn).await?;
+    assert!(!ExecPosition::voting_is_open(position_id, &mut conn).await);
+
+    // Opened automatically
+    ExecPosition::schedule(position_id, Some(now - 60), Some(now + 3600), &mut conn).await?;
+    assert!(ExecPosition::voting_is_open(position_id, &mut conn).await);
+
+    let positions = ExecPosition::for_election(election_id, &mut conn).await?;
+    assert!(positions[0].open);
+    assert!(!positions[0].has_closed(now));
+
+    // Closed automatically, even after being opened by hand
+    ExecPosition::toggle_state(position_id, &mut conn).await?;
+    ExecPosition::toggle_state(position_id, &mut conn).await?;
+    ExecPosition::schedule(position_id, None, Some(now - 60), &mut conn).await?;
+    assert!(!ExecPosition::voting_is_open(position_id, &mut conn).await);
+
+    let positions = ExecPosition::for_election(election_id, &mut conn).await?;
+    assert!(positions[0].has_closed(now));
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn toggling_voting_by_hand_removes_the_schedule() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let election_id = start_election(&mut conn).await?;
+    let position_id = ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        true,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+    let now = chrono::Utc::now().timestamp();
+
+    ExecPosition::schedule(position_id, Some(now - 60), Some(now + 3600), &mut conn).await?;
+
+    // Closing by hand while the schedule has it open
+    ExecPosition::toggle_state(position_id, &mut conn).await?;
+    assert!(!ExecPosition::voting_is_open(position_id, &mut conn).await);
+
+    let positions = ExecPosition::for_election(election_id, &mut conn).await?;
+    assert_eq!(positions[0].opens_at, None);
+    assert_eq!(positions[0].closes_at, None);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ballots_can_only_be_replaced_with_their_secret() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let election_id = start_election(&mut conn).await?;
+    let position_id = ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        true,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+
+    candidate(election_id, 1702502, "Alex")
+        .insert(&mut conn)
+        .await?;
+
+    let first = HashMap::from([(1, 1702502), (2, RON_ID)]);
+    let second = HashMap::from([(1, RON_ID), (2, 1702502)]);
+
+    assert!(!Vote::has_voted(1, position_id, &mut conn).await?);
+
+    let first_receipt = Vote::cast(1, position_id, None, &first, &mut conn)
+        .await?
+        .unwrap();
+    let secret = first_receipt.secret.as_str();
+
+    assert!(Vote::has_voted(1, position_id, &mut conn).await?);
+
+    // Voting again without the secret changes nothing
+    assert_eq!(
+        Vote::cast(1, position_id, None, &second, &mut conn).await?,
+        None
+    );
+    assert_eq!(
+        Vote::get_current_ballot(secret, position_id, &mut conn).await?,
+        Some(vec![String::from("Alex"), String::from(RON_NAME)])
+    );
+
+    // The secret replaces the same ballot rather than adding another, with a new receipt code
+    let second_receipt = Vote::cast(1, position_id, Some(secret), &second, &mut conn)
+        .await?
+        .unwrap();
+
+    assert_eq!(second_receipt.secret, secret);
+    assert_ne!(second_receipt.code, first_receipt.code);
+    assert_eq!(
+        Vote::get_current_ballot(secret, position_id, &mut conn).await?,
+        Some(vec![String::from(RON_NAME), String::from("Alex")])
+    );
+
+    let votes = Vote::for_election(election_id, &mut conn).await?;
+    assert_eq!(votes.len(), 2);
+    assert!(votes
+        .iter()
+        .all(|vote| vote.ballot_id == votes[0].ballot_id));
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ballots_can_be_cast_at_the_same_time() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let election_id = start_election(&mut conn).await?;
+    let position_id = ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        true,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+
+    candidate(election_id, 1702502, "Alex")
+        .insert(&mut conn)
+        .await?;
+
+    let ballot = HashMap::from([(1, 1702502), (2, RON_ID)]);
+
+    // Each cast rewrites every ballot for the position, so give it some to rewrite first
+    for warwick_id in 1..=5 {
+        Vote::cast(warwick_id, position_id, None, &ballot, &mut conn).await?;
+    }
+
+    let mut first = pool.acquire().await?;
+    let mut second = pool.acquire().await?;
+    let mut third = pool.acquire().await?;
+
+    let (a, b, c) = tokio::join!(
+        Vote::cast(6, position_id, None, &ballot, &mut first),
+        Vote::cast(7, position_id, None, &ballot, &mut second),
+        Vote::cast(8, position_id, None, &ballot, &mut third),
+    );
+
+    assert!(a?.is_some());
+    assert!(b?.is_some());
+    assert!(c?.is_some());
+
+    let votes = Vote::for_election(election_id, &mut conn).await?;
+    assert_eq!(votes.iter().map(|vote| &vote.ballot_id).unique().count(), 8);
+
+    drop((first, second, third));
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ballots_cannot_be_linked_to_their_voters() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let election_id = start_election(&mut conn).await?;
+    let position_id = ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        true,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+
+    candidate(election_id, 1702502, "Alex")
+        .insert(&mut conn)
+        .await?;
+
+    let ballot = HashMap::from([(1, 1702502), (2, RON_ID)]);
+
+    for warwick_id in 1..=3 {
+        Vote::cast(warwick_id, position_id, None, &ballot, &mut conn).await?;
+    }
+
+    // Nothing about the ballots refers to the voters
+    let columns: Vec<String> = sqlx::query_scalar(
+        "SELECT column_name::TEXT FROM information_schema.columns WHERE table_name IN ('ballots', 'votes')",
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    assert!(!columns.iter().any(|column| column == "warwick_id"));
+
+    // Each voter was added in their own transaction, while the ballots were all last written
+    // together by another one
+    let voters: Vec<String> =
+        sqlx::query_scalar("SELECT xmin::TEXT FROM voters WHERE position_id = $1")
+            .bind(position_id)
+            .fetch_all(&mut conn)
+            .await?;
+    let ballots: Vec<String> =
+        sqlx::query_scalar("SELECT DISTINCT xmin::TEXT FROM ballots WHERE position_id = $1")
+            .bind(position_id)
+            .fetch_all(&mut conn)
+            .await?;
+    let votes: Vec<String> =
+        sqlx::query_scalar("SELECT DISTINCT xmin::TEXT FROM votes WHERE position_id = $1")
+            .bind(position_id)
+            .fetch_all(&mut conn)
+            .await?;
+
+    assert_eq!(voters.len(), 3);
+    assert_eq!(ballots.len(), 1);
+    assert_eq!(votes, ballots);
+    assert!(!voters.contains(&ballots[0]));
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ballots_are_published_with_their_receipt_codes() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let election_id = start_election(&mut conn).await?;
+    let position_id = ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        true,
+        CountingMethod::Irv,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+
+    candidate(election_id, 1702502, "Alex")
+        .insert(&mut conn)
+        .await?;
+
+    let ballot = HashMap::from([(1, 1702502), (2, RON_ID)]);
+    let receipt = Vote::cast(1, position_id, None, &ballot, &mut conn)
+        .await?
+        .unwrap();
+
+    Vote::cast(2, position_id, None, &ballot, &mut conn).await?;
+
+    assert_eq!(
+        Vote::get_receipt(&receipt.secret, position_id, &mut conn).await?,
+        Some(receipt.clone())
+    );
+
+    // Voters can calculate their code from what it commits to
+    let expected = format!(":{}:1702502,{}", position_id, RON_ID);
+    assert!(receipt.commitment.ends_with(&expected));
+    assert_eq!(
+        format!("{:x}", Sha256::digest(receipt.commitment.as_bytes())),
+        receipt.code
+    );
+
+    // Identical ballots still get different codes, and are published with their preferences
+    let ballots = Vote::published(position_id, &mut conn).await?;
+    assert_eq!(ballots.len(), 2);
+    assert_ne!(ballots[0].receipt, ballots[1].receipt);
+    assert!(ballots.iter().any(|ballot| ballot.receipt == receipt.code));
+    assert!(ballots
+        .windows(2)
+        .all(|pair| pair[0].receipt < pair[1].receipt));
+    assert!(ballots
+        .iter()
+        .all(|ballot| ballot.preferences == vec![String::from("Alex"), String::from(RON_NAME)]));
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn positions_keep_their_tie_break_settings() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let election_id = start_election(&mut conn).await?;
+    let first = ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        true,
+        CountingMethod::Irv,
+        TieBreak::Casting,
+        &mut conn,
+    )
+    .await?;
+    let second = ExecPosition::create(
+        election_id,
+        "Treasurer",
+        1,
+        true,
+        CountingMethod::Irv,
+        TieBreak::Lot,
+        &mut conn,
+    )
+    .await?;
+
+    assert!(ExecPosition::set_casting_vote(first, &[1702502, RON_ID], &mut conn).await?);
+
+    let positions = ExecPosition::for_election(election_id, &mut conn).await?;
+
+    assert_eq!(positions[0].tie_break, TieBreak::Casting);
+    assert_eq!(positions[0].casting_vote, vec![1702502, RON_ID]);
+    assert_eq!(positions[1].tie_break, TieBreak::Lot);
+    assert!(positions[1].casting_vote.is_empty());
+
+    assert_eq!(positions[1].id, second);
+
+    // Each position draws lots with its own seed, which is chosen when it is created
+    let seed = positions[1].lot_seed.clone();
+    assert_ne!(seed, positions[0].lot_seed);
+    assert_ne!(positions[1].lot_seed_hash(), seed);
+
+    // Neither opening and closing voting nor scheduling it changes the seed
+    ExecPosition::toggle_state(second, &mut conn).await?;
+    ExecPosition::toggle_state(second, &mut conn).await?;
+    ExecPosition::schedule(second, Some(i64::MAX - 1), Some(i64::MAX), &mut conn).await?;
+    ExecPosition::toggle_state(second, &mut conn).await?;
+
+    let position = ExecPosition::find(second, &mut conn).await?.unwrap();
+    assert_eq!(position.lot_seed, seed);
+    assert_eq!(position.lot_seed_hash(), positions[1].lot_seed_hash());
+
+    // Neither the tie-break nor the casting vote can change once the position has been voted for
+    let ballot = HashMap::from([(1, 1702502), (2, RON_ID)]);
+    Vote::cast(1, first, None, &ballot, &mut conn).await?;
+
+    assert!(!ExecPosition::set_casting_vote(first, &[RON_ID, 1702502], &mut conn).await?);
+    assert!(
+        !ExecPosition::update(
+            first,
+            "President",
+            1,
+            true,
+            CountingMethod::Irv,
+            TieBreak::Lot,
+            &mut conn
+        )
+        .await?
+    );
+    assert!(
+        ExecPosition::update(
+            first,
+            "Chair",
+            1,
+            true,
+            CountingMethod::Irv,
+            TieBreak::Casting,
+            &mut conn
+        )
+        .await?
+    );
+
+    let position = ExecPosition::find(first, &mut conn).await?.unwrap();
+    assert_eq!(position.tie_break, TieBreak::Casting);
+    assert_eq!(position.casting_vote, vec![1702502, RON_ID]);
+
+    // Casting votes can't be changed once the election is archived
+    Election::archive_current(&mut conn).await?;
+    assert!(!ExecPosition::set_casting_vote(first, &[RON_ID], &mut conn).await?);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn first_past_the_post_ballots_choose_a_single_candidate() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    UserRole::grant(None, 1, "member", &mut conn).await?;
+
+    let election_id = start_election(&mut conn).await?;
+    let position_id = ExecPosition::create(
+        election_id,
+        "President",
+        1,
+        false,
+        CountingMethod::Fptp,
+        TieBreak::Backwards,
+        &mut conn,
+    )
+    .await?;
+
+    for (warwick_id, name) in [(1702502, "Alex"), (1700000, "Bea")] {
+        candidate(election_id, warwick_id, name)
+            .insert(&mut conn)
+            .await?;
+
+        let nomination = Nomination {
+            position_id,
+            warwick_id,
+        };
+
+        assert!(nomination.insert(&mut conn).await?);
+    }
+
+    ExecPosition::toggle_state(position_id, &mut conn).await?;
+
+    let client = create_client(uuid).await;
+    let vote = |fields: &str| {
+        client
+            .post(format!("/election/vote/{}", position_id))
+            .header(ContentType::Form)
+            .private_cookie(Cookie::new("id", "1"))
+            .private_cookie(Cookie::new("name", "Dan"))
+            .private_cookie(Cookie::new("csrf_token", "token"))
+            .body(format!("csrf_token=token&{}", fields))
+            .dispatch()
+    };
+
+    let response = vote("1=1702502&2=1700000").await;
+    assert_eq!(response.status(), Status::SeeOther);
+    assert!(response
+        .cookies()
+        .get("_flash")
+        .unwrap()
+        .value()
+        .contains("Please choose a single candidate."));
+    assert!(Vote::for_election(election_id, &mut conn).await?.is_empty());
+
+    let response = vote("1=1700000").await;
+    assert_eq!(response.status(), Status::SeeOther);
+
+    let votes = Vote::for_election(election_id, &mut conn).await?;
+    assert_eq!(votes.len(), 1);
+    assert_eq!(votes[0].candidate_id, 1700000);
+
+    drop(client);
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use rocket::http::{ContentType, Cookie, Status};
+use rocket::local::asynchronous::{Client, LocalResponse};
+
+use crate::{cleanup_database, create_client, create_database};
+
+use blackboards::schema::{ImpersonationEntry, PersonalBest, UserRole};
+
+/// Submits a form as a site administrator, viewing the website as Sam if `impersonating`.
+async fn submit<'c>(
+    client: &'c Client,
+    uri: &'static str,
+    fields: &str,
+    impersonating: bool,
+) -> LocalResponse<'c> {
+    let mut request = client
+        .post(uri)
+        .header(ContentType::Form)
+        .private_cookie(Cookie::new("id", "1702502"))
+        .private_cookie(Cookie::new("name", "Alex"))
+        .private_cookie(Cookie::new("csrf_token", "token"))
+        .body(format!("csrf_token=token&{}", fields));
+
+    if impersonating {
+        request = request
+            .private_cookie(Cookie::new("impersonating_id", "1820900"))
+            .private_cookie(Cookie::new("impersonating_name", "Sam"));
+    }
+
+    request.dispatch().await
+}
+
+#[tokio::test]
+async fn impersonation_is_logged() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    ImpersonationEntry::insert(1702502, 1, "start", &mut conn).await?;
+    ImpersonationEntry::insert(1702502, 1, "stop", &mut conn).await?;
+
+    let entries: Vec<_> = ImpersonationEntry::get_results(&mut conn)
+        .await?
+        .into_iter()
+        .map(|entry| (entry.admin_id, entry.warwick_id, entry.action))
+        .collect();
+
+    let expected = vec![
+        (1702502, 1, String::from("stop")),
+        (1702502, 1, String::from("start")),
+    ];
+
+    assert_eq!(entries, expected);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn names_can_be_found_for_impersonation() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let name = ImpersonationEntry::find_name(2, &mut conn).await?;
+    assert_eq!(name.as_deref(), Some("James"));
+
+    let name = ImpersonationEntry::find_name(1702502, &mut conn).await?;
+    assert_eq!(name, None);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn nothing_can_be_changed_while_impersonating() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    UserRole::grant(None, 1702502, "site_admin", &mut conn).await?;
+    UserRole::grant(None, 1820900, "member", &mut conn).await?;
+
+    let client = create_client(uuid).await;
+    let fields = "lifts[squat]=200&show_pl=true&show_wl=true&show_other=true";
+
+    let response = submit(&client, "/pbs", fields, true).await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let bests = PersonalBest::find(1820900, "Sam", &mut conn).await?;
+    assert_eq!(bests.bests.get("squat"), None);
+
+    // Stopping is the only thing that can be done
+    let response = submit(&client, "/impersonate/stop", "warwick_id=1820900", true).await;
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/impersonate"));
+
+    let entries = ImpersonationEntry::get_results(&mut conn).await?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].action, "stop");
+
+    drop(client);
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn site_admins_cannot_be_impersonated() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    UserRole::grant(None, 1702502, "site_admin", &mut conn).await?;
+    UserRole::grant(None, 1234567, "site_admin", &mut conn).await?;
+
+    let client = create_client(uuid).await;
+
+    let response = submit(&client, "/impersonate/start", "warwick_id=1234567", false).await;
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/impersonate"));
+    assert!(response.cookies().get("impersonating_id").is_none());
+
+    // Anyone else can still be viewed as
+    let response = submit(&client, "/impersonate/start", "warwick_id=1820900", false).await;
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/sessions"));
+
+    let entries: Vec<_> = ImpersonationEntry::get_results(&mut conn)
+        .await?
+        .into_iter()
+        .map(|entry| entry.warwick_id)
+        .collect();
+
+    assert_eq!(entries, vec![1820900]);
+
+    drop(client);
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
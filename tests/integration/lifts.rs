@@ -0,0 +1,152 @@
+use crate::{cleanup_database, create_database};
+
+use blackboards::forms::BoardQuery;
+use blackboards::schema::{custom_types, lift, Lift, LiftRecord};
+
+fn log_press() -> Lift {
+    Lift {
+        position: 6,
+        ..Lift::new("log_press", "Log Press", "LOG", "Strongman")
+    }
+}
+
+fn record(warwick_id: i32, lift: &str, weight: f32) -> LiftRecord {
+    let day = chrono::NaiveDate::from_ymd_opt(2022, 10, 1).unwrap();
+    LiftRecord::single(warwick_id, lift, weight, custom_types::Date(day))
+}
+
+#[tokio::test]
+async fn the_catalogue_starts_with_the_original_lifts() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let lifts = Lift::get_results(&mut conn).await?;
+    let names: Vec<_> = lifts.iter().map(|lift| lift.name.as_str()).collect();
+
+    assert_eq!(
+        names,
+        vec!["squat", "bench", "deadlift", "snatch", "clean_and_jerk"]
+    );
+    assert!(lifts.iter().all(Lift::is_scored));
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_lifts_in_the_catalogue_can_be_recorded() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    assert!(record(2, "log_press", 80.0)
+        .insert(&mut conn)
+        .await
+        .is_err());
+
+    log_press().insert(&mut conn).await?;
+    record(2, "log_press", 80.0).insert(&mut conn).await?;
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn added_lifts_appear_on_their_own_board() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    log_press().insert(&mut conn).await?;
+    record(1, "log_press", 80.0).insert(&mut conn).await?;
+    record(2, "log_press", 100.0).insert(&mut conn).await?;
+    record(2, "log_press", 90.0).insert(&mut conn).await?;
+
+    let lifts = Lift::get_results(&mut conn).await?;
+    let bests = lift::BoardBest::get_results(&BoardQuery::default(), &mut conn).await?;
+    let boards = lift::boards(&lifts, &bests);
+
+    assert_eq!(boards.len(), 1);
+    assert_eq!(boards[0].name, "Strongman");
+
+    let lifters: Vec<_> = boards[0]
+        .lifters
+        .iter()
+        .map(|lifter| (lifter.warwick_id, lifter.bests[0]))
+        .collect();
+
+    assert_eq!(lifters, vec![(2, Some(100.0)), (1, Some(80.0))]);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recorded_and_scored_lifts_cannot_be_removed() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    assert!(!Lift::delete("squat", &mut conn).await?);
+
+    log_press().insert(&mut conn).await?;
+    record(2, "log_press", 80.0).insert(&mut conn).await?;
+    assert!(!Lift::delete("log_press", &mut conn).await?);
+
+    let mut yoke = log_press();
+    yoke.name = String::from("yoke");
+    yoke.insert(&mut conn).await?;
+    assert!(Lift::delete("yoke", &mut conn).await?);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn other_boards_only_show_lifters_who_chose_them() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    log_press().insert(&mut conn).await?;
+    record(2, "log_press", 100.0).insert(&mut conn).await?;
+    record(3, "log_press", 120.0).insert(&mut conn).await?;
+
+    let bests = lift::BoardBest::get_results(&BoardQuery::default(), &mut conn).await?;
+    let lifters: Vec<_> = bests.iter().map(|best| best.warwick_id).collect();
+
+    assert_eq!(lifters, vec![2]);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lifts_can_be_edited_but_not_moved_onto_the_scored_boards() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    log_press().insert(&mut conn).await?;
+
+    let renamed = Lift {
+        position: 2,
+        ..Lift::new("log_press", "Axle Log Press", "AXL", "Strongman")
+    };
+
+    assert!(renamed.update(&mut conn).await?);
+    assert_eq!(Lift::find("log_press", &mut conn).await?, Some(renamed));
+
+    let moved = Lift::new("log_press", "Log Press", "LOG", lift::POWERLIFTING);
+    assert!(!moved.update(&mut conn).await?);
+
+    let moved = Lift::new("squat", "Squat", "SQ", "Strongman");
+    assert!(!moved.update(&mut conn).await?);
+
+    let renamed = Lift::new("squat", "Back Squat", "BS", lift::POWERLIFTING);
+    assert!(renamed.update(&mut conn).await?);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
@@ -1,10 +1,22 @@
+use std::collections::BTreeMap;
+
+use rocket::figment::Figment;
+use rocket::local::asynchronous::Client;
+use rocket::Config;
 use sqlx::pool::PoolConnection;
 use sqlx::{migrate::Migrator, pool::Pool, Postgres};
 use uuid::Uuid;
 
 use blackboards::schema::{custom_types, PersonalBest, Registration, Session};
 
+pub mod api_tokens;
+pub mod club_records;
+pub mod csrf;
+pub mod elections;
+pub mod impersonation;
+pub mod lifts;
 pub mod personal_bests;
+pub mod roles;
 pub mod sessions;
 
 static MIGRATOR: Migrator = sqlx::migrate!();
@@ -63,35 +75,49 @@ async fn insert_personal_bests(conn: &mut PoolConnection<Postgres>) -> sqlx::Res
         PersonalBest {
             warwick_id: 1,
             name: String::from("Dan"),
-            squat: Some(180.0),
-            bench: None,
-            deadlift: Some(210.0),
-            snatch: Some(45.0),
-            clean_and_jerk: None,
+            bests: BTreeMap::from([
+                (String::from("squat"), 180.0),
+                (String::from("deadlift"), 210.0),
+                (String::from("snatch"), 45.0),
+            ]),
             show_pl: true,
             show_wl: true,
+            show_other: true,
+            bodyweight: None,
+            sex: None,
+            weight_class: None,
+            verified: vec![],
         },
         PersonalBest {
             warwick_id: 2,
             name: String::from("James"),
-            squat: Some(150.0),
-            bench: Some(97.5),
-            deadlift: Some(175.0),
-            snatch: None,
-            clean_and_jerk: None,
+            bests: BTreeMap::from([
+                (String::from("squat"), 150.0),
+                (String::from("bench"), 97.5),
+                (String::from("deadlift"), 175.0),
+            ]),
             show_pl: true,
             show_wl: false,
+            show_other: true,
+            bodyweight: None,
+            sex: None,
+            weight_class: None,
+            verified: vec![],
         },
         PersonalBest {
             warwick_id: 3,
             name: String::from("Michael"),
-            squat: None,
-            bench: None,
-            deadlift: None,
-            snatch: Some(70.0),
-            clean_and_jerk: Some(95.0),
+            bests: BTreeMap::from([
+                (String::from("snatch"), 70.0),
+                (String::from("clean_and_jerk"), 95.0),
+            ]),
             show_pl: false,
             show_wl: true,
+            show_other: false,
+            bodyweight: None,
+            sex: None,
+            weight_class: None,
+            verified: vec![],
         },
     ];
 
@@ -144,6 +170,19 @@ async fn create_database() -> sqlx::Result<(Pool<Postgres>, Uuid)> {
     Ok((pool, uuid))
 }
 
+async fn create_client(uuid: Uuid) -> Client {
+    // Point the website at the test database
+    let url = format!("{}/{}", BASE_URL, get_test_db_name(uuid));
+
+    let config = Figment::from(Config::default())
+        .merge(("log_level", "off"))
+        .merge(("databases.blackboards.url", url));
+
+    Client::tracked(blackboards::build_rocket(config))
+        .await
+        .unwrap()
+}
+
 async fn cleanup_database(
     pool: Pool<Postgres>,
     conn: PoolConnection<Postgres>,
@@ -1,37 +1,76 @@
 use crate::{cleanup_database, create_database};
 
-use blackboards::schema::PersonalBest;
+use std::collections::BTreeMap;
+
+use blackboards::forms::{BoardQuery, BoardSort, PersonalBests};
+use blackboards::schema::{custom_types, lift, Lift, LiftRecord, PersonalBest, Preferences};
+use blackboards::scoring::{self, ScoredLifter, SinclairPeriod};
+
+fn dan() -> PersonalBest {
+    PersonalBest {
+        warwick_id: 1,
+        name: String::from("Dan"),
+        bests: BTreeMap::from([
+            (String::from("squat"), 180.0),
+            (String::from("deadlift"), 210.0),
+            (String::from("snatch"), 45.0),
+        ]),
+        show_pl: true,
+        show_wl: true,
+        show_other: true,
+        bodyweight: None,
+        sex: None,
+        weight_class: None,
+        verified: vec![],
+    }
+}
 
 #[tokio::test]
-async fn powerlifting_pbs_can_be_queried() -> sqlx::Result<()> {
+async fn all_pbs_can_be_queried() -> sqlx::Result<()> {
     let (pool, uuid) = create_database().await?;
     let mut conn = pool.acquire().await?;
 
-    let pbs = PersonalBest::get_pl(&mut conn).await?;
-    let expected = vec![
-        PersonalBest {
-            warwick_id: 1,
-            name: String::from("Dan"),
-            squat: Some(180.0),
-            bench: None,
-            deadlift: Some(210.0),
-            snatch: Some(45.0),
-            clean_and_jerk: None,
-            show_pl: true,
-            show_wl: true,
-        },
-        PersonalBest {
-            warwick_id: 2,
-            name: String::from("James"),
-            squat: Some(150.0),
-            bench: Some(97.5),
-            deadlift: Some(175.0),
-            snatch: None,
-            clean_and_jerk: None,
-            show_pl: true,
-            show_wl: false,
-        },
-    ];
+    let lifts = Lift::get_results(&mut conn).await?;
+    let pbs = PersonalBest::get_results(&BoardQuery::default(), &lifts, &mut conn).await?;
+    let expected = (
+        vec![
+            dan(),
+            PersonalBest {
+                warwick_id: 2,
+                name: String::from("James"),
+                bests: BTreeMap::from([
+                    (String::from("squat"), 150.0),
+                    (String::from("bench"), 97.5),
+                    (String::from("deadlift"), 175.0),
+                ]),
+                show_pl: true,
+                show_wl: false,
+                show_other: true,
+                bodyweight: None,
+                sex: None,
+                weight_class: None,
+                verified: vec![],
+            },
+        ],
+        vec![
+            dan(),
+            PersonalBest {
+                warwick_id: 3,
+                name: String::from("Michael"),
+                bests: BTreeMap::from([
+                    (String::from("snatch"), 70.0),
+                    (String::from("clean_and_jerk"), 95.0),
+                ]),
+                show_pl: false,
+                show_wl: true,
+                show_other: false,
+                bodyweight: None,
+                sex: None,
+                weight_class: None,
+                verified: vec![],
+            },
+        ],
+    );
 
     assert_eq!(expected, pbs);
 
@@ -41,37 +80,36 @@ async fn powerlifting_pbs_can_be_queried() -> sqlx::Result<()> {
 }
 
 #[tokio::test]
-async fn weightlifting_pbs_can_be_queried() -> sqlx::Result<()> {
+async fn boards_show_the_lifts_in_the_catalogue() -> sqlx::Result<()> {
     let (pool, uuid) = create_database().await?;
     let mut conn = pool.acquire().await?;
 
-    let pbs = PersonalBest::get_wl(&mut conn).await?;
-    let expected = vec![
-        PersonalBest {
-            warwick_id: 1,
-            name: String::from("Dan"),
-            squat: Some(180.0),
-            bench: None,
-            deadlift: Some(210.0),
-            snatch: Some(45.0),
-            clean_and_jerk: None,
-            show_pl: true,
-            show_wl: true,
-        },
-        PersonalBest {
-            warwick_id: 3,
-            name: String::from("Michael"),
-            squat: None,
-            bench: None,
-            deadlift: None,
-            snatch: Some(70.0),
-            clean_and_jerk: Some(95.0),
-            show_pl: false,
-            show_wl: true,
-        },
-    ];
+    let data = PersonalBests {
+        lifts: BTreeMap::new(),
+        show_pl: true,
+        show_wl: true,
+        show_other: false,
+        bodyweight: None,
+        bodyweight_recorded_on: None,
+        sex: None,
+        weight_class: None,
+        unit: None,
+        formula: None,
+    };
 
-    assert_eq!(expected, pbs);
+    PersonalBest::update(3, String::from("Michael"), data, &mut conn).await?;
+
+    // Michael has only done the Olympic lifts, so isn't shown for powerlifting
+    let mut lifts = Lift::get_results(&mut conn).await?;
+    let (pl, _) = PersonalBest::get_results(&BoardQuery::default(), &lifts, &mut conn).await?;
+    assert!(pl.iter().all(|pb| pb.warwick_id != 3));
+
+    let snatch = lifts.iter_mut().find(|lift| lift.name == "snatch").unwrap();
+    snatch.board = String::from(lift::POWERLIFTING);
+
+    let (pl, _) = PersonalBest::get_results(&BoardQuery::default(), &lifts, &mut conn).await?;
+    let pl: Vec<_> = pl.into_iter().map(|pb| pb.warwick_id).collect();
+    assert_eq!(pl, vec![1, 2, 3]);
 
     cleanup_database(pool, conn, uuid).await?;
 
@@ -79,63 +117,332 @@ async fn weightlifting_pbs_can_be_queried() -> sqlx::Result<()> {
 }
 
 #[tokio::test]
-async fn all_pbs_can_be_queried() -> sqlx::Result<()> {
+async fn updating_pbs_keeps_previous_records() -> sqlx::Result<()> {
     let (pool, uuid) = create_database().await?;
     let mut conn = pool.acquire().await?;
 
-    let pbs = PersonalBest::get_results(&mut conn).await?;
-    let expected = (
-        vec![
-            PersonalBest {
-                warwick_id: 1,
-                name: String::from("Dan"),
-                squat: Some(180.0),
-                bench: None,
-                deadlift: Some(210.0),
-                snatch: Some(45.0),
-                clean_and_jerk: None,
-                show_pl: true,
-                show_wl: true,
-            },
-            PersonalBest {
-                warwick_id: 2,
-                name: String::from("James"),
-                squat: Some(150.0),
-                bench: Some(97.5),
-                deadlift: Some(175.0),
-                snatch: None,
-                clean_and_jerk: None,
-                show_pl: true,
-                show_wl: false,
-            },
-        ],
-        vec![
-            PersonalBest {
-                warwick_id: 1,
-                name: String::from("Dan"),
-                squat: Some(180.0),
-                bench: None,
-                deadlift: Some(210.0),
-                snatch: Some(45.0),
-                clean_and_jerk: None,
-                show_pl: true,
-                show_wl: true,
-            },
-            PersonalBest {
-                warwick_id: 3,
-                name: String::from("Michael"),
-                squat: None,
-                bench: None,
-                deadlift: None,
-                snatch: Some(70.0),
-                clean_and_jerk: Some(95.0),
-                show_pl: false,
-                show_wl: true,
-            },
-        ],
+    let data = PersonalBests {
+        lifts: BTreeMap::from([
+            (String::from("squat"), Some(170.0)),
+            (String::from("bench"), Some(100.0)),
+            (String::from("deadlift"), None),
+        ]),
+        show_pl: true,
+        show_wl: true,
+        show_other: true,
+        bodyweight: Some(82.5),
+        bodyweight_recorded_on: None,
+        sex: Some(custom_types::Sex::Male),
+        weight_class: Some(String::from("83")),
+        unit: None,
+        formula: None,
+    };
+
+    PersonalBest::update(1, String::from("Dan"), data, &mut conn).await?;
+
+    // A lighter squat shouldn't replace the best, but the new bench should be shown
+    let pbs = PersonalBest::find(1, "Dan", &mut conn).await?;
+    assert_eq!(pbs.bests["squat"], 180.0);
+    assert_eq!(pbs.bests["bench"], 100.0);
+    assert_eq!(pbs.bodyweight, Some(82.5));
+    assert_eq!(pbs.sex, Some(custom_types::Sex::Male));
+    assert_eq!(pbs.weight_class.as_deref(), Some("83"));
+
+    let squats: Vec<_> = LiftRecord::for_user(1, &mut conn)
+        .await?
+        .into_iter()
+        .filter(|record| record.lift == "squat")
+        .map(|record| record.weight)
+        .collect();
+
+    assert_eq!(squats, vec![180.0, 170.0]);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_singles_count_towards_pbs() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let record = LiftRecord {
+        id: 0,
+        warwick_id: 2,
+        lift: String::from("bench"),
+        weight: 120.0,
+        reps: Some(3),
+        rpe: None,
+        recorded_on: custom_types::Date(chrono::NaiveDate::from_ymd_opt(2022, 10, 1).unwrap()),
+        note: Some(String::from("Triple")),
+        video_url: None,
+        status: custom_types::LiftStatus::Pending,
+    };
+
+    record.insert(&mut conn).await?;
+
+    let pbs = PersonalBest::find(2, "James", &mut conn).await?;
+    assert_eq!(pbs.bests["bench"], 97.5);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn boards_can_be_sorted_by_a_lift() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let query = BoardQuery {
+        pl_sort: Some(BoardSort::Lift(String::from("squat"))),
+        wl_sort: Some(BoardSort::Lift(String::from("snatch"))),
+        ..BoardQuery::default()
+    };
+
+    let lifts = Lift::get_results(&mut conn).await?;
+    let (pl, wl) = PersonalBest::get_results(&query, &lifts, &mut conn).await?;
+
+    // James has the heaviest total, but Dan has the heaviest squat
+    let mut pl: Vec<_> = pl
+        .into_iter()
+        .map(|pb| ScoredLifter::powerlifting(pb, &lifts))
+        .collect();
+    let mut wl: Vec<_> = wl
+        .into_iter()
+        .map(|pb| ScoredLifter::weightlifting(pb, &lifts, SinclairPeriod::default()))
+        .collect();
+
+    scoring::rank(&mut pl, &query.pl_sort(&lifts));
+    scoring::rank(&mut wl, &query.wl_sort(&lifts));
+
+    let pl: Vec<_> = pl
+        .into_iter()
+        .map(|lifter| lifter.personal_best.warwick_id)
+        .collect();
+    let wl: Vec<_> = wl
+        .into_iter()
+        .map(|lifter| lifter.personal_best.warwick_id)
+        .collect();
+
+    assert_eq!(pl, vec![1, 2]);
+    assert_eq!(wl, vec![3, 1]);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn boards_can_be_filtered_by_category_and_class() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let mut data = PersonalBests {
+        lifts: BTreeMap::new(),
+        show_pl: true,
+        show_wl: true,
+        show_other: false,
+        bodyweight: None,
+        bodyweight_recorded_on: None,
+        sex: Some(custom_types::Sex::Female),
+        weight_class: Some(String::from("63")),
+        unit: None,
+        formula: None,
+    };
+
+    PersonalBest::update(1, String::from("Dan"), data.clone(), &mut conn).await?;
+
+    data.show_wl = false;
+    data.weight_class = Some(String::from("69"));
+    PersonalBest::update(2, String::from("James"), data, &mut conn).await?;
+
+    let query = BoardQuery {
+        sex: Some(custom_types::Sex::Female),
+        ..BoardQuery::default()
+    };
+
+    let lifts = Lift::get_results(&mut conn).await?;
+    let (pl, wl) = PersonalBest::get_results(&query, &lifts, &mut conn).await?;
+    assert_eq!(pl.len(), 2);
+    assert_eq!(wl.len(), 1);
+
+    let query = BoardQuery {
+        class: Some(String::from("63")),
+        ..BoardQuery::default()
+    };
+
+    let (pl, _) = PersonalBest::get_results(&query, &lifts, &mut conn).await?;
+    let pl: Vec<_> = pl.into_iter().map(|pb| pb.warwick_id).collect();
+    assert_eq!(pl, vec![1]);
+
+    let query = BoardQuery {
+        sex: Some(custom_types::Sex::Male),
+        ..BoardQuery::default()
+    };
+
+    let (pl, _) = PersonalBest::get_results(&query, &lifts, &mut conn).await?;
+    assert!(pl.is_empty());
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lifts_can_be_verified_or_rejected() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let record = LiftRecord {
+        id: 0,
+        warwick_id: 2,
+        lift: String::from("squat"),
+        weight: 250.0,
+        reps: None,
+        rpe: None,
+        recorded_on: custom_types::Date(chrono::NaiveDate::from_ymd_opt(2022, 10, 1).unwrap()),
+        note: None,
+        video_url: Some(String::from("https://example.com/squat")),
+        status: custom_types::LiftStatus::Pending,
+    };
+
+    record.insert(&mut conn).await?;
+
+    let pending: Vec<_> = LiftRecord::pending(&mut conn)
+        .await?
+        .into_iter()
+        .filter(|lift| lift.video_url.is_some())
+        .collect();
+
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].name.as_deref(), Some("James"));
+
+    // Coaches can't review their own lifts
+    let id = pending[0].id;
+    let rejected = custom_types::LiftStatus::Rejected;
+
+    assert!(!LiftRecord::review(id, 2, rejected, &mut conn).await?);
+    assert_eq!(
+        PersonalBest::find(2, "James", &mut conn).await?.bests["squat"],
+        250.0
     );
 
-    assert_eq!(expected, pbs);
+    assert!(LiftRecord::review(id, 1, rejected, &mut conn).await?);
+    assert!(!LiftRecord::review(id, 1, rejected, &mut conn).await?);
+
+    let pbs = PersonalBest::find(2, "James", &mut conn).await?;
+    assert_eq!(pbs.bests["squat"], 150.0);
+    assert!(pbs.verified.is_empty());
+
+    // Verifying the best remaining squat marks it as verified on the board
+    let squat = LiftRecord::for_user(2, &mut conn)
+        .await?
+        .into_iter()
+        .find(|record| record.lift == "squat" && record.weight == 150.0)
+        .unwrap();
+
+    let verified = custom_types::LiftStatus::Verified;
+    assert!(LiftRecord::review(squat.id, 1, verified, &mut conn).await?);
+
+    let pbs = PersonalBest::find(2, "James", &mut conn).await?;
+    assert_eq!(pbs.verified, vec![String::from("squat")]);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unit_preferences_default_to_kilograms() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    assert_eq!(
+        Preferences::find(1, &mut conn).await?.unit,
+        custom_types::Unit::Kg
+    );
+
+    let preferences = Preferences {
+        warwick_id: 1,
+        unit: custom_types::Unit::Lb,
+        formula: custom_types::Formula::Brzycki,
+    };
+
+    preferences.save(&mut conn).await?;
+
+    let preferences = Preferences {
+        warwick_id: 2,
+        ..Preferences::default()
+    };
+
+    preferences.save(&mut conn).await?;
+
+    assert_eq!(
+        Preferences::find(1, &mut conn).await?.unit,
+        custom_types::Unit::Lb
+    );
+    assert_eq!(
+        Preferences::find(1, &mut conn).await?.formula,
+        custom_types::Formula::Brzycki
+    );
+    assert_eq!(
+        Preferences::find(2, &mut conn).await?.unit,
+        custom_types::Unit::Kg
+    );
+
+    // Weights are stored in kilograms and only converted for display
+    let pbs = PersonalBest::find(2, "James", &mut conn)
+        .await?
+        .in_unit(custom_types::Unit::Lb);
+
+    assert_eq!(pbs.bests["squat"], 330.7);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn rep_maxes_keep_the_heaviest_set_for_each_number_of_reps() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let sets = [
+        (100.0, Some(5), None),
+        (95.0, Some(5), Some(8.0)),
+        (110.0, Some(3), Some(9.0)),
+        (80.0, Some(12), None),
+    ];
+
+    for (weight, reps, rpe) in sets {
+        let record = LiftRecord {
+            id: 0,
+            warwick_id: 2,
+            lift: String::from("bench"),
+            weight,
+            reps,
+            rpe,
+            recorded_on: custom_types::Date(chrono::NaiveDate::from_ymd_opt(2022, 10, 1).unwrap()),
+            note: None,
+            video_url: None,
+            status: custom_types::LiftStatus::Pending,
+        };
+
+        record.insert(&mut conn).await?;
+    }
+
+    let rep_maxes = LiftRecord::rep_maxes(Some(2), &mut conn).await?;
+    let bench: Vec<_> = rep_maxes
+        .iter()
+        .filter(|rep_max| rep_max.lift == "bench" && rep_max.reps > 1)
+        .map(|rep_max| (rep_max.reps, rep_max.weight, rep_max.rpe))
+        .collect();
+
+    // Sets of more than 10 reps are not rep maxes
+    assert_eq!(bench, vec![(3, 110.0, Some(9.0)), (5, 100.0, None)]);
+    assert!(rep_maxes.iter().all(|rep_max| rep_max.warwick_id == 2));
 
     cleanup_database(pool, conn, uuid).await?;
 
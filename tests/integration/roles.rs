@@ -0,0 +1,98 @@
+use crate::{cleanup_database, create_database};
+
+use blackboards::schema::{custom_types, Membership, RoleAuditEntry, UserRole};
+use blackboards::su_export::ExportedMember;
+
+#[tokio::test]
+async fn roles_can_be_granted_and_revoked() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    UserRole::grant(Some(1702502), 1820900, "member", &mut conn).await?;
+    UserRole::grant(Some(1702502), 1820900, "election_admin", &mut conn).await?;
+
+    let mut roles = UserRole::roles_for(1820900, &mut conn).await?;
+    roles.sort();
+
+    assert_eq!(roles, vec!["election_admin", "member"]);
+
+    UserRole::revoke(Some(1702502), 1820900, "member", &mut conn).await?;
+
+    let roles = UserRole::roles_for(1820900, &mut conn).await?;
+    assert_eq!(roles, vec!["election_admin"]);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_roles_cannot_be_granted() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let result = UserRole::grant(Some(1702502), 1820900, "unknown", &mut conn).await;
+    assert!(result.is_err());
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn role_changes_are_audited() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    UserRole::grant(Some(1702502), 1820900, "member", &mut conn).await?;
+    UserRole::grant(Some(1702502), 1820900, "member", &mut conn).await?;
+    UserRole::revoke(None, 1820900, "member", &mut conn).await?;
+
+    let entries: Vec<_> = RoleAuditEntry::get_results(&mut conn)
+        .await?
+        .into_iter()
+        .map(|entry| (entry.actor_id, entry.warwick_id, entry.action))
+        .collect();
+
+    // Granting the role twice should only be recorded once
+    let expected = vec![
+        (None, 1820900, String::from("revoke")),
+        (Some(1702502), 1820900, String::from("grant")),
+    ];
+
+    assert_eq!(entries, expected);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn current_memberships_grant_the_member_role() -> sqlx::Result<()> {
+    let (pool, uuid) = create_database().await?;
+    let mut conn = pool.acquire().await?;
+
+    let today = chrono::Local::today().naive_local();
+    let exported = [ExportedMember {
+        warwick_id: 1820900,
+        membership_type: String::from("Standard"),
+    }];
+
+    // Record a membership that has already expired
+    let expired = custom_types::Date(today - chrono::Duration::days(1));
+    Membership::import(&exported, expired, expired, "test", &mut conn).await?;
+
+    let roles = UserRole::roles_for(1820900, &mut conn).await?;
+    assert!(roles.is_empty());
+
+    // Record a membership that is valid today
+    let current = custom_types::Date(today);
+    Membership::import(&exported, current, current, "test", &mut conn).await?;
+
+    let roles = UserRole::roles_for(1820900, &mut conn).await?;
+    assert_eq!(roles, vec!["member"]);
+
+    cleanup_database(pool, conn, uuid).await?;
+
+    Ok(())
+}